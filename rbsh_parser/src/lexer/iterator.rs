@@ -2,10 +2,22 @@ use super::{Lexer, Location};
 use crate::{Result, Token, TokenKind};
 use std::iter::Iterator;
 
+// A comment or a blank line, which is not a part of commands but kept for formatting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trivia {
+    Comment(String, bool), // text after '#', trailing a command on the same line?
+    BlankLine,
+}
+
 #[derive(Debug)]
 pub struct LexerIterator {
     lexer: Lexer,
     peeked: Option<Option<Result<Token>>>,
+    trivia: Vec<Trivia>,
+    started: bool,    // any token other than spaces and newlines has been read
+    line_start: bool, // no token other than spaces has been read in the line
+    newlines: usize,  // the number of newlines since the last token
+    end: usize,       // the byte offset after the last token taken
 }
 
 impl Lexer {
@@ -13,6 +25,11 @@ impl Lexer {
         LexerIterator {
             lexer: self,
             peeked: None,
+            trivia: Vec::new(),
+            started: false,
+            line_start: true,
+            newlines: 0,
+            end: 0,
         }
     }
 }
@@ -21,33 +38,91 @@ impl Iterator for LexerIterator {
     type Item = Result<Token>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.peeked.take() {
+        let result = match self.peeked.take() {
             Some(v) => v,
-            None => self.lexer.lex(),
+            None => self.lex(),
+        };
+        if let Some(Ok(token)) = &result {
+            self.end = token.location.end;
         }
+        result
     }
 }
 
 impl LexerIterator {
+    // Comments and blank lines are skipped, and recorded to be taken by the parser.
+    fn lex(&mut self) -> Option<Result<Token>> {
+        loop {
+            let newlines = self.lexer.newlines();
+            let result = self.lexer.lex();
+            let kind = match &result {
+                Some(Ok(token)) => &token.value,
+                _ => return result,
+            };
+
+            match kind {
+                TokenKind::Space => (),
+                // A token has consecutive newlines.
+                TokenKind::NewLine => {
+                    self.newlines += self.lexer.newlines() - newlines;
+                    self.line_start = true;
+                }
+                _ => {
+                    if self.started && self.newlines > 1 {
+                        self.trivia.push(Trivia::BlankLine);
+                    }
+                    self.newlines = 0;
+                    self.started = true;
+                    if let TokenKind::Comment(text) = kind {
+                        self.trivia
+                            .push(Trivia::Comment(text.to_string(), !self.line_start));
+                        continue;
+                    }
+                    self.line_start = false;
+                }
+            }
+            return result;
+        }
+    }
+
     pub fn peek(&mut self) -> Option<&Result<Token>> {
-        let lexer = &mut self.lexer;
-        self.peeked.get_or_insert_with(|| lexer.lex()).as_ref()
+        if self.peeked.is_none() {
+            self.peeked = Some(self.lex());
+        }
+        self.peeked.as_ref().unwrap().as_ref()
+    }
+
+    // Takes the comments and blank lines which have been read.
+    pub fn take_trivia(&mut self) -> Vec<Trivia> {
+        std::mem::take(&mut self.trivia)
     }
 
     pub fn next_if<F>(&mut self, f: F) -> Option<Result<Token>>
     where
         F: FnOnce(&TokenKind) -> bool,
     {
+        let end = self.end;
         match self.next() {
             Some(Ok(token)) if f(&token.value) => Some(Ok(token)),
             Some(Err(e)) => Some(Err(e)),
             other => {
+                self.end = end;
                 self.peeked = Some(other);
                 None
             }
         }
     }
 
+    // Returns the byte offset after the last token taken.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    // Returns the text of the input at the location of a token.
+    pub fn source(&self, location: &Location) -> Option<&str> {
+        self.lexer.input.get(location.range())
+    }
+
     pub fn skip_if_space(&mut self) -> Result<bool> {
         match self.next_if(|token| token == &TokenKind::Space) {
             None => Ok(false),
@@ -106,6 +181,39 @@ mod test {
         assert_eq!(iter.next(), None);
     }
 
+    #[test]
+    fn skip_comment() {
+        let mut iter = Lexer::new("# abc\nxyz # ghi", 0).iter();
+        assert_eq!(iter.next(), Some(Ok(Token::newline(location!(6)))));
+        assert_eq!(
+            iter.next(),
+            Some(Ok(Token::word("xyz", WordKind::Normal, location!(1, 2))))
+        );
+        assert_eq!(iter.next(), Some(Ok(Token::space(location!(4, 2)))));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn trivia() {
+        let mut iter = Lexer::new("# abc\nxyz # def\n\n\n# ghi\nuvw", 0).iter();
+        while iter.next().is_some() {}
+        assert_eq!(
+            iter.take_trivia(),
+            vec![
+                Trivia::Comment(" abc".to_string(), false),
+                Trivia::Comment(" def".to_string(), true),
+                Trivia::BlankLine,
+                Trivia::Comment(" ghi".to_string(), false),
+            ]
+        );
+        assert_eq!(iter.take_trivia(), vec![]);
+
+        // The lines of a here-document are not blank lines.
+        let mut iter = Lexer::new("cat <<EOF\n\nEOF\nxyz", 0).iter();
+        while iter.next().is_some() {}
+        assert_eq!(iter.take_trivia(), vec![]);
+    }
+
     #[test]
     fn peek() {
         let mut iter = Lexer::new("abc", 0).iter();
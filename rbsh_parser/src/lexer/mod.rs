@@ -1,25 +1,35 @@
 mod iterator;
 mod reader;
+mod substitution;
 mod utils;
 
-pub use iterator::LexerIterator;
+pub use iterator::{LexerIterator, Trivia};
 
-use crate::{error::Error, location::Location, Result, Token, TokenKind, WordKind};
+use crate::{error::Error, location::Location, Result, Token, TokenKind, Word, WordKind};
 use reader::Reader;
 use utils::*;
 
 #[derive(Debug)]
 pub struct Lexer {
+    input: String, // from which the source of a word is taken
     reader: Reader,
     quoted_word_location: Option<Location>,
     before_token: Option<TokenKind>,
     head: bool,
     statement: Option<Statement>,
+    brace_depth: usize,
+    group_level: usize,
 }
 
 #[derive(Debug)]
 enum Statement {
     For,
+    Select,
+    Case,
+    Coproc,     // coproc [NAME] command
+    CoprocName, // NAME of coproc NAME compound-command
+    Time,       // time [-p]
+    Function,   // function NAME, after which '{' starts the body
 }
 
 macro_rules! token {
@@ -43,14 +53,21 @@ macro_rules! token {
 impl Lexer {
     pub fn new(input: &str, line: usize) -> Self {
         Lexer {
+            input: input.to_string(),
             reader: Reader::new(input, line),
             quoted_word_location: None,
             before_token: None,
             head: true,
             statement: None,
+            brace_depth: 0,
+            group_level: 0,
         }
     }
 
+    pub(crate) fn newlines(&self) -> usize {
+        self.reader.newlines()
+    }
+
     pub fn location(&self) -> Location {
         self.reader.location()
     }
@@ -66,6 +83,7 @@ impl Lexer {
 
         self.reader.peek().cloned().map(|c| {
             let token = match c {
+                _ if self.quoted_word_location.is_some() => self.quoted_word(),
                 _ if is_space(&c) => self.space(),
                 _ if is_newline(&c) => self.newline(),
                 _ if is_number(&c) => self.number(),
@@ -92,22 +110,78 @@ impl Lexer {
                     self.statement = Some(Statement::For);
                     keyword!("for")
                 }
-                _ if matches!(self.statement, Some(Statement::For)) && self.starts_with("in") => {
+                _ if self.head && self.starts_with("select") => {
+                    self.statement = Some(Statement::Select);
+                    keyword!("select")
+                }
+                _ if self.head && self.starts_with("case") => {
+                    self.statement = Some(Statement::Case);
+                    keyword!("case")
+                }
+                _ if self.head && self.starts_with("esac") => keyword!("esac"),
+                _ if self.head && self.starts_with("when") => {
+                    self.statement = None;
+                    keyword!("when")
+                }
+                _ if self.head && self.starts_with("function") => {
+                    self.statement = Some(Statement::Function);
+                    keyword!("function")
+                }
+                _ if self.head && self.starts_with("def") => keyword!("def"),
+                _ if self.head && self.starts_with("coproc") => {
+                    self.statement = Some(Statement::Coproc);
+                    keyword!("coproc")
+                }
+                _ if self.head && self.starts_with("!") => keyword!("!"),
+                _ if self.head && self.starts_with("time") => {
+                    self.statement = Some(Statement::Time);
+                    keyword!("time")
+                }
+                _ if matches!(self.statement, Some(Statement::Time)) && self.starts_with("-p") => {
+                    self.normal_word()
+                }
+                _ if self.head && self.starts_with("[[") => self.conditional_command(),
+                _ if matches!(self.statement, Some(Statement::Coproc)) && self.is_coproc_name() => {
+                    self.statement = Some(Statement::CoprocName);
+                    self.normal_word()
+                }
+                _ if matches!(
+                    self.statement,
+                    Some(Statement::For) | Some(Statement::Select) | Some(Statement::Case)
+                )
+                    && self.starts_with("in") =>
+                {
                     self.statement = None;
                     keyword!("in")
                 }
 
-                ';' => self.termination(),
+                ';' => self.semicolon(),
                 '&' => self.ampersand(),
                 '#' => self.comment(),
                 '|' => self.vertical_line(),
                 '<' => self.less_than(),
                 '>' => self.greater_than(),
-                '{' => self.group_start(),
-                '}' => self.group_end(),
+                '{' if self.head => {
+                    self.group_level += 1;
+                    self.group_start()
+                }
+                '}' if self.brace_depth == 0 && self.group_level > 0 => {
+                    self.group_level -= 1;
+                    self.group_end()
+                }
+                '(' if self.reader.peek_nth(1) == Some(&'(')
+                    && (self.head || matches!(self.statement, Some(Statement::For))) =>
+                {
+                    self.statement = None;
+                    self.arithmetic_command()
+                }
+                '(' if matches!(&self.before_token, Some(TokenKind::Word(s, WordKind::Normal, _)) if s.ends_with('=')) => {
+                    self.array()
+                }
+                '(' => self.subshell_start(),
+                ')' => self.subshell_end(),
                 '-' if matches!(self.before_token, Some(TokenKind::Number { .. })) => self.hyphen(),
 
-                _ if self.quoted_word_location.is_some() => self.quoted_word(),
                 '"' => self.quoted_word(),
                 '$' => self.dollar_word(),
                 _ => self.normal_word(),
@@ -117,13 +191,35 @@ impl Lexer {
                 let kind = token.clone().unwrap().value;
                 self.head = match kind {
                     TokenKind::Space => self.head,
+                    TokenKind::Word(..)
+                        if matches!(
+                            self.statement,
+                            Some(Statement::CoprocName | Statement::Function)
+                        ) =>
+                    {
+                        true
+                    }
+                    TokenKind::Word(ref s, ..)
+                        if matches!(self.statement, Some(Statement::Time)) && s == "-p" =>
+                    {
+                        true
+                    }
                     TokenKind::NewLine
                     | TokenKind::Termination
+                    | TokenKind::CaseBreak
+                    | TokenKind::CaseFallThrough
+                    | TokenKind::CaseContinue
                     | TokenKind::And
+                    | TokenKind::Or
                     | TokenKind::Comment { .. }
                     | TokenKind::Pipe
+                    | TokenKind::PipeBoth
                     | TokenKind::GroupStart
                     | TokenKind::GroupEnd
+                    | TokenKind::SubshellStart
+                    | TokenKind::SubshellEnd
+                    | TokenKind::Arithmetic(_)
+                    | TokenKind::Conditional(_)
                     | TokenKind::If
                     | TokenKind::Then
                     | TokenKind::Else
@@ -135,13 +231,38 @@ impl Lexer {
                     | TokenKind::While
                     | TokenKind::Until
                     | TokenKind::Do
-                    | TokenKind::Done => true,
+                    | TokenKind::Done
+                    | TokenKind::Esac
+                    | TokenKind::Coproc
+                    | TokenKind::Bang
+                    | TokenKind::Time => true,
                     _ => false,
                 };
 
+                if !matches!(kind, TokenKind::Word(..)) {
+                    self.brace_depth = 0;
+                }
+                if !matches!(
+                    kind,
+                    TokenKind::Space | TokenKind::Coproc | TokenKind::Time | TokenKind::Function
+                ) && matches!(
+                    self.statement,
+                    Some(
+                        Statement::Coproc
+                            | Statement::CoprocName
+                            | Statement::Time
+                            | Statement::Function
+                    )
+                )
+                {
+                    self.statement = None;
+                }
                 self.before_token = Some(kind);
             }
-            token
+            token.map(|mut token| {
+                token.location.end = self.reader.location().offset;
+                token
+            })
         })
     }
 
@@ -150,6 +271,8 @@ impl Lexer {
     token!(termination, is_termination);
     token!(group_start);
     token!(group_end);
+    token!(subshell_start);
+    token!(subshell_end);
     token!(hyphen);
 
     fn number(&mut self) -> Result<Token> {
@@ -178,6 +301,25 @@ impl Lexer {
         }
     }
 
+    fn semicolon(&mut self) -> Result<Token> {
+        let location = self.reader.location();
+
+        match self.reader.peek_nth(1) {
+            Some(';') => {
+                self.reader.skip(2);
+                match self.reader.next_if(|c| c == &'&') {
+                    Some(_) => Ok(Token::case_continue(location)), // ;;&
+                    None => Ok(Token::case_break(location)),       // ;;
+                }
+            }
+            Some('&') => {
+                self.reader.skip(2);
+                Ok(Token::case_fall_through(location)) // ;&
+            }
+            _ => self.termination(), // ;
+        }
+    }
+
     fn ampersand(&mut self) -> Result<Token> {
         let location = self.reader.location();
         self.reader.next(); // remove '&'
@@ -218,11 +360,15 @@ impl Lexer {
     fn less_than(&mut self) -> Result<Token> {
         let location = self.reader.location();
         self.reader.next(); // remove '<'
+        if self.reader.peek() == Some(&'(') {
+            return self.word(WordKind::InputProcess, |c| c == &')', true, true, false);
+            // <(
+        }
 
         match self.reader.next_if(|c| c == &'<' || c == &'&' || c == &'>') {
             Some('<') => match self.reader.next_if(|c| c == &'<') {
                 Some('<') => Ok(Token::here_string(location)), // <<<
-                _ => Ok(Token::here_document(location)),       // <<
+                _ => self.here_document(location),             // <<
             },
             Some('&') => match self.reader.next_if(|c| c == &'-') {
                 Some('-') => Ok(Token::read_close(location)), // <&-
@@ -236,15 +382,19 @@ impl Lexer {
     fn greater_than(&mut self) -> Result<Token> {
         let location = self.reader.location();
         self.reader.next(); // remove '>'
+        if self.reader.peek() == Some(&'(') {
+            return self.word(WordKind::OutputProcess, |c| c == &')', true, true, false);
+            // >(
+        }
 
         match self.reader.next_if(|c| c == &'&' || c == &'|' || c == &'>') {
             Some('&') => match self.reader.next_if(|c| c == &'-') {
                 Some('-') => Ok(Token::write_close(location)), // >&-
                 _ => {
-                    if matches!(self.reader.peek(), Some(c) if is_number(c))
+                    if matches!(self.reader.peek(), Some(c) if is_number(c) || c == &'$')
                         || matches!(self.before_token, Some(TokenKind::Number { .. }))
                     {
-                        Ok(Token::write_copy(location)) // m>&n
+                        Ok(Token::write_copy(location)) // m>&n, >&$n
                     } else {
                         Ok(Token::write_both(location)) // >&
                     }
@@ -256,6 +406,101 @@ impl Lexer {
         }
     }
 
+    fn here_document(&mut self, location: Location) -> Result<Token> {
+        let strip_tabs = self.reader.next_if(|c| c == &'-').is_some(); // <<-
+        while self.reader.next_if(is_space).is_some() {}
+
+        let (delimiter, quoted) = self.here_document_delimiter()?;
+        let (body, body_location) = self
+            .reader
+            .here_document(&delimiter, strip_tabs)
+            .ok_or_else(|| Error::eof(self.location()))?;
+
+        let body = if quoted {
+            vec![Word::new(body, WordKind::Quote, body_location)]
+        } else {
+            Lexer::new(&body, body_location.line - 1).here_document_body()?
+        };
+        Ok(Token::here_document(body, location))
+    }
+
+    fn here_document_delimiter(&mut self) -> Result<(String, bool)> {
+        let location = self.reader.location();
+        let mut delimiter = String::new();
+        let mut quoted = false;
+
+        while let Some(c) = self.reader.next_if(|c| !is_here_document_delimiter(c)) {
+            match c {
+                '\'' | '"' => {
+                    quoted = true;
+                    loop {
+                        match self.reader.next() {
+                            Some(q) if q == c => break,
+                            Some('\\') if c == '"' => {
+                                if let Some(e) = self.reader.next() {
+                                    delimiter.push(e)
+                                }
+                            }
+                            Some(e) => delimiter.push(e),
+                            None => return Err(Error::unterminated_string(location)),
+                        }
+                    }
+                }
+                '\\' => {
+                    quoted = true;
+                    if let Some(e) = self.reader.next() {
+                        delimiter.push(e)
+                    }
+                }
+                _ => delimiter.push(c),
+            }
+        }
+
+        if delimiter.is_empty() && !quoted {
+            match self.lex() {
+                Some(Ok(token)) => Err(Error::unexpected_token(&token)),
+                Some(Err(e)) => Err(e),
+                None => Err(Error::eof(self.location())),
+            }
+        } else {
+            Ok((delimiter, quoted))
+        }
+    }
+
+    // Expands only parameters, command substitutions and backslash escapes, like double quotes.
+    fn here_document_body(&mut self) -> Result<Vec<Word>> {
+        let mut result = vec![];
+        while let Some(c) = self.reader.peek().cloned() {
+            let token = match c {
+                '$' => self.dollar_word()?,
+                '`' => self.word(WordKind::Command, is_back_quote, true, true, false)?,
+                _ => {
+                    let location = self.reader.location();
+                    let mut s = String::new();
+                    while let Some(c) = self.reader.next_if(|c| c != &'$' && c != &'`') {
+                        match c {
+                            '\\' => match self.reader.peek() {
+                                Some('$' | '`' | '\\') => s.push(self.reader.next().unwrap()),
+                                Some('\n') => {
+                                    self.reader.next();
+                                }
+                                _ => s.push(c),
+                            },
+                            _ => s.push(c),
+                        }
+                    }
+                    Token::word(s, WordKind::Normal, location)
+                }
+            };
+
+            match token.value {
+                TokenKind::Word(s, k, _) => result.push(Word::new(s, k, token.location)),
+                _ => unreachable![],
+            }
+        }
+        Ok(result)
+    }
+
     fn quoted_word(&mut self) -> Result<Token> {
         macro_rules! error_unterminated_string {
             () => {{
@@ -268,48 +513,54 @@ impl Lexer {
             }};
         }
 
-        match self.reader.next_if(is_double_quote) {
-            Some(_) => {
-                let location = self.reader.location();
-                self.quoted_word_location = Some(location);
-                self.quoted_word()
-            }
-            None => match self.reader.peek() {
-                Some(&'$') => self.dollar_word(),
-                Some(_) => self.word(
-                    WordKind::Normal,
+        if self.quoted_word_location.is_none() {
+            self.reader.next(); // remove '"'
+            self.quoted_word_location = Some(self.reader.location());
+        }
+
+        match self.reader.peek() {
+            Some(&'$') => self.dollar_word().map(Token::into_quoted),
+            Some(_) => self
+                .word(
+                    WordKind::Quote,
                     |c| is_double_quote(c) || c == &'$',
                     false,
                     true,
                     false,
-                ),
-                None => error_unterminated_string![],
-            }
-            .and_then(|result| match self.reader.peek() {
-                Some(_) => {
-                    if self.reader.next_if(is_double_quote).is_some() {
-                        self.quoted_word_location = None;
-                    }
-                    Ok(result)
-                }
-                None => error_unterminated_string![],
-            }),
+                )
+                .map(Token::into_quoted),
+            None => error_unterminated_string![],
         }
+        .and_then(|result| match self.reader.peek() {
+            Some(_) => {
+                if self.reader.next_if(is_double_quote).is_some() {
+                    self.quoted_word_location = None;
+                }
+                Ok(result)
+            }
+            None => error_unterminated_string![],
+        })
     }
 
     fn dollar_word(&mut self) -> Result<Token> {
         let location = self.reader.location();
         self.reader.next(); // remove '$'
         match self.reader.peek() {
-            Some(&'{') => self.word(WordKind::Parameter, |c| c == &'}', true, true, false),
-            Some('(') => self.word(WordKind::Command, |c| c == &')', true, true, false),
-            Some('$') => {
+            // $'...' and $"..." are not quoted in double quotes.
+            Some('\'') if self.quoted_word_location.is_none() => self.ansi_c_quoted_word(),
+            Some('"') if self.quoted_word_location.is_none() => self.quoted_word(),
+            Some(&'{') => self.parameter(),
+            Some('(') if self.reader.peek_nth(1) == Some(&'(') => self
+                .arithmetic()
+                .map(|(expr, location)| Token::word(expr, WordKind::Arithmetic, location)),
+            Some('(') => self.command_substitution(),
+            Some(&c @ ('$' | '?' | '#' | '@' | '*')) => {
                 self.reader.next();
-                Ok(Token::word("$", WordKind::Variable, location))
+                Ok(Token::word(c.to_string(), WordKind::Variable, location))
             }
-            Some(c) if !c.is_ascii_punctuation() => self.word(
+            Some(c) if !is_variable_delimiter(c) => self.word(
                 WordKind::Variable,
-                |c| c.is_ascii_punctuation(),
+                is_variable_delimiter,
                 false,
                 false,
                 false,
@@ -318,14 +569,346 @@ impl Lexer {
         }
     }
 
+    // Reads $'...' without decoding the escape sequences. An escaped quote does not end it.
+    fn ansi_c_quoted_word(&mut self) -> Result<Token> {
+        let location = self.reader.location();
+        self.reader.next(); // remove '\''
+
+        let mut result = String::new();
+        loop {
+            match self.reader.next() {
+                None => return Err(Error::unterminated_string(location)),
+                Some('\'') => return Ok(Token::word(result, WordKind::AnsiC, location)),
+                Some('\\') => {
+                    result.push('\\');
+                    if let Some(c) = self.reader.next() {
+                        result.push(c);
+                    }
+                }
+                Some(c) => result.push(c),
+            }
+        }
+    }
+
+    // Reads ${...} up to the matching '}'. Nested ${...} and quoted strings are kept as is,
+    // so that they can be expanded later with parse_word().
+    fn parameter(&mut self) -> Result<Token> {
+        let location = self.reader.location();
+        self.reader.next(); // remove '{'
+
+        let mut result = String::new();
+        let mut depth = 0;
+        loop {
+            match self.reader.next() {
+                None => return Err(Error::unterminated_string(location)),
+                Some('\\') => match self.reader.next() {
+                    Some('}') => result.push('}'),
+                    Some(c) => {
+                        result.push('\\');
+                        result.push(c);
+                    }
+                    None => return Err(Error::unterminated_string(location)),
+                },
+                Some(q @ ('\'' | '"')) => {
+                    result.push(q);
+                    loop {
+                        match self.reader.next() {
+                            None => return Err(Error::unterminated_string(location)),
+                            Some('\\') if q == '"' => {
+                                result.push('\\');
+                                if let Some(c) = self.reader.next() {
+                                    result.push(c);
+                                }
+                            }
+                            Some(c) => {
+                                result.push(c);
+                                if c == q {
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                }
+                Some('{') => {
+                    depth += 1;
+                    result.push('{');
+                }
+                Some('}') if depth == 0 => break,
+                Some('}') => {
+                    depth -= 1;
+                    result.push('}');
+                }
+                Some(c) => result.push(c),
+            }
+        }
+
+        Ok(Token::word(result, WordKind::Parameter, location))
+    }
+
+    // Reads the rest of the input as a single word, e.g. the default value of ${var:-word}.
+    // Quoted parts and escaped characters are returned as WordKind::Quote.
+    pub(crate) fn parameter_word(&mut self) -> Result<Vec<Word>> {
+        let mut result = vec![];
+        let mut quoted = false;
+        while let Some(c) = self.reader.peek().cloned() {
+            let token = match c {
+                '"' => {
+                    quoted = !quoted;
+                    self.reader.next();
+                    continue;
+                }
+                '$' if matches!(self.reader.peek_nth(1), Some('\'' | '"')) => {
+                    let location = self.reader.location();
+                    self.reader.next();
+                    match (quoted, self.reader.peek()) {
+                        (true, _) => Token::word("$", WordKind::Quote, location),
+                        (false, Some('\'')) => self.ansi_c_quoted_word()?,
+                        (false, _) => continue, // $"..." is "..."
+                    }
+                }
+                '$' if quoted => self.dollar_word()?.into_quoted(),
+                '$' => self.dollar_word()?,
+                '`' => self.word(WordKind::Command, is_back_quote, true, true, false)?,
+                '\'' if !quoted => {
+                    self.word(WordKind::Quote, is_single_quote, true, false, false)?
+                }
+                '\\' => {
+                    let location = self.reader.location();
+                    self.reader.next();
+                    match self.reader.next() {
+                        Some(c) => Token::word(c.to_string(), WordKind::Quote, location),
+                        None => Token::word("\\", WordKind::Normal, location),
+                    }
+                }
+                _ => {
+                    let location = self.reader.location();
+                    let mut s = String::new();
+                    while let Some(c) = self
+                        .reader
+                        .next_if(|c| !['"', '$', '`', '\\'].contains(c) && (quoted || c != &'\''))
+                    {
+                        s.push(c);
+                    }
+                    let kind = match quoted {
+                        true => WordKind::Quote,
+                        false => WordKind::Normal,
+                    };
+                    Token::word(s, kind, location)
+                }
+            };
+
+            match token.value {
+                TokenKind::Word(s, k, q) => result.push(Word::from_token(s, k, q, token.location)),
+                _ => unreachable![],
+            }
+        }
+
+        match quoted {
+            true => Err(Error::unterminated_string(self.location())),
+            false => Ok(result),
+        }
+    }
+
+    // Reads (...) of an array assignment up to the matching ')'. The elements are
+    // parsed later with parse_array(). It may continue to the next lines.
+    fn array(&mut self) -> Result<Token> {
+        let location = self.reader.location();
+        self.reader.next(); // remove '('
+
+        let mut result = String::new();
+        let mut depth = 0;
+        loop {
+            match self.reader.next() {
+                None => return Err(Error::eof(self.location())),
+                Some('\\') => {
+                    result.push('\\');
+                    match self.reader.next() {
+                        Some(c) => result.push(c),
+                        None => return Err(Error::eof(self.location())),
+                    }
+                }
+                Some(q @ ('\'' | '"' | '`')) => {
+                    result.push(q);
+                    loop {
+                        match self.reader.next() {
+                            None => return Err(Error::eof(self.location())),
+                            Some('\\') if q != '\'' => {
+                                result.push('\\');
+                                if let Some(c) = self.reader.next() {
+                                    result.push(c);
+                                }
+                            }
+                            Some(c) => {
+                                result.push(c);
+                                if c == q {
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                }
+                Some('(') => {
+                    depth += 1;
+                    result.push('(');
+                }
+                Some(')') if depth == 0 => break,
+                Some(')') => {
+                    depth -= 1;
+                    result.push(')');
+                }
+                Some(c) => result.push(c),
+            }
+        }
+
+        Ok(Token::word(result, WordKind::Array, location))
+    }
+
+    // Reads the rest of the input as the elements of an array. Each element is a list of
+    // words separated by spaces or newlines.
+    pub(crate) fn array_elements(&mut self) -> Result<Vec<Vec<Word>>> {
+        let mut result = vec![];
+        let mut element = vec![];
+        loop {
+            self.head = false; // keywords are not recognized in an array
+            let token = match self.lex() {
+                None => break,
+                Some(token) => token?,
+            };
+            match token.value {
+                TokenKind::Word(s, k, q) => element.push(Word::from_token(s, k, q, token.location)),
+                TokenKind::Space | TokenKind::NewLine | TokenKind::Comment { .. } => {
+                    if !element.is_empty() {
+                        result.push(std::mem::take(&mut element));
+                    }
+                }
+                _ => return Err(Error::unexpected_token(&token)),
+            }
+        }
+        if !element.is_empty() {
+            result.push(element);
+        }
+        Ok(result)
+    }
+
+    // ((expr)) at the beginning of a command or after "for".
+    fn arithmetic_command(&mut self) -> Result<Token> {
+        self.arithmetic()
+            .map(|(expr, location)| Token::arithmetic(expr, location))
+    }
+
+    // [[ expr ]] at the beginning of a command. The expression is read up to "]]" which is
+    // a separate word, and parsed later by the parser. It may continue to the next lines.
+    fn conditional_command(&mut self) -> Result<Token> {
+        let location = self.reader.location();
+        self.reader.skip(2); // remove "[["
+
+        let mut result = String::new();
+        loop {
+            if self.reader.peek() == Some(&']')
+                && self.reader.peek_nth(1) == Some(&']')
+                && result.ends_with(|c: char| c.is_whitespace())
+                && match self.reader.peek_nth(2) {
+                    None => true,
+                    Some(c) => is_here_document_delimiter(c),
+                }
+            {
+                self.reader.skip(2);
+                break Ok(Token::conditional(result, location));
+            }
+
+            match self.reader.next() {
+                None => break Err(Error::eof(self.location())),
+                Some('\\') => {
+                    result.push('\\');
+                    match self.reader.next() {
+                        Some(c) => result.push(c),
+                        None => break Err(Error::eof(self.location())),
+                    }
+                }
+                Some(q @ ('\'' | '"')) => {
+                    result.push(q);
+                    loop {
+                        match self.reader.next() {
+                            None => return Err(Error::eof(self.location())),
+                            Some('\\') if q == '"' => {
+                                result.push('\\');
+                                if let Some(c) = self.reader.next() {
+                                    result.push(c);
+                                }
+                            }
+                            Some(c) => {
+                                result.push(c);
+                                if c == q {
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                }
+                Some(c) => result.push(c),
+            }
+        }
+    }
+
+    // Reads an arithmetic expression up to the matching "))".
+    fn arithmetic(&mut self) -> Result<(String, Location)> {
+        let location = self.reader.location();
+        self.reader.skip(2); // remove "(("
+
+        let mut result = String::new();
+        let mut depth = 0;
+        loop {
+            match self.reader.next() {
+                None => return Err(Error::unterminated_string(location)),
+                Some('(') => {
+                    depth += 1;
+                    result.push('(');
+                }
+                Some(')') if depth == 0 && self.reader.next_if(|c| c == &')').is_some() => {
+                    break Ok((result, location));
+                }
+                Some(')') => {
+                    depth -= 1;
+                    result.push(')');
+                }
+                Some(c) => result.push(c),
+            }
+        }
+    }
+
+    // Reads an unquoted word. Braces are part of the word unless the '}' closes a group,
+    // and an escaped pattern character is read as a quoted word so that it is not expanded.
     fn normal_word(&mut self) -> Result<Token> {
-        self.word(
-            WordKind::Normal,
-            is_normal_word_delimiter,
-            false,
-            true,
-            true,
-        )
+        let location = self.reader.location();
+        if self.reader.peek() == Some(&'\\')
+            && matches!(self.reader.peek_nth(1), Some(c) if is_pattern_char(c))
+        {
+            self.reader.next();
+            let c = self.reader.next().unwrap();
+            return Ok(Token::word(c.to_string(), WordKind::Quote, location));
+        }
+
+        let mut result = String::new();
+        while let Some(&c) = self.reader.peek() {
+            match c {
+                '\\' if matches!(self.reader.peek_nth(1), Some(c) if is_pattern_char(c)) => break,
+                '\\' => {
+                    self.reader.next();
+                    if let Some(c) = self.reader.next() {
+                        result.push(c);
+                    }
+                    continue;
+                }
+                '{' => self.brace_depth += 1,
+                '}' if self.brace_depth > 0 => self.brace_depth -= 1,
+                '}' if self.group_level == 0 => (),
+                _ if is_normal_word_delimiter(&c) => break,
+                _ => (),
+            }
+            result.push(c);
+            self.reader.next();
+        }
+        Ok(Token::word(result, WordKind::Normal, location))
     }
 
     fn word(
@@ -361,6 +944,24 @@ impl Lexer {
         }
     }
 
+    // Returns true if a name is followed by a group or a subshell, e.g. NAME of
+    // coproc NAME { command; }. The command after the name is read as a compound command.
+    fn is_coproc_name(&self) -> bool {
+        let mut i = 0;
+        while matches!(self.reader.peek_nth(i), Some(c) if c.is_ascii_alphanumeric() || c == &'_') {
+            i += 1;
+        }
+        if i == 0 || matches!(self.reader.peek(), Some(c) if c.is_ascii_digit()) {
+            return false;
+        }
+
+        let name_end = i;
+        while matches!(self.reader.peek_nth(i), Some(c) if is_space(c)) {
+            i += 1;
+        }
+        i > name_end && matches!(self.reader.peek_nth(i), Some('{' | '('))
+    }
+
     fn starts_with(&self, s: &str) -> bool {
         self.reader.starts_with(s)
             && (matches!(self.reader.peek_nth(s.len()), Some(c) if is_space(c) || is_newline(c) || is_termination(c))
@@ -373,6 +974,22 @@ mod test {
     use super::*;
     use crate::location;
 
+    #[test]
+    fn token_range() {
+        let mut lexer = Lexer::new("if 'あ' \"$x\"; fi", 0);
+        let locations = std::iter::from_fn(|| lexer.lex())
+            .map(|token| token.unwrap().location)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            locations.iter().map(Location::range).collect::<Vec<_>>(),
+            [0..2, 2..3, 3..8, 8..9, 11..13, 13..14, 14..15, 15..17]
+        );
+        assert_eq!(
+            locations.iter().map(|l| l.end).collect::<Vec<_>>(),
+            [2, 3, 8, 9, 13, 14, 15, 17]
+        );
+    }
+
     #[test]
     fn space() {
         assert_eq!(
@@ -397,6 +1014,26 @@ mod test {
         );
     }
 
+    #[test]
+    fn semicolon() {
+        assert_eq!(
+            Lexer::new(";", 0).semicolon(),
+            Ok(Token::termination(location!()))
+        );
+        assert_eq!(
+            Lexer::new(";;", 0).semicolon(),
+            Ok(Token::case_break(location!()))
+        );
+        assert_eq!(
+            Lexer::new(";&", 0).semicolon(),
+            Ok(Token::case_fall_through(location!()))
+        );
+        assert_eq!(
+            Lexer::new(";;&", 0).semicolon(),
+            Ok(Token::case_continue(location!()))
+        );
+    }
+
     #[test]
     fn group_start() {
         assert_eq!(
@@ -413,6 +1050,142 @@ mod test {
         )
     }
 
+    #[test]
+    fn subshell_start() {
+        assert_eq!(
+            Lexer::new("(", 0).subshell_start(),
+            Ok(Token::subshell_start(location!()))
+        )
+    }
+
+    #[test]
+    fn subshell_end() {
+        assert_eq!(
+            Lexer::new(")", 0).subshell_end(),
+            Ok(Token::subshell_end(location!()))
+        )
+    }
+
+    #[test]
+    fn array() {
+        let mut lexer = Lexer::new("a=(x (y) ')' \\)) b", 0);
+        assert_eq!(
+            lexer.lex(),
+            Some(Ok(Token::word("a=", WordKind::Normal, location!())))
+        );
+        assert_eq!(
+            lexer.lex(),
+            Some(Ok(Token::word(
+                "x (y) ')' \\)",
+                WordKind::Array,
+                location!(3)
+            )))
+        );
+        assert_eq!(lexer.lex(), Some(Ok(Token::space(location!(17)))));
+
+        let mut lexer = Lexer::new("a= (x)", 0);
+        lexer.lex();
+        lexer.lex();
+        assert_eq!(lexer.lex(), Some(Ok(Token::subshell_start(location!(4)))));
+
+        let mut lexer = Lexer::new("a=(x", 0);
+        lexer.lex();
+        assert_eq!(lexer.lex(), Some(Err(Error::eof(location!(5)))));
+    }
+
+    #[test]
+    fn coproc() {
+        let mut lexer = Lexer::new("coproc NAME { a; }", 0);
+        assert_eq!(lexer.lex(), Some(Ok(Token::keyword("coproc", location!()))));
+        lexer.lex();
+        assert_eq!(
+            lexer.lex(),
+            Some(Ok(Token::word("NAME", WordKind::Normal, location!(8))))
+        );
+        lexer.lex();
+        assert_eq!(lexer.lex(), Some(Ok(Token::group_start(location!(13)))));
+
+        // not a name
+        let mut lexer = Lexer::new("coproc cat if", 0);
+        lexer.lex();
+        lexer.lex();
+        lexer.lex();
+        lexer.lex();
+        assert_eq!(
+            lexer.lex(),
+            Some(Ok(Token::word("if", WordKind::Normal, location!(12))))
+        );
+    }
+
+    #[test]
+    fn bang_and_time() {
+        let mut lexer = Lexer::new("time -p ! if", 0);
+        assert_eq!(lexer.lex(), Some(Ok(Token::keyword("time", location!()))));
+        assert_eq!(lexer.lex(), Some(Ok(Token::space(location!(5)))));
+        assert_eq!(
+            lexer.lex(),
+            Some(Ok(Token::word("-p", WordKind::Normal, location!(6))))
+        );
+        assert_eq!(lexer.lex(), Some(Ok(Token::space(location!(8)))));
+        assert_eq!(lexer.lex(), Some(Ok(Token::keyword("!", location!(9)))));
+        assert_eq!(lexer.lex(), Some(Ok(Token::space(location!(10)))));
+        assert_eq!(lexer.lex(), Some(Ok(Token::keyword("if", location!(11)))));
+        assert_eq!(lexer.lex(), None);
+
+        // not at the beginning of a command
+        let mut lexer = Lexer::new("echo ! time", 0);
+        lexer.lex();
+        lexer.lex();
+        assert_eq!(
+            lexer.lex(),
+            Some(Ok(Token::word("!", WordKind::Normal, location!(6))))
+        );
+        lexer.lex();
+        assert_eq!(
+            lexer.lex(),
+            Some(Ok(Token::word("time", WordKind::Normal, location!(8))))
+        );
+
+        // a part of a word
+        let mut lexer = Lexer::new("!a", 0);
+        assert_eq!(
+            lexer.lex(),
+            Some(Ok(Token::word("!a", WordKind::Normal, location!())))
+        );
+    }
+
+    #[test]
+    fn arithmetic_command() {
+        let mut lexer = Lexer::new("((i++)) > a", 0);
+        assert_eq!(lexer.lex(), Some(Ok(Token::arithmetic("i++", location!()))));
+        assert_eq!(lexer.lex(), Some(Ok(Token::space(location!(8)))));
+        assert_eq!(lexer.lex(), Some(Ok(Token::write_to(location!(9)))));
+
+        let mut lexer = Lexer::new("for ((i = 0; i < (1 + 2); i++))", 0);
+        assert_eq!(lexer.lex(), Some(Ok(Token::keyword("for", location!()))));
+        assert_eq!(lexer.lex(), Some(Ok(Token::space(location!(4)))));
+        assert_eq!(
+            lexer.lex(),
+            Some(Ok(Token::arithmetic(
+                "i = 0; i < (1 + 2); i++",
+                location!(5)
+            )))
+        );
+        assert_eq!(lexer.lex(), None);
+
+        // not at the beginning of a command
+        let mut lexer = Lexer::new("a ((", 0);
+        lexer.lex();
+        lexer.lex();
+        assert_eq!(lexer.lex(), Some(Ok(Token::subshell_start(location!(3)))));
+
+        let mut lexer = Lexer::new("((1 + 2)", 0);
+        assert_eq!(
+            lexer.lex(),
+            Some(Err(Error::unterminated_string(location!())))
+        );
+    }
+
     #[test]
     fn hyphen() {
         assert_eq!(Lexer::new("-", 0).hyphen(), Ok(Token::hyphen(location!())))
@@ -493,8 +1266,8 @@ mod test {
             Ok(Token::here_string(location!()))
         );
         assert_eq!(
-            Lexer::new("<<", 0).less_than(),
-            Ok(Token::here_document(location!()))
+            Lexer::new("<<EOF\nEOF", 0).less_than(),
+            Ok(Token::here_document(vec![], location!()))
         );
         assert_eq!(
             Lexer::new("<&-", 0).less_than(),
@@ -512,6 +1285,81 @@ mod test {
             Lexer::new("<", 0).less_than(),
             Ok(Token::read_from(location!()))
         );
+        assert_eq!(
+            Lexer::new("<(sort a)", 0).less_than(),
+            Ok(Token::word("sort a", WordKind::InputProcess, location!(2)))
+        );
+        assert_eq!(
+            Lexer::new("<(sort a", 0).less_than(),
+            Err(Error::unterminated_string(location!(2)))
+        );
+    }
+
+    #[test]
+    fn here_document() {
+        assert_eq!(
+            Lexer::new("<<EOF\nfoo\nEOF", 0).less_than(),
+            Ok(Token::here_document(
+                vec![Word::new(
+                    "foo\n".to_string(),
+                    WordKind::Normal,
+                    location!(1, 2)
+                )],
+                location!()
+            ))
+        );
+        assert_eq!(
+            Lexer::new("<< EOF\nfoo $bar\\$baz\nEOF", 0).less_than(),
+            Ok(Token::here_document(
+                vec![
+                    Word::new("foo ".to_string(), WordKind::Normal, location!(1, 2)),
+                    Word::new("bar".to_string(), WordKind::Variable, location!(6, 2)),
+                    Word::new("$baz\n".to_string(), WordKind::Normal, location!(9, 2)),
+                ],
+                location!()
+            ))
+        );
+        assert_eq!(
+            Lexer::new("<<-EOF\n\tfoo\n\tEOF", 0).less_than(),
+            Ok(Token::here_document(
+                vec![Word::new(
+                    "foo\n".to_string(),
+                    WordKind::Normal,
+                    location!(1, 2)
+                )],
+                location!()
+            ))
+        );
+        for s in ["<<'EOF'", "<<\"EOF\"", "<<\\EOF", "<<E'O'F"] {
+            assert_eq!(
+                Lexer::new(&format!("{s}\n$foo\nEOF"), 0).less_than(),
+                Ok(Token::here_document(
+                    vec![Word::new(
+                        "$foo\n".to_string(),
+                        WordKind::Quote,
+                        location!(1, 2)
+                    )],
+                    location!()
+                ))
+            );
+        }
+
+        assert_eq!(
+            Lexer::new("<<EOF", 0).less_than(),
+            Err(Error::eof(location!(6)))
+        );
+        assert_eq!(
+            Lexer::new("<<EOF\nfoo", 0).less_than(),
+            Err(Error::eof(location!(6)))
+        );
+        assert_eq!(
+            Lexer::new("<<", 0).less_than(),
+            Err(Error::eof(location!(3)))
+        );
+        assert_eq!(
+            Lexer::new("<<'EOF", 0).less_than(),
+            Err(Error::unterminated_string(location!(3)))
+        );
     }
 
     #[test]
@@ -544,6 +1392,10 @@ mod test {
             Lexer::new(">", 0).greater_than(),
             Ok(Token::write_to(location!()))
         );
+        assert_eq!(
+            Lexer::new(">(wc -l)", 0).greater_than(),
+            Ok(Token::word("wc -l", WordKind::OutputProcess, location!(2)))
+        );
     }
 
     #[test]
@@ -551,31 +1403,52 @@ mod test {
         let mut lexer = Lexer::new("\"abc\"", 0);
         assert_eq!(
             lexer.quoted_word(),
-            Ok(Token::word("abc", WordKind::Normal, location!(2)))
+            Ok(Token::quoted_word("abc", WordKind::Quote, location!(2)))
         );
         assert_eq!(lexer.lex(), None);
 
         let mut lexer = Lexer::new("\"abc${def}ghi\"", 0);
         assert_eq!(
             lexer.quoted_word(),
-            Ok(Token::word("abc", WordKind::Normal, location!(2)))
+            Ok(Token::quoted_word("abc", WordKind::Quote, location!(2)))
         );
         assert_eq!(
             lexer.quoted_word(),
-            Ok(Token::word("def", WordKind::Parameter, location!(6)))
+            Ok(Token::quoted_word("def", WordKind::Parameter, location!(6)))
         );
         assert_eq!(
             lexer.quoted_word(),
-            Ok(Token::word("ghi", WordKind::Normal, location!(11)))
+            Ok(Token::quoted_word("ghi", WordKind::Quote, location!(11)))
         );
         assert_eq!(lexer.lex(), None);
 
         let mut lexer = Lexer::new("\"${abc}\"", 0);
         assert_eq!(
             lexer.quoted_word(),
-            Ok(Token::word("abc", WordKind::Parameter, location!(3)))
+            Ok(Token::quoted_word("abc", WordKind::Parameter, location!(3)))
         );
         assert_eq!(lexer.lex(), None);
+
+        let mut lexer = Lexer::new("\"$abc 1\"", 0);
+        assert_eq!(
+            lexer.quoted_word(),
+            Ok(Token::quoted_word("abc", WordKind::Variable, location!(3)))
+        );
+        assert_eq!(
+            lexer.lex(),
+            Some(Ok(Token::quoted_word(" 1", WordKind::Quote, location!(6))))
+        );
+        assert_eq!(lexer.lex(), None);
+
+        let mut lexer = Lexer::new("\"\"a", 0);
+        assert_eq!(
+            lexer.quoted_word(),
+            Ok(Token::quoted_word("", WordKind::Quote, location!(2)))
+        );
+        assert_eq!(
+            lexer.lex(),
+            Some(Ok(Token::word("a", WordKind::Normal, location!(3))))
+        );
     }
 
     #[test]
@@ -593,6 +1466,22 @@ mod test {
         );
         assert_eq!(lexer.lex(), None);
 
+        let mut lexer = Lexer::new("${a:-${b}}${c#'}'}", 0);
+        assert_eq!(
+            lexer.dollar_word(),
+            Ok(Token::word("a:-${b}", WordKind::Parameter, location!(2)))
+        );
+        assert_eq!(
+            lexer.dollar_word(),
+            Ok(Token::word("c#'}'", WordKind::Parameter, location!(12)))
+        );
+        assert_eq!(lexer.lex(), None);
+        let mut lexer = Lexer::new("${abc", 0);
+        assert_eq!(
+            lexer.dollar_word(),
+            Err(Error::unterminated_string(location!(2)))
+        );
+
         let mut lexer = Lexer::new("$(abc)", 0);
         assert_eq!(
             lexer.dollar_word(),
@@ -602,10 +1491,67 @@ mod test {
         let mut lexer = Lexer::new("$(abc\\)def)", 0);
         assert_eq!(
             lexer.dollar_word(),
-            Ok(Token::word("abc)def", WordKind::Command, location!(2)))
+            Ok(Token::word("abc\\)def", WordKind::Command, location!(2)))
+        );
+        assert_eq!(lexer.lex(), None);
+
+        let mut lexer = Lexer::new("$((1 + (2 * 3)))", 0);
+        assert_eq!(
+            lexer.dollar_word(),
+            Ok(Token::word(
+                "1 + (2 * 3)",
+                WordKind::Arithmetic,
+                location!(2)
+            ))
+        );
+        assert_eq!(lexer.lex(), None);
+        let mut lexer = Lexer::new("$((1 + 2)", 0);
+        assert_eq!(
+            lexer.dollar_word(),
+            Err(Error::unterminated_string(location!(2)))
+        );
+
+        let mut lexer = Lexer::new("$'a\\tb\\'c'x", 0);
+        assert_eq!(
+            lexer.dollar_word(),
+            Ok(Token::word("a\\tb\\'c", WordKind::AnsiC, location!(2)))
+        );
+        assert_eq!(
+            lexer.lex(),
+            Some(Ok(Token::word("x", WordKind::Normal, location!(11))))
+        );
+        let mut lexer = Lexer::new("$'abc", 0);
+        assert_eq!(
+            lexer.dollar_word(),
+            Err(Error::unterminated_string(location!(2)))
+        );
+
+        let mut lexer = Lexer::new("$\"a $b\"", 0);
+        assert_eq!(
+            lexer.dollar_word(),
+            Ok(Token::quoted_word("a ", WordKind::Quote, location!(3)))
+        );
+        assert_eq!(
+            lexer.lex(),
+            Some(Ok(Token::quoted_word(
+                "b",
+                WordKind::Variable,
+                location!(6)
+            )))
         );
         assert_eq!(lexer.lex(), None);
 
+        // not quoted in double quotes
+        let mut lexer = Lexer::new("\"$'a'\"", 0);
+        assert_eq!(
+            lexer.lex(),
+            Some(Ok(Token::quoted_word("$", WordKind::Quote, location!(2))))
+        );
+        assert_eq!(
+            lexer.lex(),
+            Some(Ok(Token::quoted_word("'a'", WordKind::Quote, location!(3))))
+        );
+
         let mut lexer = Lexer::new("$$", 0);
         assert_eq!(
             lexer.dollar_word(),
@@ -613,6 +1559,16 @@ mod test {
         );
         assert_eq!(lexer.lex(), None);
 
+        let mut lexer = Lexer::new("$?x", 0);
+        assert_eq!(
+            lexer.dollar_word(),
+            Ok(Token::word("?", WordKind::Variable, location!(1)))
+        );
+        assert_eq!(
+            lexer.lex(),
+            Some(Ok(Token::word("x", WordKind::Normal, location!(3))))
+        );
+
         let mut lexer = Lexer::new("$abc$def", 0);
         assert_eq!(
             lexer.dollar_word(),
@@ -624,6 +1580,13 @@ mod test {
         );
         assert_eq!(lexer.lex(), None);
 
+        let mut lexer = Lexer::new("$abc_def ghi", 0);
+        assert_eq!(
+            lexer.dollar_word(),
+            Ok(Token::word("abc_def", WordKind::Variable, location!(2)))
+        );
+        assert_eq!(lexer.lex(), Some(Ok(Token::space(location!(9)))));
+
         let mut lexer = Lexer::new("$🍣", 0);
         assert_eq!(
             lexer.dollar_word(),
@@ -646,6 +1609,34 @@ mod test {
         );
     }
 
+    #[test]
+    fn parameter_word() {
+        assert_eq!(
+            Lexer::new("a b$c'*'\\?\"d $e\"", 0).parameter_word(),
+            Ok(vec![
+                Word::new("a b".to_string(), WordKind::Normal, location!(1)),
+                Word::new("c".to_string(), WordKind::Variable, location!(5)),
+                Word::new("*".to_string(), WordKind::Quote, location!(6)),
+                Word::new("?".to_string(), WordKind::Quote, location!(9)),
+                Word::new("d ".to_string(), WordKind::Quote, location!(12)),
+                Word::quoted("e".to_string(), WordKind::Variable, location!(15)),
+            ])
+        );
+        assert_eq!(
+            Lexer::new("$'\\t'$\"a\"\"$'\"", 0).parameter_word(),
+            Ok(vec![
+                Word::new("\\t".to_string(), WordKind::AnsiC, location!(2)),
+                Word::new("a".to_string(), WordKind::Quote, location!(8)),
+                Word::new("$".to_string(), WordKind::Quote, location!(11)),
+                Word::new("'".to_string(), WordKind::Quote, location!(12)),
+            ])
+        );
+        assert_eq!(
+            Lexer::new("\"a", 0).parameter_word(),
+            Err(Error::unterminated_string(location!(3)))
+        );
+    }
+
     #[test]
     fn normal_word() {
         assert_eq!(
@@ -669,6 +1660,36 @@ mod test {
             lexer.normal_word(),
             Ok(Token::word("abc def", WordKind::Normal, location!()))
         );
+
+        let mut lexer = Lexer::new("a{b,{c}}}", 0);
+        assert_eq!(
+            lexer.normal_word(),
+            Ok(Token::word("a{b,{c}}}", WordKind::Normal, location!()))
+        );
+
+        let mut lexer = Lexer::new("{ a{b,{c}}}", 0);
+        assert_eq!(lexer.lex(), Some(Ok(Token::group_start(location!()))));
+        assert_eq!(lexer.lex(), Some(Ok(Token::space(location!(2)))));
+        assert_eq!(
+            lexer.lex(),
+            Some(Ok(Token::word("a{b,{c}}", WordKind::Normal, location!(3))))
+        );
+        assert_eq!(lexer.lex(), Some(Ok(Token::group_end(location!(11)))));
+
+        let mut lexer = Lexer::new("{a,\\}}", 0);
+        assert_eq!(
+            lexer.normal_word(),
+            Ok(Token::word("{a,", WordKind::Normal, location!()))
+        );
+        assert_eq!(
+            lexer.lex(),
+            Some(Ok(Token::word("}", WordKind::Quote, location!(4))))
+        );
+        assert_eq!(
+            lexer.lex(),
+            Some(Ok(Token::word("}", WordKind::Normal, location!(6))))
+        );
+        assert_eq!(lexer.lex(), None);
     }
 
     #[test]
@@ -1,10 +1,13 @@
 use crate::Location;
-use std::collections::{vec_deque::Iter, VecDeque};
+use std::collections::{vec_deque::Iter, HashMap, VecDeque};
 
 #[derive(Debug)]
 pub(crate) struct Reader {
     input: VecDeque<char>,
     location: Location,
+    position: usize,
+    skip_lines: HashMap<usize, (usize, usize)>, // position of newline => removed lines and bytes
+    newlines: usize, // the number of newlines read, excluding removed lines
 }
 
 impl Reader {
@@ -12,6 +15,9 @@ impl Reader {
         Reader {
             input: VecDeque::from_iter(input.chars()),
             location: Location::from_offset(&Location::default(), 0, offset),
+            position: 0,
+            skip_lines: HashMap::new(),
+            newlines: 0,
         }
     }
 
@@ -27,15 +33,28 @@ impl Reader {
         match self.input.pop_front() {
             None => None,
             Some(result) => {
+                self.location.offset += result.len_utf8();
                 match result {
-                    '\n' => self.location.newline(),
+                    '\n' => {
+                        self.location.newline();
+                        self.newlines += 1;
+                        if let Some((lines, bytes)) = self.skip_lines.remove(&self.position) {
+                            self.location.line += lines;
+                            self.location.offset += bytes;
+                        }
+                    }
                     _ => self.location.next(),
                 }
+                self.position += 1;
                 Some(result)
             }
         }
     }
 
+    pub(crate) fn newlines(&self) -> usize {
+        self.newlines
+    }
+
     pub(crate) fn next_if(&mut self, f: impl Fn(&char) -> bool) -> Option<char> {
         match self.peek() {
             Some(c) if f(c) => self.next(),
@@ -45,10 +64,80 @@ impl Reader {
 
     pub(crate) fn skip(&mut self, index: usize) {
         self.location.column += index;
+        self.location.offset += self
+            .input
+            .iter()
+            .take(index)
+            .map(|c| c.len_utf8())
+            .sum::<usize>();
+        self.position += index;
         self.input = self.input.drain(index..).collect::<_>();
     }
 
-    pub(crate) fn iter(&self) -> Iter<char> {
+    // Removes the lines following the current line up to the delimiter line and
+    // returns them with the location of the first line.
+    // Returns None if the delimiter line has not been read yet.
+    pub(crate) fn here_document(
+        &mut self,
+        delimiter: &str,
+        strip_tabs: bool,
+    ) -> Option<(String, Location)> {
+        let newline = self.input.iter().position(|c| c == &'\n')?;
+
+        let mut body = String::new();
+        let mut lines = 0;
+        let mut index = newline + 1;
+        loop {
+            let mut line = String::new();
+            let mut terminated = false;
+            while let Some(c) = self.input.get(index) {
+                index += 1;
+                if c == &'\n' {
+                    terminated = true;
+                    break;
+                }
+                line.push(*c);
+            }
+            lines += 1;
+
+            let line = match strip_tabs {
+                true => line.trim_start_matches('\t'),
+                false => &line,
+            };
+            if line == delimiter {
+                break;
+            }
+            if !terminated {
+                return None;
+            }
+            body.push_str(line);
+            body.push('\n');
+        }
+        let bytes = self
+            .input
+            .drain(newline + 1..index)
+            .map(|c| c.len_utf8())
+            .sum::<usize>();
+
+        let position = self.position + newline;
+        let (skipped_lines, skipped_bytes) =
+            self.skip_lines.get(&position).cloned().unwrap_or((0, 0));
+        self.skip_lines
+            .insert(position, (skipped_lines + lines, skipped_bytes + bytes));
+
+        let mut location = Location::new(1, self.location.line + skipped_lines + 1);
+        location.offset = self.location.offset
+            + self
+                .input
+                .iter()
+                .take(newline + 1)
+                .map(|c| c.len_utf8())
+                .sum::<usize>()
+            + skipped_bytes;
+        Some((body, location))
+    }
+
+    pub(crate) fn iter(&self) -> Iter<'_, char> {
         self.input.iter()
     }
 
@@ -111,6 +200,65 @@ mod test {
         assert_eq!(reader.location, location!(3, 1));
     }
 
+    #[test]
+    fn here_document() {
+        let mut reader = Reader::new("cat <<EOF\nfoo\n  bar\nEOF\nbaz", 0);
+        assert_eq!(
+            reader.here_document("EOF", false),
+            Some(("foo\n  bar\n".to_string(), location!(1, 2)))
+        );
+        assert_eq!(String::from_iter(reader.iter()), "cat <<EOF\nbaz");
+        reader.skip(9);
+        assert_eq!(reader.next(), Some('\n'));
+        assert_eq!(reader.location, location!(1, 5));
+
+        let mut reader = Reader::new("cat <<-EOF\n\tfoo\n\tEOF", 0);
+        assert_eq!(
+            reader.here_document("EOF", true),
+            Some(("foo\n".to_string(), location!(1, 2)))
+        );
+        assert_eq!(String::from_iter(reader.iter()), "cat <<-EOF\n");
+
+        let mut reader = Reader::new("cat <<A <<B\na\nA\nb\nB\n", 0);
+        assert_eq!(
+            reader.here_document("A", false),
+            Some(("a\n".to_string(), location!(1, 2)))
+        );
+        assert_eq!(
+            reader.here_document("B", false),
+            Some(("b\n".to_string(), location!(1, 4)))
+        );
+        assert_eq!(String::from_iter(reader.iter()), "cat <<A <<B\n");
+
+        assert_eq!(
+            Reader::new("cat <<EOF", 0).here_document("EOF", false),
+            None
+        );
+        assert_eq!(
+            Reader::new("cat <<EOF\nfoo", 0).here_document("EOF", false),
+            None
+        );
+    }
+
+    #[test]
+    fn offset() {
+        let mut reader = Reader::new("aあ\nb", 0);
+        reader.skip(2);
+        assert_eq!(reader.location.offset, 4);
+        reader.next();
+        assert_eq!(reader.location.offset, 5);
+
+        let mut reader = Reader::new("<<A <<B\nあ\nA\nb\nB\nc", 0);
+        let (_, location) = reader.here_document("A", false).unwrap();
+        assert_eq!(location.offset, 8);
+        let (_, location) = reader.here_document("B", false).unwrap();
+        assert_eq!(location.offset, 14);
+        reader.skip(7);
+        reader.next();
+        assert_eq!(reader.location, location!(1, 6));
+        assert_eq!(reader.location.offset, 18);
+    }
+
     #[test]
     fn iter() {
         assert!(Reader::new("abc", 0).iter().eq([&'a', &'b', &'c']));
@@ -0,0 +1,421 @@
+use super::{utils::*, Lexer, Reader};
+use crate::{Error, Result, Token, WordKind};
+
+// The reserved words which are followed by a command.
+const KEYWORDS: [&str; 14] = [
+    "if", "then", "else", "elif", "elsif", "unless", "while", "until", "do", "for", "select", "!",
+    "time", "{",
+];
+
+impl Lexer {
+    // Reads $(...) up to the matching ')'. The command is scanned as a script, so that
+    // a ')' in quotes, comments, here-documents, case patterns and nested substitutions
+    // does not end it.
+    pub(super) fn command_substitution(&mut self) -> Result<Token> {
+        let location = self.reader.location();
+        self.reader.next(); // remove '('
+
+        let end = Scanner::new(&self.reader)
+            .command(0)
+            .ok_or_else(|| Error::eof(location))?; // the command continues to the next line
+        let command = (0..end)
+            .filter_map(|_| self.reader.next())
+            .collect::<String>();
+        self.reader.next(); // remove ')'
+
+        Ok(Token::word(command, WordKind::Command, location))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum CaseState {
+    Subject, // case word
+    In,      // in
+    Pattern, // pattern)
+    Body,    // commands ;;
+}
+
+// Scans the input from the reader without consuming it. The methods return the index after
+// the construct, or None if it is not terminated.
+struct Scanner<'a> {
+    reader: &'a Reader,
+    depth: usize,                        // nested parentheses
+    head: bool,                          // at the beginning of a command
+    cases: Vec<(usize, CaseState)>,      // the depth of case statements
+    here_documents: Vec<(String, bool)>, // the delimiters to be read after the newline
+}
+
+impl<'a> Scanner<'a> {
+    fn new(reader: &'a Reader) -> Self {
+        Self {
+            reader,
+            depth: 0,
+            head: true,
+            cases: vec![],
+            here_documents: vec![],
+        }
+    }
+
+    fn get(&self, i: usize) -> Option<char> {
+        self.reader.peek_nth(i).cloned()
+    }
+
+    // Returns the index of the ')' which ends the command starting at i.
+    fn command(&mut self, mut i: usize) -> Option<usize> {
+        let mut word = String::new();
+        let mut quoted = false;
+        loop {
+            let c = self.get(i)?;
+            let delimiter = is_space(&c)
+                || is_newline(&c)
+                || is_termination(&c)
+                || ['&', '|', '<', '>', '(', ')'].contains(&c);
+            if delimiter && (!word.is_empty() || quoted) {
+                self.end_word(&word, quoted);
+                word.clear();
+                quoted = false;
+            }
+
+            i = match c {
+                '\\' => {
+                    quoted = true;
+                    i + 2
+                }
+                '\'' => {
+                    quoted = true;
+                    self.single_quote(i)?
+                }
+                '"' => {
+                    quoted = true;
+                    self.double_quote(i)?
+                }
+                '`' => {
+                    quoted = true;
+                    self.back_quote(i)?
+                }
+                '$' if matches!(self.get(i + 1), Some('\'' | '(' | '{')) => {
+                    quoted = true;
+                    self.dollar(i)?
+                }
+                '#' if word.is_empty() && !quoted => {
+                    let mut i = i;
+                    while !matches!(self.get(i), None | Some('\n')) {
+                        i += 1;
+                    }
+                    i
+                }
+
+                ' ' | '\t' => i + 1,
+                '\n' => {
+                    self.head = true;
+                    self.skip_here_documents(i + 1)?
+                }
+                ';' => {
+                    self.head = true;
+                    let len = match (self.get(i + 1), self.get(i + 2)) {
+                        (Some(';'), Some('&')) => 3,
+                        (Some(';' | '&'), _) => 2,
+                        _ => 1,
+                    };
+                    if len > 1 {
+                        self.set_case_state(CaseState::Body, CaseState::Pattern);
+                    }
+                    i + len
+                }
+                '&' | '|' => {
+                    self.head = true;
+                    i + 1
+                }
+                '(' => {
+                    // The leading '(' of a pattern is optional.
+                    if !self.is_case_state(CaseState::Pattern) {
+                        self.depth += 1;
+                    }
+                    self.head = true;
+                    i + 1
+                }
+                ')' => {
+                    if self.set_case_state(CaseState::Pattern, CaseState::Body) {
+                        self.head = true;
+                    } else if self.depth == 0 {
+                        return Some(i);
+                    } else {
+                        self.depth -= 1;
+                        self.head = false;
+                    }
+                    i + 1
+                }
+                '<' if self.get(i + 1) == Some('<') && self.get(i + 2) != Some('<') => {
+                    self.here_document(i + 2)
+                }
+                '<' if self.get(i + 1) == Some('<') => i + 3,
+                '<' | '>' => i + 1,
+
+                c => {
+                    word.push(c);
+                    i + 1
+                }
+            };
+        }
+    }
+
+    // Updates the state of the reserved words at the end of a word.
+    fn end_word(&mut self, word: &str, quoted: bool) {
+        let keyword = |s| !quoted && word == s;
+        match self.cases.last_mut() {
+            Some((depth, state)) if *depth == self.depth && *state != CaseState::Body => {
+                match state {
+                    CaseState::Subject => *state = CaseState::In,
+                    CaseState::In if keyword("in") => *state = CaseState::Pattern,
+                    CaseState::In => {
+                        // case word when ... end
+                        self.cases.pop();
+                        self.head = true;
+                    }
+                    CaseState::Pattern if keyword("esac") => {
+                        self.cases.pop();
+                        self.head = false;
+                    }
+                    _ => (),
+                }
+                return;
+            }
+            _ => (),
+        }
+
+        if self.head && keyword("case") {
+            self.cases.push((self.depth, CaseState::Subject));
+            self.head = false;
+        } else if self.head && keyword("esac") && self.is_case_state(CaseState::Body) {
+            self.cases.pop();
+            self.head = false;
+        } else {
+            self.head = self.head && !quoted && KEYWORDS.contains(&word);
+        }
+    }
+
+    fn is_case_state(&self, state: CaseState) -> bool {
+        matches!(self.cases.last(), Some((depth, s)) if *depth == self.depth && *s == state)
+    }
+
+    fn set_case_state(&mut self, from: CaseState, to: CaseState) -> bool {
+        let found = self.is_case_state(from);
+        if found {
+            self.cases.last_mut().unwrap().1 = to;
+        }
+        found
+    }
+
+    // <<word or <<-word. The delimiter is read and the body is skipped after the newline.
+    fn here_document(&mut self, mut i: usize) -> usize {
+        let strip_tabs = self.get(i) == Some('-');
+        if strip_tabs {
+            i += 1;
+        }
+        while matches!(self.get(i), Some(c) if is_space(&c)) {
+            i += 1;
+        }
+
+        let mut delimiter = String::new();
+        while let Some(c) = self.get(i) {
+            if is_space(&c)
+                || is_newline(&c)
+                || is_termination(&c)
+                || ['&', '|', '<', '>', '(', ')'].contains(&c)
+            {
+                break;
+            }
+            if !['\'', '"', '\\'].contains(&c) {
+                delimiter.push(c);
+            }
+            i += 1;
+        }
+        self.here_documents.push((delimiter, strip_tabs));
+        self.head = false;
+        i
+    }
+
+    fn skip_here_documents(&mut self, mut i: usize) -> Option<usize> {
+        for (delimiter, strip_tabs) in std::mem::take(&mut self.here_documents) {
+            loop {
+                let mut line = String::new();
+                loop {
+                    match self.get(i) {
+                        None if line.is_empty() => return None,
+                        None => break,
+                        Some('\n') => {
+                            i += 1;
+                            break;
+                        }
+                        Some(c) => {
+                            line.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+                let line = match strip_tabs {
+                    true => line.trim_start_matches('\t'),
+                    false => &line,
+                };
+                if line == delimiter {
+                    break;
+                }
+            }
+        }
+        Some(i)
+    }
+
+    fn single_quote(&self, i: usize) -> Option<usize> {
+        let mut i = i + 1;
+        while self.get(i)? != '\'' {
+            i += 1;
+        }
+        Some(i + 1)
+    }
+
+    fn double_quote(&self, i: usize) -> Option<usize> {
+        let mut i = i + 1;
+        loop {
+            i = match self.get(i)? {
+                '"' => return Some(i + 1),
+                '\\' => i + 2,
+                '`' => self.back_quote(i)?,
+                '$' if matches!(self.get(i + 1), Some('(' | '{')) => self.dollar(i)?,
+                _ => i + 1,
+            };
+        }
+    }
+
+    fn back_quote(&self, i: usize) -> Option<usize> {
+        let mut i = i + 1;
+        loop {
+            i = match self.get(i)? {
+                '`' => return Some(i + 1),
+                '\\' => i + 2,
+                _ => i + 1,
+            };
+        }
+    }
+
+    // $'...', $((...)), $(...) or ${...}
+    fn dollar(&self, i: usize) -> Option<usize> {
+        match (self.get(i + 1), self.get(i + 2)) {
+            (Some('\''), _) => {
+                let mut i = i + 2;
+                loop {
+                    i = match self.get(i)? {
+                        '\'' => return Some(i + 1),
+                        '\\' => i + 2,
+                        _ => i + 1,
+                    };
+                }
+            }
+            (Some('('), Some('(')) => self
+                .arithmetic(i + 3)
+                .or_else(|| Scanner::new(self.reader).command(i + 2).map(|i| i + 1)),
+            (Some('('), _) => Scanner::new(self.reader).command(i + 2).map(|i| i + 1),
+            _ => self.parameter(i + 2),
+        }
+    }
+
+    // Returns the index after "))" of $((...)).
+    fn arithmetic(&self, mut i: usize) -> Option<usize> {
+        let mut depth = 0;
+        loop {
+            match self.get(i)? {
+                '(' => depth += 1,
+                ')' if depth > 0 => depth -= 1,
+                ')' if self.get(i + 1) == Some(')') => return Some(i + 2),
+                ')' => return None,
+                _ => (),
+            }
+            i += 1;
+        }
+    }
+
+    // Returns the index after '}' of ${...}.
+    fn parameter(&self, mut i: usize) -> Option<usize> {
+        let mut depth = 0;
+        loop {
+            i = match self.get(i)? {
+                '\\' => i + 2,
+                '\'' => self.single_quote(i)?,
+                '"' => self.double_quote(i)?,
+                '`' => self.back_quote(i)?,
+                '$' if matches!(self.get(i + 1), Some('(' | '{')) => self.dollar(i)?,
+                '{' => {
+                    depth += 1;
+                    i + 1
+                }
+                '}' if depth == 0 => return Some(i + 1),
+                '}' => {
+                    depth -= 1;
+                    i + 1
+                }
+                _ => i + 1,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{location, Location};
+
+    macro_rules! assert_command {
+        ($input:expr, $command:expr, $rest:expr) => {{
+            let mut lexer = Lexer::new($input, 0);
+            lexer.reader.next(); // remove '$'
+            assert_eq!(
+                lexer.command_substitution(),
+                Ok(Token::word($command, WordKind::Command, location!(2)))
+            );
+            assert_eq!(lexer.reader.iter().collect::<String>(), $rest);
+        }};
+    }
+
+    #[test]
+    fn command_substitution() {
+        assert_command!("$(abc) def", "abc", " def");
+        assert_command!("$(echo $(date))x", "echo $(date)", "x");
+        assert_command!("$((a); (b))", "(a); (b)", "");
+        assert_command!("$(echo $((1 + (2))))", "echo $((1 + (2)))", "");
+        assert_command!(
+            "$(printf ')' \")\" \\) `echo )`)",
+            "printf ')' \")\" \\) `echo )`",
+            ""
+        );
+        assert_command!("$(echo ${a:-)} $'\\')')", "echo ${a:-)} $'\\')'", "");
+        assert_command!("$(echo a # )\n)", "echo a # )\n", "");
+        assert_command!("$(echo a#)", "echo a#", "");
+
+        // case patterns
+        assert_command!(
+            "$(case x in a) echo a;; (b|c) echo b;; esac)",
+            "case x in a) echo a;; (b|c) echo b;; esac",
+            ""
+        );
+        assert_command!(
+            "$(case x in\n  a)\n    (echo a)\nesac; echo case in)",
+            "case x in\n  a)\n    (echo a)\nesac; echo case in",
+            ""
+        );
+        assert_command!("$(case x\nwhen a\n  echo )", "case x\nwhen a\n  echo ", "");
+
+        // here documents
+        assert_command!(
+            "$(cat <<EOF; cat <<-'E'\n)\nEOF\n\t)\n\tE\n)",
+            "cat <<EOF; cat <<-'E'\n)\nEOF\n\t)\n\tE\n",
+            ""
+        );
+        assert_command!("$(cat <<< a)", "cat <<< a", "");
+
+        // unterminated
+        for input in ["$(abc", "$(echo ')'", "$(echo $(a)", "$(cat <<EOF\n)"] {
+            let mut lexer = Lexer::new(input, 0);
+            lexer.reader.next();
+            assert_eq!(lexer.command_substitution(), Err(Error::eof(location!(2))));
+        }
+    }
+}
@@ -27,7 +27,7 @@ pub(crate) fn is_back_quote(c: &char) -> bool {
 }
 
 pub(crate) fn is_normal_word_delimiter(c: &char) -> bool {
-    ['&', '|', '<', '>', '{', '}', '$'].contains(c)
+    ['&', '|', '<', '>', '{', '}', '(', ')', '$'].contains(c)
         || is_space(c)
         || is_newline(c)
         || is_termination(c)
@@ -35,6 +35,18 @@ pub(crate) fn is_normal_word_delimiter(c: &char) -> bool {
         || is_double_quote(c)
 }
 
+pub(crate) fn is_pattern_char(c: &char) -> bool {
+    ['{', '}', ',', '*', '?', '[', ']', '~'].contains(c)
+}
+
+pub(crate) fn is_variable_delimiter(c: &char) -> bool {
+    (c.is_ascii_punctuation() && c != &'_') || c.is_whitespace()
+}
+
+pub(crate) fn is_here_document_delimiter(c: &char) -> bool {
+    ['&', '|', '<', '>', '(', ')'].contains(c) || is_space(c) || is_newline(c) || is_termination(c)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -86,9 +98,37 @@ mod test {
 
     #[test]
     fn test_is_normal_word_delimiter() {
-        for c in " \t\n;&|<>{}'\"$".chars() {
+        for c in " \t\n;&|<>{}()'\"$".chars() {
             assert!(is_normal_word_delimiter(&c));
         }
         assert!(!is_normal_word_delimiter(&'a'));
     }
+
+    #[test]
+    fn test_is_pattern_char() {
+        for c in "{},*?[]~".chars() {
+            assert!(is_pattern_char(&c));
+        }
+        assert!(!is_pattern_char(&'a'));
+    }
+
+    #[test]
+    fn test_is_variable_delimiter() {
+        for c in " \t\n;&|<>{}'\"$-.:/".chars() {
+            assert!(is_variable_delimiter(&c));
+        }
+        for c in "aZ0_🍣".chars() {
+            assert!(!is_variable_delimiter(&c));
+        }
+    }
+
+    #[test]
+    fn test_is_here_document_delimiter() {
+        for c in " \t\n;&|<>()".chars() {
+            assert!(is_here_document_delimiter(&c));
+        }
+        for c in "a'\"\\$".chars() {
+            assert!(!is_here_document_delimiter(&c));
+        }
+    }
 }
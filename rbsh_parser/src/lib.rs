@@ -8,7 +8,12 @@ mod word;
 pub use error::{Error, ErrorKind};
 pub use lexer::Lexer;
 pub use location::{Annotate, Location};
-pub use parser::{parse_command_line, ConnecterKind, Redirect, RedirectKind, Unit, UnitKind};
+pub use parser::{
+    ast_tree, check_command_line, parse_array, parse_command_line, parse_word, unparse, CaseItem,
+    CaseTerminator, ConditionalExpression, ConnecterKind, FormatStyle, Redirect, RedirectKind,
+    Unit, UnitKind,
+};
+pub use parser::{visit, Fold, Visit, VisitMut};
 pub use token::{Token, TokenKind};
 pub use word::{Word, WordKind};
 
@@ -1,14 +1,25 @@
-use std::{any, fmt};
+use std::{
+    any, fmt,
+    hash::{Hash, Hasher},
+};
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Location {
     pub column: usize,
     pub line: usize,
+    pub offset: usize, // the byte offset in the input
+    pub end: usize,    // the byte offset after the token
 }
 
 impl Location {
     pub fn new(column: usize, line: usize) -> Self {
-        Self { column, line }
+        Self {
+            column,
+            line,
+            offset: 0,
+            end: 0,
+        }
     }
 
     pub fn from(other: &Self) -> Self {
@@ -19,6 +30,11 @@ impl Location {
         Self::new(other.column + column_offset, other.line + line_offset)
     }
 
+    // Returns the byte range of the token in the input.
+    pub fn range(&self) -> std::ops::Range<usize> {
+        self.offset..self.end.max(self.offset)
+    }
+
     pub fn next(&mut self) {
         self.column += 1;
     }
@@ -29,6 +45,29 @@ impl Location {
     }
 }
 
+// Debug, PartialEq and Hash use only the column and the line. The byte offsets depend on
+// the input passed to the lexer, so that the same command read in a different input would
+// not be equal. Use range() to check the offsets.
+impl fmt::Debug for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.column, self.line)
+    }
+}
+
+impl PartialEq for Location {
+    fn eq(&self, other: &Self) -> bool {
+        (self.column, self.line) == (other.column, other.line)
+    }
+}
+
+impl Eq for Location {}
+
+impl Hash for Location {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.column, self.line).hash(state);
+    }
+}
+
 impl Default for Location {
     fn default() -> Self {
         Self::new(1, 1)
@@ -49,6 +88,7 @@ macro_rules! location {
 }
 
 #[derive(Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Annotate<T: fmt::Debug + Clone> {
     pub value: T,
     pub location: Location,
@@ -70,7 +110,7 @@ impl<T: fmt::Debug + Clone> fmt::Debug for Annotate<T> {
 
         f.debug_struct(struct_name)
             .field(field_name, &self.value)
-            .field("location", &(self.location.column, self.location.line))
+            .field("location", &self.location)
             .finish()
     }
 }
@@ -91,6 +131,24 @@ mod test {
         location.next();
         assert_eq!(location.column, 2);
         assert_eq!(Location::default(), Location::new(1, 1));
+
+        location.offset = 3;
+        assert_eq!(location.range(), 3..3);
+        location.end = 5;
+        assert_eq!(location.range(), 3..5);
+        assert_eq!((location.offset, location.end), (3, 5));
+        location.end = 1;
+        assert_eq!(location.range(), 3..3);
+
+        // The byte offsets are ignored by Debug, PartialEq and Hash.
+        let hash = |location: &Location| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            location.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(location, Location::new(2, 3));
+        assert_eq!(hash(&location), hash(&Location::new(2, 3)));
+        assert_eq!(format!("{location:?}"), "(2, 3)");
     }
 
     #[test]
@@ -1,7 +1,22 @@
-use rbsh_parser::parse_command_line;
-use std::io::{self, Write};
+use rbsh_parser::{ast_tree, parse_command_line, Unit};
+use std::{
+    env,
+    io::{self, Write},
+};
+
+// Prints the units as JSON with --json, or as a tree.
+fn dump(units: &[Unit], json: bool) {
+    if json {
+        #[cfg(feature = "serde")]
+        return println!("{}", serde_json::to_string_pretty(units).unwrap());
+        #[cfg(not(feature = "serde"))]
+        return eprintln!("Error: --json requires the serde feature");
+    }
+    print!("{}", ast_tree(units));
+}
 
 fn main() {
+    let json = env::args().skip(1).any(|arg| arg == "--json");
     let mut input = String::new();
     loop {
         if input.is_empty() {
@@ -14,12 +29,12 @@ fn main() {
         let mut buffer = String::new();
         match io::stdin().read_line(&mut buffer) {
             Err(_) => break,
-            Ok(n) if n == 0 => break,
+            Ok(0) => break,
             Ok(_) => {
                 let buffer = buffer.trim_end();
                 if buffer.is_empty() {
                     match parse_command_line(&input, 0) {
-                        Ok(_) => (),
+                        Ok((units, _)) => dump(&units, json),
                         Err(e) => eprintln!("Error: {e:?}"),
                     }
                     input.clear();
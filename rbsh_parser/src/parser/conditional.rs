@@ -0,0 +1,335 @@
+use crate::{lexer::Lexer, Error, Location, Result, Token, Word};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ConditionalExpression {
+    Word(Vec<Word>),                                             // word
+    Unary(String, Vec<Word>),                                    // op word, e.g. -f word
+    Binary(String, Vec<Word>, Vec<Word>), // word op word, e.g. word1 == word2
+    Not(Box<ConditionalExpression>),      // ! expr
+    And(Box<ConditionalExpression>, Box<ConditionalExpression>), // expr1 && expr2
+    Or(Box<ConditionalExpression>, Box<ConditionalExpression>), // expr1 || expr2
+}
+
+const UNARY_OPERATORS: [&str; 24] = [
+    "-a", "-b", "-c", "-d", "-e", "-f", "-g", "-h", "-k", "-n", "-p", "-r", "-s", "-t", "-u", "-v",
+    "-w", "-x", "-z", "-G", "-L", "-N", "-O", "-S",
+];
+
+const BINARY_OPERATORS: [&str; 15] = [
+    "=", "==", "!=", "<", ">", "=~", "-eq", "-ne", "-lt", "-le", "-gt", "-ge", "-nt", "-ot", "-ef",
+];
+
+// A part of the expression. Operators are "&&", "||", "(" and ")".
+#[derive(Debug, PartialEq, Eq)]
+enum Element {
+    Operator(&'static str),
+    Word(String),
+}
+
+// Parses the inside of [[ ... ]]. The words are not split by spaces in double quotes, and
+// the right side of "=~" may contain parentheses and '|' as a regular expression.
+pub fn parse_conditional(input: &str, location: Location) -> Result<ConditionalExpression> {
+    let elements = split(input);
+    let mut parser = Parser {
+        elements: &elements,
+        position: 0,
+        error: || Error::unexpected_token(&Token::conditional(input, location)),
+        line: location.line.saturating_sub(1),
+    };
+
+    let expression = parser.or()?;
+    match parser.position < elements.len() {
+        true => Err((parser.error)()),
+        false => Ok(expression),
+    }
+}
+
+struct Parser<'a, F: Fn() -> Error> {
+    elements: &'a [Element],
+    position: usize,
+    error: F,
+    line: usize,
+}
+
+impl<F: Fn() -> Error> Parser<'_, F> {
+    fn peek(&self) -> Option<&Element> {
+        self.elements.get(self.position)
+    }
+
+    fn peek_word(&self, n: usize) -> Option<&str> {
+        match self.elements.get(self.position + n) {
+            Some(Element::Word(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn next_if_operator(&mut self, op: &str) -> bool {
+        let found = self.peek() == Some(&Element::Operator(static_operator(op)));
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<ConditionalExpression> {
+        let mut left = self.and()?;
+        while self.next_if_operator("||") {
+            let right = self.and()?;
+            left = ConditionalExpression::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<ConditionalExpression> {
+        let mut left = self.not()?;
+        while self.next_if_operator("&&") {
+            let right = self.not()?;
+            left = ConditionalExpression::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<ConditionalExpression> {
+        // "!" alone is a word.
+        let next = self.elements.get(self.position + 1);
+        if self.peek_word(0) == Some("!")
+            && matches!(next, Some(Element::Word(_) | Element::Operator("(")))
+        {
+            self.position += 1;
+            return Ok(ConditionalExpression::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<ConditionalExpression> {
+        if self.next_if_operator("(") {
+            let expression = self.or()?;
+            return match self.next_if_operator(")") {
+                true => Ok(expression),
+                false => Err((self.error)()),
+            };
+        }
+
+        let first = self.peek_word(0).ok_or_else(&self.error)?.to_string();
+        let second = self.peek_word(1).map(|s| s.to_string());
+        match second {
+            Some(op) if BINARY_OPERATORS.contains(&op.as_str()) => {
+                let right = self.peek_word(2).ok_or_else(&self.error)?.to_string();
+                self.position += 3;
+                Ok(ConditionalExpression::Binary(
+                    op,
+                    self.words(&first)?,
+                    self.words(&right)?,
+                ))
+            }
+            Some(word) if UNARY_OPERATORS.contains(&first.as_str()) => {
+                self.position += 2;
+                Ok(ConditionalExpression::Unary(first, self.words(&word)?))
+            }
+            _ => {
+                self.position += 1;
+                Ok(ConditionalExpression::Word(self.words(&first)?))
+            }
+        }
+    }
+
+    fn words(&self, s: &str) -> Result<Vec<Word>> {
+        Lexer::new(s, self.line).parameter_word()
+    }
+}
+
+fn static_operator(op: &str) -> &'static str {
+    match op {
+        "&&" => "&&",
+        "||" => "||",
+        "(" => "(",
+        _ => ")",
+    }
+}
+
+// Splits the input into words and operators. Quoted strings, escaped characters and
+// expansions such as $(...) are part of a word.
+fn split(input: &str) -> Vec<Element> {
+    let chars = input.chars().collect::<Vec<_>>();
+    let mut result = vec![];
+    let mut regex = false;
+    let mut i = 0;
+    loop {
+        while i < chars.len() && chars[i].is_whitespace() {
+            i += 1;
+        }
+        let rest = &chars[i..];
+        match rest {
+            [] => break,
+            ['&', '&', ..] | ['|', '|', ..] if !regex => {
+                result.push(Element::Operator(static_operator(
+                    &rest[..2].iter().collect::<String>(),
+                )));
+                i += 2;
+                continue;
+            }
+            ['(' | ')', ..] if !regex => {
+                result.push(Element::Operator(static_operator(&rest[0].to_string())));
+                i += 1;
+                continue;
+            }
+            _ => (),
+        }
+
+        let mut word = String::new();
+        let mut depth = 0; // parentheses in a regular expression, or $(...) and ${...}
+        while i < chars.len() {
+            let c = chars[i];
+            match c {
+                _ if c.is_whitespace() && depth == 0 => break,
+                '\\' => {
+                    word.push(c);
+                    i += 1;
+                    if i < chars.len() {
+                        word.push(chars[i]);
+                    }
+                }
+                '\'' | '"' => {
+                    word.push(c);
+                    i += 1;
+                    while i < chars.len() && chars[i] != c {
+                        if chars[i] == '\\' && c == '"' && i + 1 < chars.len() {
+                            word.push(chars[i]);
+                            i += 1;
+                        }
+                        word.push(chars[i]);
+                        i += 1;
+                    }
+                    if i < chars.len() {
+                        word.push(c);
+                    }
+                }
+                '$' if matches!(chars.get(i + 1), Some('(' | '{')) => {
+                    word.push(c);
+                    word.push(chars[i + 1]);
+                    depth += 1;
+                    i += 1;
+                }
+                '(' | '{' if depth > 0 || regex && c == '(' => {
+                    depth += 1;
+                    word.push(c);
+                }
+                ')' | '}' if depth > 0 => {
+                    depth -= 1;
+                    word.push(c);
+                }
+                '(' | ')' if !regex => break,
+                '&' | '|' if !regex && chars.get(i + 1) == Some(&c) => break,
+                _ => word.push(c),
+            }
+            i += 1;
+        }
+
+        regex = word == "=~";
+        result.push(Element::Word(word));
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{location, WordKind};
+
+    macro_rules! word {
+        ($s: expr) => {
+            vec![Word::normal($s, location!())]
+        };
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(
+            split(r#"-f "a b" && ! (x||y)"#),
+            vec![
+                Element::Word("-f".to_string()),
+                Element::Word(r#""a b""#.to_string()),
+                Element::Operator("&&"),
+                Element::Word("!".to_string()),
+                Element::Operator("("),
+                Element::Word("x".to_string()),
+                Element::Operator("||"),
+                Element::Word("y".to_string()),
+                Element::Operator(")"),
+            ]
+        );
+        assert_eq!(
+            split("$(a b) =~ ^(x|y z)$ )"),
+            vec![
+                Element::Word("$(a b)".to_string()),
+                Element::Word("=~".to_string()),
+                Element::Word("^(x|y z)$".to_string()),
+                Element::Operator(")"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_conditional() {
+        let parse = |s| parse_conditional(s, location!());
+        assert_eq!(parse(" a "), Ok(ConditionalExpression::Word(word!("a"))));
+        assert_eq!(
+            parse("-f a"),
+            Ok(ConditionalExpression::Unary("-f".to_string(), word!("a")))
+        );
+        assert_eq!(parse("-f"), Ok(ConditionalExpression::Word(word!("-f"))));
+        assert_eq!(
+            parse("a == b"),
+            Ok(ConditionalExpression::Binary(
+                "==".to_string(),
+                word!("a"),
+                word!("b")
+            ))
+        );
+        assert_eq!(
+            parse("! a && (b || -n c)"),
+            Ok(ConditionalExpression::And(
+                Box::new(ConditionalExpression::Not(Box::new(
+                    ConditionalExpression::Word(word!("a"))
+                ))),
+                Box::new(ConditionalExpression::Or(
+                    Box::new(ConditionalExpression::Word(word!("b"))),
+                    Box::new(ConditionalExpression::Unary("-n".to_string(), word!("c")))
+                ))
+            ))
+        );
+        assert_eq!(
+            parse("'a' != \"$b\""),
+            Ok(ConditionalExpression::Binary(
+                "!=".to_string(),
+                vec![Word::quote("a", location!())],
+                vec![Word::quoted(
+                    "b".to_string(),
+                    WordKind::Variable,
+                    location!(3)
+                )]
+            ))
+        );
+
+        let error = Err(Error::unexpected_token(&Token::conditional(
+            "a b",
+            location!(),
+        )));
+        assert_eq!(parse_conditional("a b", location!()), error);
+        assert_eq!(
+            parse_conditional("(a", location!()),
+            Err(Error::unexpected_token(&Token::conditional(
+                "(a",
+                location!()
+            )))
+        );
+        assert_eq!(
+            parse_conditional("", location!()),
+            Err(Error::unexpected_token(&Token::conditional(
+                "",
+                location!()
+            )))
+        );
+    }
+}
@@ -1,203 +1,141 @@
-use super::Unit;
+use super::{
+    visit::{self, Visit},
+    CaseItem, ConditionalExpression, ConnecterKind, Redirect, RedirectKind, Unit, UnitKind,
+};
+use crate::Word;
+
+// Returns the units as an indented tree, which is shown by --dump-ast=tree. Each line is
+// a node followed by its children indented by 2 spaces, e.g.
+//
+//     If
+//       SimpleCommand
+//         Word
+//           Normal "a" at 1:4
+pub fn ast_tree(units: &[Unit]) -> String {
+    let mut tree = Tree::default();
+    tree.visit_units(units);
+    tree.out
+}
+
 #[cfg(not(feature = "debug"))]
 pub(crate) fn print(_: &[Unit]) {}
 
 #[cfg(feature = "debug")]
 pub(crate) fn print(units: &[Unit]) {
     eprintln!("debug(parser): results:");
-    pp::pp(units, 0);
+    for line in ast_tree(units).lines() {
+        eprintln!("debug(parser): {line}");
+    }
 }
 
-#[cfg(feature = "debug")]
-mod pp {
-    use crate::{ConnecterKind, Redirect, Unit, UnitKind, Word};
+#[derive(Default)]
+struct Tree {
+    out: String,
+    depth: usize,
+}
 
-    macro_rules! debug {
-        ($indent:expr, $($args:tt)* ) => {{
-            let i = " ".repeat($indent);
-            eprint!("debug(parser): {}", i);
-            eprintln!($($args)*);
-        }};
-    }
+impl Tree {
+    // Writes the node and visits its children one level deeper.
+    fn node(&mut self, label: &str, children: impl FnOnce(&mut Self)) {
+        self.out.push_str(&"  ".repeat(self.depth));
+        self.out.push_str(label);
+        self.out.push('\n');
 
-    pub(crate) fn pp(units: &[Unit], indent: usize) {
-        for unit in units.iter() {
-            let indent = indent + 1;
-            pp_unit(unit, indent);
-        }
+        self.depth += 1;
+        children(self);
+        self.depth -= 1;
     }
+}
 
-    fn pp_unit(unit: &Unit, indent: usize) {
-        let background = unit.background;
-        match &unit.kind {
-            UnitKind::SimpleCommand { command, redirect } => {
-                print_simple_command(indent, command, redirect, background)
-            }
-            UnitKind::Connecter { left, right, kind } => {
-                print_connecter(indent, left, right, kind, background)
-            }
-            UnitKind::Pipe { left, right, both } => {
-                print_pipe(indent, left, right, both, background)
-            }
-            UnitKind::If {
-                condition,
-                true_case,
-                false_case,
-                redirect,
-            } => print_if(
-                indent, condition, true_case, false_case, redirect, false, background,
-            ),
-            UnitKind::Unless {
-                condition,
-                false_case,
-                true_case,
-                redirect,
-            } => print_if(
-                indent, condition, false_case, true_case, redirect, true, background,
-            ),
-            UnitKind::While {
-                condition,
-                command,
-                redirect,
-            } => print_while(indent, condition, command, redirect, false, background),
-            UnitKind::Until {
-                condition,
-                command,
-                redirect,
-            } => print_while(indent, condition, command, redirect, true, background),
-            UnitKind::For {
-                identifier,
-                list,
-                command,
-                redirect,
-            } => print_for(indent, identifier, list, command, redirect, background),
+impl Visit for Tree {
+    fn visit_unit(&mut self, unit: &Unit) {
+        let mut label = match &unit.kind {
+            UnitKind::SimpleCommand { .. } => "SimpleCommand".to_string(),
+            UnitKind::Connecter { kind, .. } => match kind {
+                ConnecterKind::And => "And".to_string(),
+                ConnecterKind::Or => "Or".to_string(),
+            },
+            UnitKind::Pipe { both: false, .. } => "Pipe".to_string(),
+            UnitKind::Pipe { both: true, .. } => "Pipe |&".to_string(),
+            UnitKind::Not { .. } => "Not".to_string(),
+            UnitKind::Time { posix: false, .. } => "Time".to_string(),
+            UnitKind::Time { posix: true, .. } => "Time -p".to_string(),
+            UnitKind::If { .. } => "If".to_string(),
+            UnitKind::Unless { .. } => "Unless".to_string(),
+            UnitKind::While { .. } => "While".to_string(),
+            UnitKind::Until { .. } => "Until".to_string(),
+            UnitKind::For { .. } => "For".to_string(),
+            UnitKind::Select { .. } => "Select".to_string(),
+            UnitKind::ArithmeticFor { .. } => "ArithmeticFor".to_string(),
+            UnitKind::Case { .. } => "Case".to_string(),
+            UnitKind::Function { .. } => "Function".to_string(),
+            UnitKind::Group { .. } => "Group".to_string(),
+            UnitKind::Subshell { .. } => "Subshell".to_string(),
+            UnitKind::Arithmetic { .. } => "Arithmetic".to_string(),
+            UnitKind::Conditional { .. } => "Conditional".to_string(),
+            UnitKind::Coproc { .. } => "Coproc".to_string(),
+            UnitKind::Comment { text, .. } => format!("Comment {text:?}"),
+            UnitKind::BlankLine => "BlankLine".to_string(),
+        };
+        if unit.background {
+            label.push_str(" &");
         }
+        self.node(&label, |tree| visit::visit_unit(tree, unit));
     }
 
-    fn print_simple_command(
-        indent: usize,
-        command: &[Vec<Word>],
-        redirect: &Option<Vec<Redirect>>,
-        backgroud: bool,
-    ) {
-        debug!(indent, "SimpleCommand:");
-        debug!(indent + 1, "command: {:?}", command);
-        debug!(indent + 1, "redirect: {:?}", redirect);
-        debug!(indent + 1, "background: {}", backgroud);
+    fn visit_case_item(&mut self, item: &CaseItem) {
+        let label = format!("CaseItem {:?}", item.terminator);
+        self.node(&label, |tree| visit::visit_case_item(tree, item));
     }
 
-    fn print_connecter(
-        indent: usize,
-        left: &Unit,
-        right: &Unit,
-        kind: &ConnecterKind,
-        background: bool,
-    ) {
-        match kind {
-            ConnecterKind::And => debug!(indent, "And:"),
-            ConnecterKind::Or => debug!(indent, "Or:"),
-        }
-
-        debug!(indent + 1, "left:");
-        pp_unit(left, indent + 2);
-
-        debug!(indent + 1, "right:");
-        pp_unit(right, indent + 2);
-
-        debug!(indent + 1, "background: {}", background);
+    fn visit_conditional(&mut self, expression: &ConditionalExpression) {
+        let label = match expression {
+            ConditionalExpression::Word(_) => "Word".to_string(),
+            ConditionalExpression::Unary(op, _) => format!("Unary {op}"),
+            ConditionalExpression::Binary(op, _, _) => format!("Binary {op}"),
+            ConditionalExpression::Not(_) => "Not".to_string(),
+            ConditionalExpression::And(..) => "And".to_string(),
+            ConditionalExpression::Or(..) => "Or".to_string(),
+        };
+        self.node(&label, |tree| visit::visit_conditional(tree, expression));
     }
 
-    fn print_pipe(indent: usize, left: &Unit, right: &Unit, both: &bool, background: bool) {
-        debug!(indent, "Pipe:");
-
-        debug!(indent + 1, "left:");
-        pp_unit(left, indent + 2);
-
-        debug!(indent + 1, "right:");
-        pp_unit(right, indent + 2);
-
-        debug!(indent + 1, "both: {}", both);
-        debug!(indent + 1, "background: {}", background);
+    fn visit_redirect(&mut self, redirect: &Redirect) {
+        let flag = |set: bool, name: &str| match set {
+            true => format!(" {name}"),
+            false => String::new(),
+        };
+        let label = match &redirect.value {
+            RedirectKind::ReadFrom(fd, _) => format!("ReadFrom {fd}"),
+            RedirectKind::WriteTo(fd, _, force) => format!("WriteTo {fd}{}", flag(*force, "force")),
+            RedirectKind::WriteBoth(_) => "WriteBoth".to_string(),
+            RedirectKind::Copy(src, dest, close) => {
+                format!("Copy {src} {dest}{}", flag(*close, "close"))
+            }
+            RedirectKind::Duplicate(fd, _) => format!("Duplicate {fd}"),
+            RedirectKind::Append(fd, _) => format!("Append {fd}"),
+            RedirectKind::AppendBoth(_) => "AppendBoth".to_string(),
+            RedirectKind::Close(fd) => format!("Close {fd}"),
+            RedirectKind::ReadWrite(fd, _) => format!("ReadWrite {fd}"),
+            RedirectKind::HereDocument(fd, _) => format!("HereDocument {fd}"),
+            RedirectKind::HereString(fd, _) => format!("HereString {fd}"),
+        };
+        self.node(&label, |tree| visit::visit_redirect(tree, redirect));
     }
 
-    fn print_if(
-        indent: usize,
-        condition: &Unit,
-        true_case: &[Unit],
-        false_case: &Option<Vec<Unit>>,
-        redirect: &Option<Vec<Redirect>>,
-        reverse: bool,
-        background: bool,
-    ) {
-        if reverse {
-            debug!(indent, "Unless:")
-        } else {
-            debug!(indent, "If:")
-        }
-
-        debug!(indent + 1, "condition:");
-        pp_unit(condition, indent + 2);
-
-        debug!(indent + 1, "true_case:");
-        pp(true_case, indent + 2);
-
-        if let Some(units) = false_case {
-            debug!(indent + 1, "false_case:");
-            pp(units, indent + 2);
-        } else {
-            debug!(indent + 1, "false_case: null");
-        }
-
-        debug!(indent + 1, "redirect: {:?}", redirect);
-        debug!(indent + 1, "background: {}", background);
+    fn visit_wordlist(&mut self, words: &[Word]) {
+        self.node("Word", |tree| visit::visit_wordlist(tree, words));
     }
 
-    fn print_while(
-        indent: usize,
-        condition: &Unit,
-        command: &[Unit],
-        redirect: &Option<Vec<Redirect>>,
-        reverse: bool,
-        background: bool,
-    ) {
-        if reverse {
-            debug!(indent, "Until:");
-        } else {
-            debug!(indent, "While:");
+    fn visit_word(&mut self, word: &Word) {
+        let location = word.location;
+        let mut label = format!(
+            "{:?} {:?} at {}:{}",
+            word.kind, word.string, location.line, location.column
+        );
+        if word.quoted {
+            label.push_str(" quoted");
         }
-
-        debug!(indent + 1, "condition:");
-        pp_unit(condition, indent + 2);
-
-        debug!(indent + 1, "command:");
-        pp(command, indent + 2);
-
-        debug!(indent + 1, "redirect: {:?}", redirect);
-        debug!(indent + 1, "background: {}", background);
-    }
-
-    fn print_for(
-        indent: usize,
-        identifier: &Vec<Word>,
-        list: &Option<Vec<Vec<Word>>>,
-        command: &[Unit],
-        redirect: &Option<Vec<Redirect>>,
-        background: bool,
-    ) {
-        debug!(indent, "For:");
-
-        debug!(indent + 1, "identifier: {:?}", identifier);
-
-        if let Some(list) = list {
-            debug!(indent + 1, "list: {:?}", list);
-        } else {
-            debug!(indent + 1, "list: null");
-        }
-
-        debug!(indent + 1, "command:");
-        pp(command, indent + 2);
-
-        debug!(indent + 1, "redirect: {:?}", redirect);
-        debug!(indent + 1, "background: {}", background);
+        self.node(&label, |_| ());
     }
 }
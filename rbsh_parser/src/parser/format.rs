@@ -0,0 +1,679 @@
+use super::{
+    CaseItem, CaseTerminator, ConditionalExpression, ConnecterKind, Redirect, RedirectKind, Unit,
+    UnitKind,
+};
+use crate::{Word, WordKind};
+use std::{fmt, os::unix::io::RawFd};
+
+// The keywords of compound commands in the formatted source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FormatStyle {
+    #[default]
+    Bash, // if ...; then ... fi, while ...; do ... done, case ... in ... esac
+    Ruby, // if ... end, while ... end, case ... when ... end, def ... end
+}
+
+// Returns the source of the units with one command per line, indented by 2 spaces.
+// Parsing the result gives the same units except for the style of keywords.
+pub fn unparse(units: &[Unit], style: FormatStyle) -> String {
+    let mut formatter = Formatter::new(style);
+    formatter.list(units, 0);
+    formatter.flush();
+    formatter.out
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = unparse(std::slice::from_ref(self), FormatStyle::Bash);
+        f.write_str(source.trim_end_matches('\n'))
+    }
+}
+
+struct Formatter {
+    style: FormatStyle,
+    out: String,
+    line: String,
+    here_documents: Vec<String>, // written after the current line
+}
+
+impl Formatter {
+    fn new(style: FormatStyle) -> Self {
+        Self {
+            style,
+            out: String::new(),
+            line: String::new(),
+            here_documents: vec![],
+        }
+    }
+
+    fn push(&mut self, s: &str) {
+        self.line.push_str(s);
+    }
+
+    // Writes the current line followed by the bodies of its here-documents.
+    fn flush(&mut self) {
+        if !self.line.trim().is_empty() {
+            self.out.push_str(self.line.trim_end());
+            self.out.push('\n');
+        }
+        self.line.clear();
+        for body in self.here_documents.drain(..) {
+            self.out.push_str(&body);
+        }
+    }
+
+    fn newline(&mut self, indent: usize) {
+        self.flush();
+        self.line = "  ".repeat(indent);
+    }
+
+    fn list(&mut self, units: &[Unit], indent: usize) {
+        let mut blank = false;
+        for (i, unit) in units.iter().enumerate() {
+            match &unit.kind {
+                // Consecutive blank lines and those at the start of a list are removed.
+                UnitKind::BlankLine => blank = i > 0,
+                UnitKind::Comment {
+                    text,
+                    trailing: true,
+                } if !self.line.trim().is_empty() && !self.line.trim().starts_with('#') => {
+                    self.push(" #");
+                    self.push(text);
+                }
+                _ => {
+                    self.flush();
+                    if blank {
+                        self.out.push('\n');
+                        blank = false;
+                    }
+                    self.newline(indent);
+                    self.unit(unit, indent);
+                }
+            }
+        }
+    }
+
+    fn unit(&mut self, unit: &Unit, indent: usize) {
+        match &unit.kind {
+            UnitKind::SimpleCommand { command, redirect } => {
+                let command = command.iter().map(|w| word(w)).collect::<Vec<_>>();
+                self.push(&command.join(" "));
+                self.redirect(redirect);
+            }
+            UnitKind::Connecter { left, right, kind } => {
+                self.unit(left, indent);
+                self.push(match kind {
+                    ConnecterKind::And => " && ",
+                    ConnecterKind::Or => " || ",
+                });
+                self.unit(right, indent);
+            }
+            UnitKind::Pipe { left, right, both } => {
+                self.unit(left, indent);
+                self.push(if *both { " |& " } else { " | " });
+                self.unit(right, indent);
+            }
+            UnitKind::Not { command } => {
+                self.push("! ");
+                self.unit(command, indent);
+            }
+            UnitKind::Time { command, posix } => {
+                self.push(if *posix { "time -p " } else { "time " });
+                self.unit(command, indent);
+            }
+            UnitKind::If {
+                condition,
+                true_case,
+                false_case,
+                redirect,
+            } => self.if_clause("if", condition, true_case, false_case, redirect, indent),
+            UnitKind::Unless {
+                condition,
+                false_case,
+                true_case,
+                redirect,
+            } => {
+                // unless can not be closed by fi.
+                self.push("unless ");
+                self.unit(condition, indent);
+                self.list(false_case, indent + 1);
+                if let Some(true_case) = true_case {
+                    self.newline(indent);
+                    self.push("else");
+                    self.list(true_case, indent + 1);
+                }
+                self.newline(indent);
+                self.push("end");
+                self.redirect(redirect);
+            }
+            UnitKind::While {
+                condition,
+                command,
+                redirect,
+            } => self.while_clause("while", condition, command, redirect, indent),
+            UnitKind::Until {
+                condition,
+                command,
+                redirect,
+            } => self.while_clause("until", condition, command, redirect, indent),
+            UnitKind::For {
+                identifier,
+                list,
+                command,
+                redirect,
+            } => self.for_clause("for", identifier, list, command, redirect, indent),
+            UnitKind::Select {
+                identifier,
+                list,
+                command,
+                redirect,
+            } => self.for_clause("select", identifier, list, command, redirect, indent),
+            UnitKind::ArithmeticFor {
+                init,
+                condition,
+                step,
+                command,
+                redirect,
+            } => {
+                self.push(&format!(
+                    "for (({};{};{}))",
+                    init.string, condition.string, step.string
+                ));
+                self.loop_body(command, redirect, indent);
+            }
+            UnitKind::Case {
+                word,
+                items,
+                else_case,
+                redirect,
+            } => self.case_clause(word, items, else_case, redirect, indent),
+            UnitKind::Function { name, params, body } => match &body.kind {
+                // Parameters can be written only in ruby style.
+                UnitKind::Group { command, redirect }
+                    if self.style == FormatStyle::Ruby || !params.is_empty() =>
+                {
+                    self.push("def ");
+                    self.push(&word(name));
+                    if !params.is_empty() {
+                        let params = params.iter().map(|w| word(w)).collect::<Vec<_>>();
+                        self.push(&format!("({})", params.join(", ")));
+                    }
+                    self.list(command, indent + 1);
+                    self.newline(indent);
+                    self.push("end");
+                    self.redirect(redirect);
+                }
+                _ => {
+                    self.push(&word(name));
+                    self.push("() ");
+                    self.unit(body, indent);
+                }
+            },
+            UnitKind::Group { command, redirect } => {
+                match self.inline(command) {
+                    Some(s) if s.ends_with('&') => self.push(&format!("{{ {s} }}")),
+                    Some(s) => self.push(&format!("{{ {s}; }}")),
+                    None => {
+                        self.push("{");
+                        self.list(command, indent + 1);
+                        self.newline(indent);
+                        self.push("}");
+                    }
+                }
+                self.redirect(redirect);
+            }
+            UnitKind::Subshell { command, redirect } => {
+                match self.inline(command) {
+                    // (( is the start of an arithmetic command.
+                    Some(s) if s.starts_with('(') => self.push(&format!("( {s} )")),
+                    Some(s) => self.push(&format!("({s})")),
+                    None => {
+                        self.push("(");
+                        self.list(command, indent + 1);
+                        self.newline(indent);
+                        self.push(")");
+                    }
+                }
+                self.redirect(redirect);
+            }
+            UnitKind::Arithmetic {
+                expression,
+                redirect,
+            } => {
+                self.push(&format!("(({}))", expression.string));
+                self.redirect(redirect);
+            }
+            UnitKind::Conditional {
+                expression,
+                redirect,
+            } => {
+                self.push(&format!("[[ {} ]]", conditional(expression)));
+                self.redirect(redirect);
+            }
+            UnitKind::Coproc { name, command } => {
+                self.push("coproc ");
+                if let Some(name) = name {
+                    self.push(&word(name));
+                    self.push(" ");
+                }
+                self.unit(command, indent);
+            }
+            UnitKind::Comment { text, .. } => self.push(&format!("#{text}")),
+            UnitKind::BlankLine => (),
+        }
+
+        if unit.background {
+            self.push(" &");
+        }
+    }
+
+    // Returns the command in a single line, e.g. the inside of { command; }.
+    fn inline(&self, units: &[Unit]) -> Option<String> {
+        match units {
+            [unit] if !matches!(unit.kind, UnitKind::Comment { .. } | UnitKind::BlankLine) => {
+                let mut formatter = Formatter::new(self.style);
+                formatter.unit(unit, 0);
+                match formatter.out.is_empty() && formatter.here_documents.is_empty() {
+                    true => Some(formatter.line),
+                    false => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    // A nested if in the else clause is written as elif.
+    fn if_clause(
+        &mut self,
+        keyword: &str,
+        condition: &Unit,
+        true_case: &[Unit],
+        false_case: &Option<Vec<Unit>>,
+        redirect: &Option<Vec<Redirect>>,
+        indent: usize,
+    ) {
+        self.push(keyword);
+        self.push(" ");
+        self.unit(condition, indent);
+        if self.style == FormatStyle::Bash {
+            self.push("; then");
+        }
+        self.list(true_case, indent + 1);
+
+        match false_case.as_deref() {
+            Some(
+                [Unit {
+                    kind:
+                        UnitKind::If {
+                            condition,
+                            true_case,
+                            false_case,
+                            redirect: elif_redirect,
+                        },
+                    background: false,
+                }],
+            ) if redirect.is_none() => {
+                self.newline(indent);
+                let keyword = match self.style {
+                    FormatStyle::Bash => "elif",
+                    FormatStyle::Ruby => "elsif",
+                };
+                return self.if_clause(
+                    keyword,
+                    condition,
+                    true_case,
+                    false_case,
+                    elif_redirect,
+                    indent,
+                );
+            }
+            Some(false_case) => {
+                self.newline(indent);
+                self.push("else");
+                self.list(false_case, indent + 1);
+            }
+            None => (),
+        }
+
+        self.newline(indent);
+        self.push(match self.style {
+            FormatStyle::Bash => "fi",
+            FormatStyle::Ruby => "end",
+        });
+        self.redirect(redirect);
+    }
+
+    fn while_clause(
+        &mut self,
+        keyword: &str,
+        condition: &Unit,
+        command: &[Unit],
+        redirect: &Option<Vec<Redirect>>,
+        indent: usize,
+    ) {
+        self.push(keyword);
+        self.push(" ");
+        self.unit(condition, indent);
+        self.loop_body(command, redirect, indent);
+    }
+
+    fn for_clause(
+        &mut self,
+        keyword: &str,
+        identifier: &[Word],
+        list: &Option<Vec<Vec<Word>>>,
+        command: &[Unit],
+        redirect: &Option<Vec<Redirect>>,
+        indent: usize,
+    ) {
+        self.push(keyword);
+        self.push(" ");
+        self.push(&word(identifier));
+        if let Some(list) = list {
+            self.push(" in");
+            for w in list {
+                self.push(" ");
+                self.push(&word(w));
+            }
+        }
+        self.loop_body(command, redirect, indent);
+    }
+
+    // do commands done
+    fn loop_body(&mut self, command: &[Unit], redirect: &Option<Vec<Redirect>>, indent: usize) {
+        if self.style == FormatStyle::Bash {
+            self.push("; do");
+        }
+        self.list(command, indent + 1);
+        self.newline(indent);
+        self.push(match self.style {
+            FormatStyle::Bash => "done",
+            FormatStyle::Ruby => "end",
+        });
+        self.redirect(redirect);
+    }
+
+    // The ruby style can not write ';&' and ';;&', and the bash style writes the else
+    // clause as the pattern '*'.
+    fn case_clause(
+        &mut self,
+        w: &[Word],
+        items: &[CaseItem],
+        else_case: &Option<Vec<Unit>>,
+        redirect: &Option<Vec<Redirect>>,
+        indent: usize,
+    ) {
+        let ruby = self.style == FormatStyle::Ruby
+            && items
+                .iter()
+                .all(|item| item.terminator == CaseTerminator::Break);
+
+        self.push("case ");
+        self.push(&word(w));
+        if ruby {
+            for item in items {
+                let patterns = item.patterns.iter().map(|w| word(w)).collect::<Vec<_>>();
+                self.newline(indent);
+                self.push(&format!("when {}", patterns.join(", ")));
+                self.list(&item.command, indent + 1);
+            }
+            if let Some(else_case) = else_case {
+                self.newline(indent);
+                self.push("else");
+                self.list(else_case, indent + 1);
+            }
+            self.newline(indent);
+            self.push("end");
+        } else {
+            self.push(" in");
+            for item in items {
+                let patterns = item.patterns.iter().map(|w| word(w)).collect::<Vec<_>>();
+                self.newline(indent + 1);
+                self.push(&format!("{})", patterns.join(" | ")));
+                self.list(&item.command, indent + 2);
+                self.newline(indent + 2);
+                self.push(match item.terminator {
+                    CaseTerminator::Break => ";;",
+                    CaseTerminator::FallThrough => ";&",
+                    CaseTerminator::Continue => ";;&",
+                });
+            }
+            if let Some(else_case) = else_case {
+                self.newline(indent + 1);
+                self.push("*)");
+                self.list(else_case, indent + 2);
+                self.newline(indent + 2);
+                self.push(";;");
+            }
+            self.newline(indent);
+            self.push("esac");
+        }
+        self.redirect(redirect);
+    }
+
+    // Redirects are written after the words, e.g. `cmd arg >file 2>&1`.
+    fn redirect(&mut self, redirect: &Option<Vec<Redirect>>) {
+        for r in redirect.iter().flatten() {
+            if !self.line.is_empty() && !self.line.ends_with(' ') {
+                self.push(" ");
+            }
+            let s = self.redirect_internal(r);
+            self.push(&s);
+        }
+    }
+
+    fn redirect_internal(&mut self, r: &Redirect) -> String {
+        // The file descriptor is omitted if it is the default.
+        let fd = |fd: &RawFd, default: RawFd| match *fd == default {
+            true => String::new(),
+            false => fd.to_string(),
+        };
+
+        match &r.value {
+            RedirectKind::ReadFrom(n, w) => format!("{}<{}", fd(n, 0), word(w)),
+            RedirectKind::WriteTo(n, w, false) => format!("{}>{}", fd(n, 1), word(w)),
+            RedirectKind::WriteTo(n, w, true) => format!("{}>|{}", fd(n, 1), word(w)),
+            RedirectKind::WriteBoth(w) => format!("&>{}", word(w)),
+            RedirectKind::Copy(src, 0, close) => {
+                format!("<&{src}{}", if *close { "-" } else { "" })
+            }
+            RedirectKind::Copy(src, dest, close) => {
+                format!("{}>&{src}{}", fd(dest, 1), if *close { "-" } else { "" })
+            }
+            RedirectKind::Duplicate(0, w) => format!("<&{}", word(w)),
+            RedirectKind::Duplicate(dest, w) => format!("{}>&{}", fd(dest, 1), word(w)),
+            RedirectKind::Append(n, w) => format!("{}>>{}", fd(n, 1), word(w)),
+            RedirectKind::AppendBoth(w) => format!("&>>{}", word(w)),
+            RedirectKind::Close(0) => "<&-".to_string(),
+            RedirectKind::Close(n) => format!("{}>&-", fd(n, 1)),
+            RedirectKind::ReadWrite(n, w) => format!("{}<>{}", fd(n, 0), word(w)),
+            RedirectKind::HereDocument(n, body) => {
+                format!("{}<<{}", fd(n, 0), self.here_document(body))
+            }
+            RedirectKind::HereString(n, w) => format!("{}<<<{}", fd(n, 0), word(w)),
+        }
+    }
+
+    // Adds the body to be written after the line, and returns the delimiter. A body which is
+    // not expanded has the quoted delimiter.
+    fn here_document(&mut self, body: &[Word]) -> String {
+        let (text, quoted) = match body {
+            [w] if w.kind == WordKind::Quote => (w.string.clone(), true),
+            _ => {
+                let text = body
+                    .iter()
+                    .enumerate()
+                    .map(|(i, w)| match w.kind {
+                        WordKind::Normal => escape(&w.string, &['\\', '$', '`']),
+                        _ => expansion(w, body.get(i + 1)),
+                    })
+                    .collect::<String>();
+                (text, false)
+            }
+        };
+
+        let mut delimiter = "EOF".to_string();
+        let mut n = 0;
+        while text.lines().any(|line| line == delimiter) {
+            n += 1;
+            delimiter = format!("EOF{n}");
+        }
+        self.here_documents.push(format!("{text}{delimiter}\n"));
+
+        match quoted {
+            true => format!("'{delimiter}'"),
+            false => delimiter,
+        }
+    }
+}
+
+// The characters which are escaped in a word.
+const SPECIAL_CHARS: [char; 14] = [
+    ' ', '\t', '\\', '\'', '"', '`', '$', ';', '&', '|', '<', '>', '(', ')',
+];
+
+fn escape(s: &str, special: &[char]) -> String {
+    let mut result = String::new();
+    for c in s.chars() {
+        match c {
+            _ if special.contains(&c) => {
+                result.push('\\');
+                result.push(c);
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+// Returns the source of a literal unless the word has been changed after it was parsed.
+fn literal_source(w: &Word) -> Option<&str> {
+    let source = w.source.as_deref()?;
+    let string = match source.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+        Some(s) => s.to_string(),
+        None => {
+            let mut chars = source.chars();
+            let mut s = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => s.push(chars.next()?),
+                    _ => s.push(c),
+                }
+            }
+            s
+        }
+    };
+    (string == w.string).then_some(source)
+}
+
+// A literal parsed in double quotes is written in double quotes, and so is a literal
+// without its source if it has no character to be escaped.
+fn in_double_quotes(w: &Word) -> bool {
+    match w.kind {
+        WordKind::Quote if literal_source(w).is_some() => false,
+        WordKind::Quote if w.string.contains(['`', '\\']) => false,
+        WordKind::Quote => w.quoted || !w.string.contains(['"', '$']),
+        WordKind::Variable | WordKind::Parameter | WordKind::Command | WordKind::Arithmetic => {
+            w.quoted
+        }
+        _ => false,
+    }
+}
+
+// Writes a word. Literals and expansions in double quotes are put together in double quotes.
+fn word(words: &[Word]) -> String {
+    word_internal(words, true)
+}
+
+fn word_internal(words: &[Word], escaped: bool) -> String {
+    let mut result = String::new();
+    let mut in_quotes = false;
+    for (i, w) in words.iter().enumerate() {
+        let quoted = in_double_quotes(w);
+        if quoted != in_quotes {
+            result.push('"');
+            in_quotes = quoted;
+        }
+
+        if let Some(source) = literal_source(w) {
+            result.push_str(source);
+            continue;
+        }
+        match w.kind {
+            WordKind::Normal if !escaped => result.push_str(&w.string),
+            WordKind::Normal => {
+                // '#' at the start of a word is a comment.
+                if i == 0 && w.string.starts_with('#') {
+                    result.push('\\');
+                }
+                // A newline can not be escaped by a backslash.
+                result.push_str(&escape(&w.string, &SPECIAL_CHARS).replace('\n', "'\n'"))
+            }
+            WordKind::Quote if quoted => result.push_str(&escape(&w.string, &['"', '$'])),
+            WordKind::Quote => result.push_str(&format!("'{}'", w.string.replace('\'', r"'\''"))),
+            _ => result.push_str(&expansion(w, words.get(i + 1))),
+        }
+    }
+    if in_quotes {
+        result.push('"');
+    }
+    result
+}
+
+// $name is written as ${name} if it is followed by a character of a name in the same quotes.
+fn expansion(w: &Word, next: Option<&Word>) -> String {
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let s = &w.string;
+    match w.kind {
+        WordKind::Variable
+            if s.chars().all(is_name)
+                && matches!(next, Some(next)
+                    if matches!(next.kind, WordKind::Normal | WordKind::Quote)
+                        && in_double_quotes(next) == in_double_quotes(w)
+                        && next.string.starts_with(is_name)) =>
+        {
+            format!("${{{s}}}")
+        }
+        WordKind::Variable => format!("${s}"),
+        WordKind::Parameter => format!("${{{s}}}"),
+        // $(( is the start of an arithmetic expansion.
+        WordKind::Command if s.starts_with('(') => format!("$( {s})"),
+        WordKind::Command => format!("$({s})"),
+        WordKind::Arithmetic => format!("$(({s}))"),
+        WordKind::AnsiC => format!("$'{s}'"),
+        WordKind::Array => format!("({s})"),
+        WordKind::InputProcess => format!("<({s})"),
+        WordKind::OutputProcess => format!(">({s})"),
+        WordKind::Normal | WordKind::Quote => s.to_string(),
+    }
+}
+
+// The operands of && and || are put in parentheses if they are not parsed as such.
+fn conditional(expression: &ConditionalExpression) -> String {
+    use ConditionalExpression::*;
+
+    let operand = |e: &ConditionalExpression, parenthesize: bool| match parenthesize {
+        true => format!("( {} )", conditional(e)),
+        false => conditional(e),
+    };
+    match expression {
+        Word(w) => word(w),
+        Unary(op, w) => format!("{op} {}", word(w)),
+        // The right side of =~ is a regular expression.
+        Binary(op, left, right) if op == "=~" => {
+            format!("{} {op} {}", word(left), word_internal(right, false))
+        }
+        Binary(op, left, right) => format!("{} {op} {}", word(left), word(right)),
+        Not(e) => format!("! {}", operand(e, matches!(**e, And(..) | Or(..)))),
+        And(left, right) => format!(
+            "{} && {}",
+            operand(left, matches!(**left, Or(..))),
+            operand(right, matches!(**right, And(..) | Or(..)))
+        ),
+        Or(left, right) => format!(
+            "{} || {}",
+            operand(left, false),
+            operand(right, matches!(**right, Or(..)))
+        ),
+    }
+}
@@ -1,13 +1,20 @@
+mod conditional;
 mod debug;
+mod format;
 mod redirect;
 mod unit;
+pub mod visit;
 
+pub use conditional::ConditionalExpression;
+pub use debug::ast_tree;
+pub use format::{unparse, FormatStyle};
 pub use redirect::{parse_redirect, Redirect, RedirectKind};
-pub use unit::{ConnecterKind, Unit, UnitKind};
+pub use unit::{CaseItem, CaseTerminator, ConnecterKind, Unit, UnitKind};
+pub use visit::{Fold, Visit, VisitMut};
 
 use crate::{
-    lexer::{Lexer, LexerIterator},
-    Error, Result, TokenKind, Word,
+    lexer::{Lexer, LexerIterator, Trivia},
+    Error, ErrorKind, Location, Result, Token, TokenKind, Word, WordKind,
 };
 
 pub fn parse_command_line<S: AsRef<str>>(input: S, offset: usize) -> Result<(Vec<Unit>, bool)> {
@@ -17,21 +24,87 @@ pub fn parse_command_line<S: AsRef<str>>(input: S, offset: usize) -> Result<(Vec
     let ignore_history = lexer.skip_if_space()?;
 
     let mut result = Vec::new();
-    while let Some(unit) = parse_command(&mut lexer)? {
-        result.push(unit)
+    loop {
+        lexer.skip_if_space()?;
+        parse_newline_or_termination(&mut lexer)?;
+        push_trivia(&mut lexer, &mut result);
+        match parse_command(&mut lexer)? {
+            Some(unit) => result.push(unit),
+            None => break,
+        }
     }
+    push_trivia(&mut lexer, &mut result);
 
     debug::print(&result);
     Ok((result, ignore_history))
 }
 
+// Parses the whole input without executing it and returns all syntax errors. After an
+// error, parsing resumes at the next command separated by a newline or ';'.
+pub fn check_command_line<S: AsRef<str>>(input: S, offset: usize) -> Vec<Error> {
+    let mut lexer = Lexer::new(input.as_ref(), offset).iter();
+    let mut errors = vec![];
+    loop {
+        match lexer
+            .skip_if_space()
+            .and_then(|_| parse_command(&mut lexer))
+        {
+            Ok(Some(_)) => (),
+            Ok(None) => break,
+            Err(e) => {
+                // An unexpected separator which has been read ends the command by itself.
+                let separator = matches!(
+                    e.value,
+                    ErrorKind::UnexpectedToken(TokenKind::NewLine | TokenKind::Termination)
+                ) && lexer.location() != e.location;
+                let eof = e.value == ErrorKind::Eof;
+                errors.push(e);
+                if eof || !(separator || skip_command(&mut lexer)) {
+                    break;
+                }
+            }
+        }
+    }
+    errors
+}
+
+// Skips the tokens to the end of the command. Returns false at the end of the input.
+fn skip_command(lexer: &mut LexerIterator) -> bool {
+    loop {
+        match lexer.next() {
+            None
+            | Some(Err(Error {
+                value: ErrorKind::Eof,
+                ..
+            })) => return false,
+            Some(Ok(token))
+                if matches!(token.value, TokenKind::NewLine | TokenKind::Termination) =>
+            {
+                return true
+            }
+            _ => (),
+        }
+    }
+}
+
+// Parses a string as a single word without splitting it by spaces,
+// e.g. the default value of ${var:-word}.
+pub fn parse_word<S: AsRef<str>>(input: S, offset: usize) -> Result<Vec<Word>> {
+    Lexer::new(input.as_ref(), offset).parameter_word()
+}
+
+// Parses the inside of name=(...) as the elements of an array.
+pub fn parse_array<S: AsRef<str>>(input: S, offset: usize) -> Result<Vec<Vec<Word>>> {
+    Lexer::new(input.as_ref(), offset).array_elements()
+}
+
 fn parse_command(lexer: &mut LexerIterator) -> Result<Option<Unit>> {
     lexer.skip_if_space()?;
     parse_newline_or_termination(lexer)?;
 
     match lexer.peek() {
         None => Ok(None),
-        Some(_) => match parse_connecter(lexer)? {
+        Some(_) => match parse_pipeline(lexer)? {
             None => Err(error_unexpected_token(lexer)),
             Some(kind) => {
                 let background = match lexer.next_if(|kind| kind == &TokenKind::Background) {
@@ -45,6 +118,46 @@ fn parse_command(lexer: &mut LexerIterator) -> Result<Option<Unit>> {
     }
 }
 
+// [time [-p]] [!] pipeline
+fn parse_pipeline(lexer: &mut LexerIterator) -> Result<Option<UnitKind>> {
+    let token = match lexer.next_if(|kind| matches!(kind, &TokenKind::Bang | &TokenKind::Time)) {
+        Some(token) => token?,
+        None => return parse_connecter(lexer),
+    };
+
+    need_space(lexer)?;
+    let posix = token.value == TokenKind::Time
+        && match lexer
+            .next_if(|kind| matches!(kind, TokenKind::Word(s, WordKind::Normal, _) if s == "-p"))
+        {
+            Some(result) => {
+                result?;
+                need_space(lexer)?;
+                true
+            }
+            None => false,
+        };
+
+    let pipeline = match parse_pipeline(lexer)? {
+        Some(kind) => kind,
+        None => return Err(error_unexpected_token(lexer)),
+    };
+
+    // The prefix applies to the pipeline before "&&" or "||".
+    let prefix = |command| match token.value {
+        TokenKind::Bang => UnitKind::Not { command },
+        _ => UnitKind::Time { command, posix },
+    };
+    Ok(Some(match pipeline {
+        UnitKind::Connecter { left, right, kind } => UnitKind::Connecter {
+            left: Box::new(Unit::new(prefix(left), false)),
+            right,
+            kind,
+        },
+        pipeline => prefix(Box::new(Unit::new(pipeline, false))),
+    }))
+}
+
 fn parse_connecter(lexer: &mut LexerIterator) -> Result<Option<UnitKind>> {
     match parse_shell_command(lexer)? {
         None => Ok(None),
@@ -101,6 +214,26 @@ fn parse_connecter(lexer: &mut LexerIterator) -> Result<Option<UnitKind>> {
 }
 
 fn parse_shell_command(lexer: &mut LexerIterator) -> Result<Option<UnitKind>> {
+    match lexer.next_if(|kind| matches!(kind, &TokenKind::GroupStart | &TokenKind::SubshellStart)) {
+        Some(Ok(token)) => {
+            return parse_group_statement(lexer, token.value == TokenKind::SubshellStart).map(Some)
+        }
+        Some(Err(e)) => return Err(e),
+        None => (),
+    }
+
+    match lexer.next_if(|kind| matches!(kind, &TokenKind::Arithmetic(_))) {
+        Some(Ok(token)) => return parse_arithmetic_statement(lexer, token).map(Some),
+        Some(Err(e)) => return Err(e),
+        None => (),
+    }
+
+    match lexer.next_if(|kind| matches!(kind, &TokenKind::Conditional(_))) {
+        Some(Ok(token)) => return parse_conditional_statement(lexer, token).map(Some),
+        Some(Err(e)) => return Err(e),
+        None => (),
+    }
+
     match lexer.next_if(|kind| {
         matches!(
             kind,
@@ -109,6 +242,11 @@ fn parse_shell_command(lexer: &mut LexerIterator) -> Result<Option<UnitKind>> {
                 | &TokenKind::While
                 | &TokenKind::Until
                 | &TokenKind::For
+                | &TokenKind::Select
+                | &TokenKind::Case
+                | &TokenKind::Function
+                | &TokenKind::Def
+                | &TokenKind::Coproc
         )
     }) {
         Some(Ok(token)) => {
@@ -123,6 +261,11 @@ fn parse_shell_command(lexer: &mut LexerIterator) -> Result<Option<UnitKind>> {
                     parse_while_statement(lexer, kind == TokenKind::Until).map(Some)
                 }
                 TokenKind::For => parse_for_statement(lexer).map(Some),
+                TokenKind::Select => parse_select_statement(lexer).map(Some),
+                TokenKind::Case => parse_case_statement(lexer).map(Some),
+                TokenKind::Function => parse_function_statement(lexer).map(Some),
+                TokenKind::Def => parse_def_statement(lexer).map(Some),
+                TokenKind::Coproc => parse_coproc_statement(lexer).map(Some),
                 _ => unreachable![],
             }
         }
@@ -144,10 +287,12 @@ fn parse_if_statement(lexer: &mut LexerIterator, reverse: bool) -> Result<UnitKi
     loop {
         lexer.skip_if_space()?;
         parse_newline_or_termination(lexer)?;
+        push_trivia(lexer, &mut case1);
         case1.push(need_command(lexer)?);
 
         lexer.skip_if_space()?;
         parse_newline_or_termination(lexer)?;
+        push_trivia(lexer, &mut case1);
 
         match lexer.next_if(|kind| {
             matches!(
@@ -206,10 +351,12 @@ fn parse_else_clause(lexer: &mut LexerIterator) -> Result<Vec<Unit>> {
     loop {
         lexer.skip_if_space()?;
         parse_newline_or_termination(lexer)?;
+        push_trivia(lexer, &mut units);
         units.push(need_command(lexer)?);
 
         lexer.skip_if_space()?;
         parse_newline_or_termination(lexer)?;
+        push_trivia(lexer, &mut units);
         match lexer.next_if(|kind| matches!(kind, &TokenKind::Fi | &TokenKind::End)) {
             Some(Ok(_)) => break Ok(units),
             Some(Err(e)) => break Err(e),
@@ -231,10 +378,12 @@ fn parse_while_statement(lexer: &mut LexerIterator, reverse: bool) -> Result<Uni
     loop {
         lexer.skip_if_space()?;
         parse_newline_or_termination(lexer)?;
+        push_trivia(lexer, &mut command);
         command.push(need_command(lexer)?);
 
         lexer.skip_if_space()?;
         parse_newline_or_termination(lexer)?;
+        push_trivia(lexer, &mut command);
         match lexer.next_if(|kind| matches!(kind, &TokenKind::Done | &TokenKind::End)) {
             Some(Ok(_)) => break,
             Some(Err(e)) => return Err(e),
@@ -262,28 +411,94 @@ fn parse_while_statement(lexer: &mut LexerIterator, reverse: bool) -> Result<Uni
 }
 
 fn parse_for_statement(lexer: &mut LexerIterator) -> Result<UnitKind> {
-    let identifier = parse_wordlist(lexer).and_then(|result| match result {
+    match lexer.next_if(|kind| matches!(kind, &TokenKind::Arithmetic(_))) {
+        Some(Ok(token)) => return parse_arithmetic_for_statement(lexer, token),
+        Some(Err(e)) => return Err(e),
+        None => (),
+    }
+
+    let identifier = parse_identifier(lexer)?;
+    let list = parse_in_list(lexer)?;
+    let (command, redirect) = parse_for_body(lexer)?;
+
+    Ok(UnitKind::For {
+        identifier,
+        list,
+        command,
+        redirect,
+    })
+}
+
+fn parse_select_statement(lexer: &mut LexerIterator) -> Result<UnitKind> {
+    let identifier = parse_identifier(lexer)?;
+    let list = parse_in_list(lexer)?;
+    let (command, redirect) = parse_for_body(lexer)?;
+
+    Ok(UnitKind::Select {
+        identifier,
+        list,
+        command,
+        redirect,
+    })
+}
+
+fn parse_identifier(lexer: &mut LexerIterator) -> Result<Vec<Word>> {
+    parse_wordlist(lexer).and_then(|result| match result {
         Some(wordlist) => Ok(wordlist),
         None => Err(error_unexpected_token(lexer)),
-    })?;
+    })
+}
 
+// [in word ...] of for and select
+fn parse_in_list(lexer: &mut LexerIterator) -> Result<Option<Vec<Vec<Word>>>> {
     lexer.skip_if_space_or_newline()?;
-    let list =
-        lexer
-            .next_if(|kind| kind == &TokenKind::In)
-            .map_or(Ok(None), |result| match result {
-                Ok(_) => {
-                    need_space(lexer)?;
-                    let mut list = Vec::new();
-                    while let Some(wordlist) = parse_wordlist(lexer)? {
-                        list.push(wordlist);
-                        lexer.skip_if_space()?;
-                    }
-                    Ok(Some(list))
+    lexer
+        .next_if(|kind| kind == &TokenKind::In)
+        .map_or(Ok(None), |result| match result {
+            Ok(_) => {
+                need_space(lexer)?;
+                let mut list = Vec::new();
+                while let Some(wordlist) = parse_wordlist(lexer)? {
+                    list.push(wordlist);
+                    lexer.skip_if_space()?;
                 }
-                Err(e) => Err(e),
-            })?;
+                Ok(Some(list))
+            }
+            Err(e) => Err(e),
+        })
+}
+
+// for ((init; condition; step))
+fn parse_arithmetic_for_statement(lexer: &mut LexerIterator, token: Token) -> Result<UnitKind> {
+    let expr = match &token.value {
+        TokenKind::Arithmetic(expr) => expr,
+        _ => unreachable![],
+    };
+
+    let mut column = token.location.column + 2; // after "(("
+    let mut words = Vec::new();
+    for expr in expr.split(';') {
+        let location = Location::new(column, token.location.line);
+        words.push(Word::new(expr.to_string(), WordKind::Arithmetic, location));
+        column += expr.chars().count() + 1;
+    }
+    let (init, condition, step) = match <[Word; 3]>::try_from(words) {
+        Ok([init, condition, step]) => (init, condition, step),
+        Err(_) => return Err(Error::unexpected_token(&token)),
+    };
 
+    let (command, redirect) = parse_for_body(lexer)?;
+
+    Ok(UnitKind::ArithmeticFor {
+        init,
+        condition,
+        step,
+        command,
+        redirect,
+    })
+}
+
+fn parse_for_body(lexer: &mut LexerIterator) -> Result<(Vec<Unit>, Option<Vec<Redirect>>)> {
     lexer.skip_if_space()?;
     parse_newline_or_termination(lexer)?;
     if let Some(result) = lexer.next_if(|kind| kind == &TokenKind::Do) {
@@ -294,10 +509,12 @@ fn parse_for_statement(lexer: &mut LexerIterator) -> Result<UnitKind> {
     loop {
         lexer.skip_if_space()?;
         parse_newline_or_termination(lexer)?;
+        push_trivia(lexer, &mut command);
         command.push(need_command(lexer)?);
 
         lexer.skip_if_space()?;
         parse_newline_or_termination(lexer)?;
+        push_trivia(lexer, &mut command);
         match lexer.next_if(|kind| matches!(kind, &TokenKind::Done | &TokenKind::End)) {
             Some(Ok(_)) => break,
             Some(Err(e)) => return Err(e),
@@ -308,11 +525,347 @@ fn parse_for_statement(lexer: &mut LexerIterator) -> Result<UnitKind> {
 
     lexer.skip_if_space()?;
     let redirect = parse_redirect(lexer)?;
+    Ok((command, redirect))
+}
 
-    Ok(UnitKind::For {
-        identifier,
-        list,
-        command,
+fn parse_case_statement(lexer: &mut LexerIterator) -> Result<UnitKind> {
+    let word = parse_wordlist(lexer).and_then(|result| match result {
+        Some(wordlist) => Ok(wordlist),
+        None => Err(error_unexpected_token(lexer)),
+    })?;
+
+    lexer.skip_if_space()?;
+    let newline = parse_newline_or_termination(lexer)?.is_some();
+    let (items, else_case) = match lexer.next_if(|kind| kind == &TokenKind::In) {
+        Some(Ok(_)) => (parse_case_items(lexer)?, None),
+        Some(Err(e)) => return Err(e),
+        None if newline => parse_when_clauses(lexer)?,
+        None => return Err(error_unexpected_token(lexer)),
+    };
+
+    lexer.skip_if_space()?;
+    let redirect = parse_redirect(lexer)?;
+
+    Ok(UnitKind::Case {
+        word,
+        items,
+        else_case,
+        redirect,
+    })
+}
+
+// bash style: case word in pattern [| pattern]...) commands ;; ... esac
+fn parse_case_items(lexer: &mut LexerIterator) -> Result<Vec<CaseItem>> {
+    let mut items: Vec<CaseItem> = Vec::new();
+    loop {
+        lexer.skip_if_space()?;
+        parse_newline_or_termination(lexer)?;
+        if let Some(item) = items.last_mut() {
+            push_trivia(lexer, &mut item.command);
+        }
+        match lexer.next_if(|kind| matches!(kind, &TokenKind::Esac | &TokenKind::SubshellStart)) {
+            Some(Ok(token)) if token.value == TokenKind::Esac => break Ok(items),
+            Some(Ok(_)) => lexer.skip_if_space()?,
+            Some(Err(e)) => break Err(e),
+            None if lexer.peek().is_none() => break Err(Error::eof(lexer.location())),
+            None => false,
+        };
+
+        let mut patterns = Vec::new();
+        loop {
+            lexer.skip_if_space()?;
+            patterns.push(need_wordlist(lexer)?);
+            lexer.skip_if_space()?;
+            match lexer.next_if(|kind| matches!(kind, &TokenKind::Pipe | &TokenKind::SubshellEnd)) {
+                Some(Ok(token)) if token.value == TokenKind::SubshellEnd => break,
+                Some(Ok(_)) => (),
+                Some(Err(e)) => return Err(e),
+                None => return Err(error_unexpected_token(lexer)),
+            }
+        }
+
+        let command = parse_commands_until(lexer, |kind| {
+            matches!(
+                kind,
+                &TokenKind::CaseBreak
+                    | &TokenKind::CaseFallThrough
+                    | &TokenKind::CaseContinue
+                    | &TokenKind::Esac
+            )
+        })?;
+
+        let terminator = match lexer.next_if(|kind| kind != &TokenKind::Esac) {
+            Some(Ok(token)) => match token.value {
+                TokenKind::CaseFallThrough => CaseTerminator::FallThrough,
+                TokenKind::CaseContinue => CaseTerminator::Continue,
+                _ => CaseTerminator::Break,
+            },
+            Some(Err(e)) => return Err(e),
+            None => CaseTerminator::Break, // the last item can omit ';;'
+        };
+
+        items.push(CaseItem::new(patterns, command, terminator));
+    }
+}
+
+// ruby style: case word; when pattern [, pattern]... commands ... [else commands] end
+fn parse_when_clauses(lexer: &mut LexerIterator) -> Result<(Vec<CaseItem>, Option<Vec<Unit>>)> {
+    let mut items: Vec<CaseItem> = Vec::new();
+    loop {
+        lexer.skip_if_space()?;
+        parse_newline_or_termination(lexer)?;
+        if let Some(item) = items.last_mut() {
+            push_trivia(lexer, &mut item.command);
+        }
+        match lexer
+            .next_if(|kind| matches!(kind, &TokenKind::When | &TokenKind::Else | &TokenKind::End))
+        {
+            Some(Ok(token)) => match token.value {
+                TokenKind::When => (),
+                TokenKind::Else => {
+                    let else_case = parse_commands_until(lexer, |kind| kind == &TokenKind::End)?;
+                    lexer.next();
+                    break Ok((items, Some(else_case)));
+                }
+                _ => break Ok((items, None)),
+            },
+            Some(Err(e)) => break Err(e),
+            None if lexer.peek().is_none() => break Err(Error::eof(lexer.location())),
+            None => break Err(error_unexpected_token(lexer)),
+        }
+
+        let patterns = parse_comma_separated_wordlist(lexer)?;
+
+        lexer.skip_if_space()?;
+        need_newline_or_termination(lexer)?;
+        if let Some(result) = lexer.next_if(|kind| kind == &TokenKind::Then) {
+            result?;
+        }
+
+        let command = parse_commands_until(lexer, |kind| {
+            matches!(kind, &TokenKind::When | &TokenKind::Else | &TokenKind::End)
+        })?;
+        items.push(CaseItem::new(patterns, command, CaseTerminator::Break));
+    }
+}
+
+// Parses wordlists separated by commas, e.g. `when "a", "b"` or `def foo(a, b)`.
+fn parse_comma_separated_wordlist(lexer: &mut LexerIterator) -> Result<Vec<Vec<Word>>> {
+    let mut result = Vec::new();
+    loop {
+        lexer.skip_if_space()?;
+        let mut wordlist = need_wordlist(lexer)?;
+
+        let comma = match wordlist.last_mut() {
+            Some(word) if word.kind == WordKind::Normal && word.string.ends_with(',') => {
+                word.string.pop();
+                if word.string.is_empty() {
+                    wordlist.pop();
+                }
+                true
+            }
+            _ => false,
+        };
+        if !wordlist.is_empty() {
+            result.push(wordlist);
+        }
+
+        if !comma {
+            break Ok(result);
+        }
+        lexer.skip_if_space_or_newline()?;
+    }
+}
+
+// Parses commands until the token which satisfies `end` appears. The token is not consumed.
+fn parse_commands_until<F>(lexer: &mut LexerIterator, end: F) -> Result<Vec<Unit>>
+where
+    F: Fn(&TokenKind) -> bool,
+{
+    let mut command = Vec::new();
+    loop {
+        lexer.skip_if_space()?;
+        parse_newline_or_termination(lexer)?;
+        push_trivia(lexer, &mut command);
+        match lexer.peek() {
+            Some(Ok(token)) if end(&token.value) => break Ok(command),
+            Some(Err(_)) => break Err(lexer.next().unwrap().unwrap_err()),
+            None => break Err(Error::eof(lexer.location())),
+            _ => command.push(need_command(lexer)?),
+        }
+    }
+}
+
+// bash style: function name [()] compound-command
+fn parse_function_statement(lexer: &mut LexerIterator) -> Result<UnitKind> {
+    let name = need_wordlist(lexer)?;
+    lexer.skip_if_space()?;
+    if let Some(result) = lexer.next_if(|kind| kind == &TokenKind::SubshellStart) {
+        result?;
+        need_subshell_end(lexer)?;
+    }
+
+    parse_function_body(lexer, name)
+}
+
+fn parse_function_body(lexer: &mut LexerIterator, name: Vec<Word>) -> Result<UnitKind> {
+    lexer.skip_if_space()?;
+    parse_newline_or_termination(lexer)?;
+    let body = match parse_shell_command(lexer)? {
+        Some(kind) => Box::new(Unit::new(kind, false)),
+        None => return Err(error_unexpected_token(lexer)),
+    };
+
+    Ok(UnitKind::Function {
+        name,
+        params: vec![],
+        body,
+    })
+}
+
+// ruby style: def name[(param [, param]...)] commands end
+fn parse_def_statement(lexer: &mut LexerIterator) -> Result<UnitKind> {
+    let name = need_wordlist(lexer)?;
+
+    lexer.skip_if_space()?;
+    let params = match lexer.next_if(|kind| kind == &TokenKind::SubshellStart) {
+        Some(Ok(_)) => {
+            lexer.skip_if_space()?;
+            match lexer.next_if(|kind| kind == &TokenKind::SubshellEnd) {
+                Some(Ok(_)) => vec![],
+                Some(Err(e)) => return Err(e),
+                None => {
+                    let params = parse_comma_separated_wordlist(lexer)?;
+                    lexer.skip_if_space()?;
+                    need_subshell_end(lexer)?;
+                    params
+                }
+            }
+        }
+        Some(Err(e)) => return Err(e),
+        None => vec![],
+    };
+
+    let command = parse_commands_until(lexer, |kind| kind == &TokenKind::End)?;
+    lexer.next(); // remove 'end'
+
+    lexer.skip_if_space()?;
+    let redirect = parse_redirect(lexer)?;
+
+    Ok(UnitKind::Function {
+        name,
+        params,
+        body: Box::new(Unit::new(UnitKind::Group { command, redirect }, false)),
+    })
+}
+
+fn parse_group_statement(lexer: &mut LexerIterator, subshell: bool) -> Result<UnitKind> {
+    let end = match subshell {
+        true => TokenKind::SubshellEnd,
+        false => TokenKind::GroupEnd,
+    };
+
+    let mut command = Vec::new();
+    loop {
+        lexer.skip_if_space()?;
+        parse_newline_or_termination(lexer)?;
+        push_trivia(lexer, &mut command);
+        command.push(need_command(lexer)?);
+
+        lexer.skip_if_space()?;
+        parse_newline_or_termination(lexer)?;
+        push_trivia(lexer, &mut command);
+        match lexer.next_if(|kind| kind == &end) {
+            Some(Ok(_)) => break,
+            Some(Err(e)) => return Err(e),
+            None if lexer.peek().is_none() => return Err(Error::eof(lexer.location())),
+            None => (),
+        }
+    }
+
+    lexer.skip_if_space()?;
+    let redirect = parse_redirect(lexer)?;
+
+    Ok(match subshell {
+        true => UnitKind::Subshell { command, redirect },
+        false => UnitKind::Group { command, redirect },
+    })
+}
+
+// coproc [NAME] command
+// NAME is given only when the command is a group or a subshell, otherwise the first word
+// is the command name.
+fn parse_coproc_statement(lexer: &mut LexerIterator) -> Result<UnitKind> {
+    let (name, kind) = match parse_wordlist(lexer)? {
+        Some(word) => {
+            lexer.skip_if_space()?;
+            match lexer.peek() {
+                Some(Ok(token))
+                    if matches!(
+                        token.value,
+                        TokenKind::GroupStart | TokenKind::SubshellStart
+                    ) =>
+                {
+                    let kind = parse_shell_command(lexer)?.unwrap();
+                    (Some(word), kind)
+                }
+                _ => match parse_simple_command(lexer)? {
+                    Some(UnitKind::SimpleCommand {
+                        mut command,
+                        redirect,
+                    }) => {
+                        command.insert(0, word);
+                        (None, UnitKind::SimpleCommand { command, redirect })
+                    }
+                    Some(_) => return Err(error_unexpected_token(lexer)),
+                    None => (
+                        None,
+                        UnitKind::SimpleCommand {
+                            command: vec![word],
+                            redirect: None,
+                        },
+                    ),
+                },
+            }
+        }
+        None => match parse_shell_command(lexer)? {
+            Some(kind) => (None, kind),
+            None => return Err(error_unexpected_token(lexer)),
+        },
+    };
+
+    Ok(UnitKind::Coproc {
+        name,
+        command: Box::new(Unit::new(kind, false)),
+    })
+}
+
+fn parse_arithmetic_statement(lexer: &mut LexerIterator, token: Token) -> Result<UnitKind> {
+    let expression = match token.value {
+        TokenKind::Arithmetic(expr) => Word::new(expr, WordKind::Arithmetic, token.location),
+        _ => unreachable![],
+    };
+
+    lexer.skip_if_space()?;
+    let redirect = parse_redirect(lexer)?;
+
+    Ok(UnitKind::Arithmetic {
+        expression,
+        redirect,
+    })
+}
+
+fn parse_conditional_statement(lexer: &mut LexerIterator, token: Token) -> Result<UnitKind> {
+    let expression = match token.value {
+        TokenKind::Conditional(expr) => conditional::parse_conditional(&expr, token.location)?,
+        _ => unreachable![],
+    };
+
+    lexer.skip_if_space()?;
+    let redirect = parse_redirect(lexer)?;
+
+    Ok(UnitKind::Conditional {
+        expression,
         redirect,
     })
 }
@@ -323,15 +876,31 @@ fn parse_simple_command(lexer: &mut LexerIterator) -> Result<Option<UnitKind>> {
 
     loop {
         lexer.skip_if_space()?;
-        if let Some(mut r) = parse_redirect(lexer)? {
-            redirect.get_or_insert(Vec::new()).append(&mut r)
-        }
+        let found = match parse_redirect(lexer)? {
+            Some(mut r) => {
+                redirect.get_or_insert(Vec::new()).append(&mut r);
+                true
+            }
+            None => false,
+        };
 
         lexer.skip_if_space()?;
         match parse_wordlist(lexer)? {
             Some(wordlist) => command.push(wordlist),
+            None if found => continue,
             None => break,
         }
+
+        // name() compound-command
+        if command.len() == 1 && redirect.is_none() {
+            lexer.skip_if_space()?;
+            if let Some(result) = lexer.next_if(|kind| kind == &TokenKind::SubshellStart) {
+                result?;
+                lexer.skip_if_space()?;
+                need_subshell_end(lexer)?;
+                return parse_function_body(lexer, command.remove(0)).map(Some);
+            }
+        }
     }
 
     if command.is_empty() && redirect.is_none() {
@@ -349,7 +918,13 @@ fn parse_wordlist(lexer: &mut LexerIterator) -> Result<Option<Vec<Word>>> {
         let kind = token.value;
         let location = token.location;
         match kind {
-            TokenKind::Word(s, k) => result.push(Word::new(s, k, location)),
+            TokenKind::Word(s, k, q) => {
+                let mut word = Word::from_token(s, k, q, location);
+                if !q && matches!(k, WordKind::Normal | WordKind::Quote) {
+                    word.source = lexer.source(&location).map(str::to_string);
+                }
+                result.push(word)
+            }
             _ => unreachable![],
         }
     }
@@ -379,10 +954,32 @@ fn parse_newline_or_termination(lexer: &mut LexerIterator) -> Result<Option<()>>
     }
 }
 
+// Adds the comments and blank lines which have been read to the command list.
+fn push_trivia(lexer: &mut LexerIterator, units: &mut Vec<Unit>) {
+    for trivia in lexer.take_trivia() {
+        let kind = match trivia {
+            Trivia::Comment(text, trailing) => UnitKind::Comment { text, trailing },
+            Trivia::BlankLine => UnitKind::BlankLine,
+        };
+        units.push(Unit::new(kind, false));
+    }
+}
+
 fn need_command(lexer: &mut LexerIterator) -> Result<Unit> {
     parse_command(lexer).and_then(|result| result.ok_or_else(|| error_unexpected_token(lexer)))
 }
 
+fn need_wordlist(lexer: &mut LexerIterator) -> Result<Vec<Word>> {
+    parse_wordlist(lexer).and_then(|result| result.ok_or_else(|| error_unexpected_token(lexer)))
+}
+
+fn need_subshell_end(lexer: &mut LexerIterator) -> Result<()> {
+    match lexer.next_if(|kind| kind == &TokenKind::SubshellEnd) {
+        Some(result) => result.map(|_| ()),
+        None => Err(error_unexpected_token(lexer)),
+    }
+}
+
 fn need_space(lexer: &mut LexerIterator) -> Result<()> {
     if lexer.skip_if_space()? {
         Ok(())
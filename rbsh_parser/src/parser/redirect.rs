@@ -1,17 +1,23 @@
 use super::{error_unexpected_token, parse_wordlist};
-use crate::{lexer::LexerIterator, Annotate, Error, Location, Result, TokenKind, Word};
+use crate::{
+    lexer::LexerIterator, Annotate, Error, Location, Result, Token, TokenKind, Word, WordKind,
+};
 use std::os::unix::io::RawFd;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RedirectKind {
     ReadFrom(RawFd, Vec<Word>),      // fd filename / n<word
     WriteTo(RawFd, Vec<Word>, bool), // fd filename force / n>word
     WriteBoth(Vec<Word>),            // filename / &>word, >&word
     Copy(RawFd, RawFd, bool),        // fd(src) fd(dest) close? / n<&n, n<&n-
+    Duplicate(RawFd, Vec<Word>),     // fd(dest) word / n<&$word, n>&$word
     Append(RawFd, Vec<Word>),        // fd filename / n>>word
     AppendBoth(Vec<Word>),           // fd filename / &>>word
     Close(RawFd),                    // fd / n<&-, n>&-
     ReadWrite(RawFd, Vec<Word>),     // fd filename / n<>word
+    HereDocument(RawFd, Vec<Word>),  // fd body / n<<word, n<<-word
+    HereString(RawFd, Vec<Word>),    // fd word / n<<<word
 }
 
 pub type Redirect = Annotate<RedirectKind>;
@@ -48,6 +54,14 @@ impl Redirect {
     pub fn read_write(fd: RawFd, words: Vec<Word>, loc: Location) -> Self {
         Self::new(RedirectKind::ReadWrite(fd, words), loc)
     }
+
+    pub fn here_document(fd: RawFd, body: Vec<Word>, loc: Location) -> Self {
+        Self::new(RedirectKind::HereDocument(fd, body), loc)
+    }
+
+    pub fn here_string(fd: RawFd, words: Vec<Word>, loc: Location) -> Self {
+        Self::new(RedirectKind::HereString(fd, words), loc)
+    }
 }
 
 pub fn parse_redirect(lexer: &mut LexerIterator) -> Result<Option<Vec<Redirect>>> {
@@ -91,7 +105,7 @@ pub fn parse_redirect_internal(lexer: &mut LexerIterator) -> Result<Option<Redir
                     | &TokenKind::ReadClose
                     | &TokenKind::WriteClose
                     | &TokenKind::ReadWrite
-                    | &TokenKind::HereDocument
+                    | &TokenKind::HereDocument { .. }
                     | &TokenKind::HereString
             )
         }) {
@@ -107,8 +121,10 @@ pub fn parse_redirect_internal(lexer: &mut LexerIterator) -> Result<Option<Redir
                 TokenKind::ReadClose => parse_redirect_close(fd.unwrap_or(0)),
                 TokenKind::WriteClose => parse_redirect_close(fd.unwrap_or(1)),
                 TokenKind::ReadWrite => parse_redirect_read_write(lexer, fd.unwrap_or(0)),
-                TokenKind::HereDocument => return Err(Error::unimplemented(token)),
-                TokenKind::HereString => return Err(Error::unimplemented(token)),
+                TokenKind::HereDocument(body) => {
+                    Ok(RedirectKind::HereDocument(fd.unwrap_or(0), body))
+                }
+                TokenKind::HereString => parse_redirect_here_string(lexer, fd.unwrap_or(0)),
                 _ => unreachable![],
             }?,
             Some(Err(e)) => return Err(e),
@@ -118,6 +134,10 @@ pub fn parse_redirect_internal(lexer: &mut LexerIterator) -> Result<Option<Redir
         None => return Ok(None),
     };
 
+    let location = Location {
+        end: lexer.end(),
+        ..location
+    };
     let redirect = Redirect::new(redirect, location);
     Ok(Some(redirect))
 }
@@ -146,12 +166,20 @@ fn parse_redirect_copy(lexer: &mut LexerIterator, dest: RawFd) -> Result<Redirec
                 let src = src
                     .parse::<RawFd>()
                     .map_err(|_| Error::invalid_fd(&src, location))?;
-                let close = matches!(lexer.next_if(|k| k == &TokenKind::Hyphen), Some(_));
+                let close = lexer.next_if(|k| k == &TokenKind::Hyphen).is_some();
                 Ok(RedirectKind::Copy(src, dest, close))
             }
             _ => unreachable![],
         },
         None => match lexer.peek() {
+            // The fd is given by an expansion, e.g. n>&$fd
+            Some(Ok(Token {
+                value: TokenKind::Word(_, kind, _),
+                ..
+            })) if !matches!(kind, WordKind::Normal | WordKind::Quote | WordKind::AnsiC) => {
+                let words = parse_wordlist(lexer)?.unwrap();
+                Ok(RedirectKind::Duplicate(dest, words))
+            }
             Some(Ok(token)) => Err(Error::unexpected_token(token)),
             Some(Err(_)) => unreachable![],
             None => Err(Error::eof(lexer.location())),
@@ -175,6 +203,10 @@ fn parse_redirect_read_write(lexer: &mut LexerIterator, fd: RawFd) -> Result<Red
     parse_redirect_word(lexer).map(|w| RedirectKind::ReadWrite(fd, w))
 }
 
+fn parse_redirect_here_string(lexer: &mut LexerIterator, fd: RawFd) -> Result<RedirectKind> {
+    parse_redirect_word(lexer).map(|w| RedirectKind::HereString(fd, w))
+}
+
 fn parse_redirect_word(lexer: &mut LexerIterator) -> Result<Vec<Word>> {
     lexer.skip_if_space()?;
     match parse_wordlist(lexer)? {
@@ -337,6 +369,20 @@ mod test {
     #[test]
     fn test_writecopy() {
         assert_redirect!(">&123", ok![copy, 123, 1, false, location!(1, 1)]);
+        assert_redirect!(
+            "2>&${fd[1]}",
+            Ok(Some(vec![Redirect::new(
+                RedirectKind::Duplicate(
+                    2,
+                    vec![Word::new(
+                        "fd[1]".to_string(),
+                        WordKind::Parameter,
+                        location!(5, 1)
+                    )]
+                ),
+                location!(1, 1)
+            )]))
+        );
         assert_redirect!(">&123-", ok![copy, 123, 1, true, location!(1, 1)]);
         assert_redirect!("123>&456", ok![copy, 456, 123, false, location!(1, 1)]);
         assert_redirect!("123>&456-", ok![copy, 456, 123, true, location!(1, 1)]);
@@ -433,4 +479,76 @@ mod test {
             Err(Error::invalid_fd("12345678901234567890", location!(1, 1)))
         );
     }
+
+    #[test]
+    fn test_here_document() {
+        assert_redirect!(
+            "<<EOF\nfoobar\nEOF",
+            ok![
+                here_document,
+                0,
+                vec![Word::normal("foobar\n", location!(1, 2))],
+                location!(1, 1)
+            ]
+        );
+
+        assert_redirect!(
+            "3<<-EOF\n\tfoobar\n\tEOF",
+            ok![
+                here_document,
+                3,
+                vec![Word::normal("foobar\n", location!(1, 2))],
+                location!(1, 1)
+            ]
+        );
+
+        assert_redirect!("<<EOF\nfoobar", Err(Error::eof(location!(6, 1))));
+    }
+    #[test]
+    fn test_here_string() {
+        assert_redirect!(
+            "<<< foobar",
+            ok![
+                here_string,
+                0,
+                vec![Word::normal("foobar", location!(5, 1))],
+                location!(1, 1)
+            ]
+        );
+
+        assert_redirect!(
+            "3<<<$foo",
+            ok![
+                here_string,
+                3,
+                vec![Word::new(
+                    "foo".to_string(),
+                    WordKind::Variable,
+                    location!(6, 1)
+                )],
+                location!(1, 1)
+            ]
+        );
+
+        assert_redirect!(
+            "<<<&",
+            Err(Error::unexpected_token(&Token::background(location!(4, 1))))
+        );
+    }
+    // Location compares only the column and the line, so the byte range is checked here.
+    #[test]
+    fn redirect_range() {
+        let ranges = |s: &str| {
+            parse_redirect(&mut lex!(s))
+                .unwrap()
+                .unwrap()
+                .into_iter()
+                .map(|r| r.location.range())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ranges("> foo"), vec![0..5]);
+        assert_eq!(ranges("2>&1<$x"), vec![0..4, 4..7]);
+        assert_eq!(ranges("12< \"a b\"$((1 + 2))>&-"), vec![0..19, 19..22]);
+        assert_eq!(ranges("<<< ${x:-y}"), vec![0..11]);
+    }
 }
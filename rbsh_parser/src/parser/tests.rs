@@ -213,6 +213,61 @@ mod test {
         };
     }
 
+    macro_rules! select_statement {
+        ($identifier:expr, $list:expr, $command:expr, $redirect:expr) => {
+            Unit::new(
+                UnitKind::Select {
+                    identifier: $identifier,
+                    list: $list,
+                    command: $command,
+                    redirect: $redirect,
+                },
+                false,
+            )
+        };
+        ($identifier:expr, $list:expr, $command:expr) => {
+            select_statement!($identifier, $list, $command, None)
+        };
+    }
+
+    macro_rules! group_statement {
+        ($kind:ident, $command:expr, $redirect:expr, $background:expr) => {
+            Unit::new(
+                UnitKind::$kind {
+                    command: $command,
+                    redirect: $redirect,
+                },
+                $background,
+            )
+        };
+        ($kind:ident, $command:expr, $redirect:expr) => {
+            group_statement!($kind, $command, $redirect, false)
+        };
+        ($kind:ident, $command:expr) => {
+            group_statement!($kind, $command, None)
+        };
+    }
+
+    macro_rules! case_statement {
+        ($word:expr, $items:expr, $else_case:expr, $redirect:expr, $background:expr) => {
+            Unit::new(
+                UnitKind::Case {
+                    word: $word,
+                    items: $items,
+                    else_case: $else_case,
+                    redirect: $redirect,
+                },
+                $background,
+            )
+        };
+        ($word:expr, $items:expr, $else_case:expr) => {
+            case_statement!($word, $items, $else_case, None, false)
+        };
+        ($word:expr, $items:expr) => {
+            case_statement!($word, $items, None)
+        };
+    }
+
     macro_rules! redirect {
         ($($name:ident($($e:expr),+)),+) => {
             Some(vec![$(redirect_inner!($name, $($e),+),)+])
@@ -352,6 +407,61 @@ mod test {
         )
     }
 
+    #[test]
+    fn here_document() {
+        assert_parse!(
+            r#"
+              cat <<EOF | cat
+              foo
+              EOF
+              bar
+            "#,
+            ok![vec![
+                connecter_pipe!(
+                    simple_command!(
+                        "cat",
+                        location!(),
+                        Some(vec![Redirect::here_document(
+                            0,
+                            vec![Word::normal("foo\n", location!(1, 2))],
+                            location!(5)
+                        )])
+                    ),
+                    simple_command!("cat", location!(13))
+                ),
+                simple_command!("bar", location!(1, 4))
+            ]]
+        );
+
+        assert_parse!(
+            "cat <<A <<B\na\nA\nb\nB\nbar",
+            ok![vec![
+                simple_command!(
+                    "cat",
+                    location!(),
+                    Some(vec![
+                        Redirect::here_document(
+                            0,
+                            vec![Word::normal("a\n", location!(1, 2))],
+                            location!(5)
+                        ),
+                        Redirect::here_document(
+                            0,
+                            vec![Word::normal("b\n", location!(1, 4))],
+                            location!(9)
+                        )
+                    ])
+                ),
+                simple_command!("bar", location!(1, 6))
+            ]]
+        );
+
+        assert_parse!(
+            "cat <<EOF\nfoo",
+            err![Error::eof(location!(10))]
+        );
+    }
+
     #[test]
     fn connecter_pipe() {
         assert_parse!(
@@ -827,6 +937,59 @@ mod test {
         );
     }
 
+    #[test]
+    fn pipeline_prefix() {
+        let not = |command| Unit::new(UnitKind::Not { command: Box::new(command) }, false);
+        let time = |command, posix| {
+            UnitKind::Time {
+                command: Box::new(command),
+                posix,
+            }
+        };
+
+        assert_parse!(
+            "! foo | bar",
+            ok![vec![not(connecter_pipe!(
+                simple_command!("foo", location!(3)),
+                simple_command!("bar", location!(9))
+            ))]]
+        );
+        assert_parse!(
+            "! foo && bar",
+            ok![vec![connecter_and!(
+                not(simple_command!("foo", location!(3))),
+                simple_command!("bar", location!(10))
+            )]]
+        );
+        assert_parse!(
+            "time -p foo || ! bar",
+            ok![vec![connecter_or!(
+                Unit::new(time(simple_command!("foo", location!(9)), true), false),
+                not(simple_command!("bar", location!(18)))
+            )]]
+        );
+        assert_parse!(
+            "time ! foo &",
+            ok![vec![Unit::new(
+                time(not(simple_command!("foo", location!(8))), false),
+                true
+            )]]
+        );
+        assert_parse!(
+            "! if foo; then bar; fi",
+            ok![vec![not(if_statement!(
+                simple_command!("foo", location!(6)),
+                vec![simple_command!("bar", location!(16))]
+            ))]]
+        );
+
+        assert_parse!("!", err![Error::eof(location!(2))]);
+        assert_parse!(
+            "time ;",
+            err![Error::unexpected_token(&Token::termination(location!(6)))]
+        );
+    }
+
     #[test]
     fn if_statement() {
         assert_parse!(
@@ -1618,4 +1781,869 @@ mod test {
             )]]
         );
     }
+
+    #[test]
+    fn select_statement() {
+        assert_parse!(
+            "select foo in bar baz; do qux; done",
+            ok![vec![select_statement!(
+                vec![Word::normal("foo", location!(8))],
+                Some(vec![
+                    vec![Word::normal("bar", location!(15))],
+                    vec![Word::normal("baz", location!(19))],
+                ]),
+                vec![simple_command!("qux", location!(27))]
+            )]]
+        );
+        assert_parse!(
+            "select foo; do bar; done > baz",
+            ok![vec![select_statement!(
+                vec![Word::normal("foo", location!(8))],
+                None,
+                vec![simple_command!("bar", location!(16))],
+                redirect![write_to("baz", location!(26))]
+            )]]
+        );
+        assert_parse!(
+            r#"
+              select foo in bar
+                qux
+              end
+            "#,
+            ok![vec![select_statement!(
+                vec![Word::normal("foo", location!(8))],
+                Some(vec![vec![Word::normal("bar", location!(15))]]),
+                vec![simple_command!("qux", location!(3, 2))]
+            )]]
+        );
+    }
+
+    #[test]
+    fn group_statement() {
+        assert_parse!(
+            "{ foo; bar; }",
+            ok![vec![group_statement!(
+                Group,
+                vec![
+                    simple_command!("foo", location!(3)),
+                    simple_command!("bar", location!(8))
+                ]
+            )]]
+        );
+
+        assert_parse!(
+            "{foo}",
+            ok![vec![group_statement!(
+                Group,
+                vec![simple_command!("foo", location!(2))]
+            )]]
+        );
+
+        assert_parse!(
+            r#"
+              {
+                foo
+              } > bar
+            "#,
+            ok![vec![group_statement!(
+                Group,
+                vec![simple_command!("foo", location!(3, 2))],
+                redirect![write_to("bar", location!(3, 3))]
+            )]]
+        );
+
+        assert_parse!(
+            "{ foo; } | bar",
+            ok![vec![connecter_pipe!(
+                group_statement!(Group, vec![simple_command!("foo", location!(3))]),
+                simple_command!("bar", location!(12))
+            )]]
+        );
+
+        assert_parse!("{ foo;", err![Error::eof(location!(7))]);
+    }
+
+    #[test]
+    fn subshell_statement() {
+        assert_parse!(
+            "( foo; bar )",
+            ok![vec![group_statement!(
+                Subshell,
+                vec![
+                    simple_command!("foo", location!(3)),
+                    simple_command!("bar", location!(8))
+                ]
+            )]]
+        );
+
+        assert_parse!(
+            "(foo) &",
+            ok![vec![group_statement!(
+                Subshell,
+                vec![simple_command!("foo", location!(2))],
+                None,
+                true
+            )]]
+        );
+
+        assert_parse!(
+            "foo && (bar)",
+            ok![vec![connecter_and!(
+                simple_command!("foo", location!(1)),
+                group_statement!(Subshell, vec![simple_command!("bar", location!(9))])
+            )]]
+        );
+
+        assert_parse!("(foo", err![Error::eof(location!(5))]);
+    }
+
+    #[test]
+    fn case_statement() {
+        assert_parse!(
+            "case foo in bar) baz;; esac",
+            ok![vec![case_statement!(
+                vec![Word::normal("foo", location!(6))],
+                vec![CaseItem::new(
+                    vec![vec![Word::normal("bar", location!(13))]],
+                    vec![simple_command!("baz", location!(18))],
+                    CaseTerminator::Break
+                )]
+            )]]
+        );
+
+        assert_parse!(
+            r#"
+              case foo in
+                (a | b)
+                  bar
+                  ;&
+                c) ;;&
+                *) baz
+              esac
+            "#,
+            ok![vec![case_statement!(
+                vec![Word::normal("foo", location!(6))],
+                vec![
+                    CaseItem::new(
+                        vec![
+                            vec![Word::normal("a", location!(4, 2))],
+                            vec![Word::normal("b", location!(8, 2))]
+                        ],
+                        vec![simple_command!("bar", location!(5, 3))],
+                        CaseTerminator::FallThrough
+                    ),
+                    CaseItem::new(
+                        vec![vec![Word::normal("c", location!(3, 5))]],
+                        vec![],
+                        CaseTerminator::Continue
+                    ),
+                    CaseItem::new(
+                        vec![vec![Word::normal("*", location!(3, 6))]],
+                        vec![simple_command!("baz", location!(6, 6))],
+                        CaseTerminator::Break
+                    )
+                ]
+            )]]
+        );
+
+        assert_parse!(
+            r#"
+              case foo
+              when "a", b
+                bar
+              when c; then baz
+              else
+                qux
+              end
+            "#,
+            ok![vec![case_statement!(
+                vec![Word::normal("foo", location!(6))],
+                vec![
+                    CaseItem::new(
+                        vec![
+                            vec![Word::quoted("a".to_string(), WordKind::Quote, location!(7, 2))],
+                            vec![Word::normal("b", location!(11, 2))]
+                        ],
+                        vec![simple_command!("bar", location!(3, 3))],
+                        CaseTerminator::Break
+                    ),
+                    CaseItem::new(
+                        vec![vec![Word::normal("c", location!(6, 4))]],
+                        vec![simple_command!("baz", location!(14, 4))],
+                        CaseTerminator::Break
+                    )
+                ],
+                Some(vec![simple_command!("qux", location!(3, 6))])
+            )]]
+        );
+
+        assert_parse!("case foo in bar)", err![Error::eof(location!(17))]);
+        assert_parse!(
+            "case foo bar",
+            err![Error::unexpected_token(&Token::word(
+                "bar",
+                WordKind::Normal,
+                location!(10)
+            ))]
+        );
+    }
+
+    #[test]
+    fn function_statement() {
+        assert_parse!(
+            "foo() { bar; }",
+            ok![vec![Unit::new(
+                UnitKind::Function {
+                    name: vec![Word::normal("foo", location!(1))],
+                    params: vec![],
+                    body: Box::new(group_statement!(
+                        Group,
+                        vec![simple_command!("bar", location!(9))]
+                    )),
+                },
+                false
+            )]]
+        );
+
+        assert_parse!(
+            r#"
+              function foo ()
+              {
+                bar
+              }
+            "#,
+            ok![vec![Unit::new(
+                UnitKind::Function {
+                    name: vec![Word::normal("foo", location!(10))],
+                    params: vec![],
+                    body: Box::new(group_statement!(
+                        Group,
+                        vec![simple_command!("bar", location!(3, 3))]
+                    )),
+                },
+                false
+            )]]
+        );
+
+        assert_parse!(
+            "function foo { bar; }",
+            ok![vec![Unit::new(
+                UnitKind::Function {
+                    name: vec![Word::normal("foo", location!(10))],
+                    params: vec![],
+                    body: Box::new(group_statement!(
+                        Group,
+                        vec![simple_command!("bar", location!(16))]
+                    )),
+                },
+                false
+            )]]
+        );
+
+        assert_parse!(
+            r#"
+              def foo(a, b)
+                bar
+              end
+            "#,
+            ok![vec![Unit::new(
+                UnitKind::Function {
+                    name: vec![Word::normal("foo", location!(5))],
+                    params: vec![
+                        vec![Word::normal("a", location!(9))],
+                        vec![Word::normal("b", location!(12))]
+                    ],
+                    body: Box::new(group_statement!(
+                        Group,
+                        vec![simple_command!("bar", location!(3, 2))]
+                    )),
+                },
+                false
+            )]]
+        );
+
+        assert_parse!(
+            "def foo; bar; end > baz",
+            ok![vec![Unit::new(
+                UnitKind::Function {
+                    name: vec![Word::normal("foo", location!(5))],
+                    params: vec![],
+                    body: Box::new(group_statement!(
+                        Group,
+                        vec![simple_command!("bar", location!(10))],
+                        redirect![write_to("baz", location!(19))]
+                    )),
+                },
+                false
+            )]]
+        );
+
+        assert_parse!("foo() {", err![Error::eof(location!(8))]);
+        assert_parse!("def foo(a", err![Error::eof(location!(10))]);
+        assert_parse!(
+            "function foo (bar)",
+            err![Error::unexpected_token(&Token::word(
+                "bar",
+                WordKind::Normal,
+                location!(15)
+            ))]
+        );
+    }
+
+    #[test]
+    fn arithmetic_statement() {
+        assert_parse!(
+            "((i += 1)) > foo && bar",
+            ok![vec![connecter_and!(
+                Unit::new(
+                    UnitKind::Arithmetic {
+                        expression: Word::new(
+                            "i += 1".to_string(),
+                            WordKind::Arithmetic,
+                            location!(1)
+                        ),
+                        redirect: redirect![write_to("foo", location!(12))],
+                    },
+                    false
+                ),
+                simple_command!("bar", location!(21))
+            )]]
+        );
+
+        // not an arithmetic command
+        assert_parse!(
+            "( (foo) )",
+            ok![vec![group_statement!(
+                Subshell,
+                vec![group_statement!(
+                    Subshell,
+                    vec![simple_command!("foo", location!(4))]
+                )]
+            )]]
+        );
+
+        assert_parse!("((1 + 2)", err![Error::unterminated_string(location!(1))]);
+    }
+
+    #[test]
+    fn conditional_statement() {
+        assert_parse!(
+            "[[ -n $a && $b == x* ]] > foo",
+            ok![vec![Unit::new(
+                UnitKind::Conditional {
+                    expression: ConditionalExpression::And(
+                        Box::new(ConditionalExpression::Unary(
+                            "-n".to_string(),
+                            vec![Word::new(
+                                "a".to_string(),
+                                WordKind::Variable,
+                                location!(2)
+                            )]
+                        )),
+                        Box::new(ConditionalExpression::Binary(
+                            "==".to_string(),
+                            vec![Word::new(
+                                "b".to_string(),
+                                WordKind::Variable,
+                                location!(2)
+                            )],
+                            vec![Word::normal("x*", location!())]
+                        ))
+                    ),
+                    redirect: redirect![write_to("foo", location!(25))],
+                },
+                false
+            )]]
+        );
+
+        // not a conditional command
+        assert_parse!("[[a]]", ok![vec![simple_command!("[[a]]", location!())]]);
+
+        assert_parse!("[[ a", err![Error::eof(location!(5))]);
+        assert_parse!(
+            "[[ a b ]]",
+            err![Error::unexpected_token(&Token::conditional(
+                " a b ",
+                location!()
+            ))]
+        );
+    }
+
+    #[test]
+    fn arithmetic_for_statement() {
+        macro_rules! arithmetic {
+            ($s:expr, $loc:expr) => {
+                Word::new($s.to_string(), WordKind::Arithmetic, $loc)
+            };
+        }
+
+        assert_parse!(
+            "for ((i = 0; i < 3; i++)); do foo; done",
+            ok![vec![Unit::new(
+                UnitKind::ArithmeticFor {
+                    init: arithmetic!("i = 0", location!(7)),
+                    condition: arithmetic!(" i < 3", location!(13)),
+                    step: arithmetic!(" i++", location!(20)),
+                    command: vec![simple_command!("foo", location!(31))],
+                    redirect: None,
+                },
+                false
+            )]]
+        );
+
+        assert_parse!(
+            r#"
+              for ((;;))
+                foo
+              end > bar
+            "#,
+            ok![vec![Unit::new(
+                UnitKind::ArithmeticFor {
+                    init: arithmetic!("", location!(7)),
+                    condition: arithmetic!("", location!(8)),
+                    step: arithmetic!("", location!(9)),
+                    command: vec![simple_command!("foo", location!(3, 2))],
+                    redirect: redirect![write_to("bar", location!(5, 3))],
+                },
+                false
+            )]]
+        );
+
+        assert_parse!(
+            "for ((;;)) do foo; done",
+            ok![vec![Unit::new(
+                UnitKind::ArithmeticFor {
+                    init: arithmetic!("", location!(7)),
+                    condition: arithmetic!("", location!(8)),
+                    step: arithmetic!("", location!(9)),
+                    command: vec![simple_command!("foo", location!(15))],
+                    redirect: None,
+                },
+                false
+            )]]
+        );
+
+        assert_parse!(
+            "for ((i = 0; i < 3)); do foo; done",
+            err![Error::unexpected_token(&Token::arithmetic(
+                "i = 0; i < 3",
+                location!(5)
+            ))]
+        );
+    }
+
+    #[test]
+    fn array_assignment() {
+        assert_parse!(
+            "a=(x 'y z')",
+            ok![vec![Unit::new(
+                UnitKind::SimpleCommand {
+                    command: vec![vec![
+                        Word::normal("a=", location!()),
+                        Word::new("x 'y z'".to_string(), WordKind::Array, location!(3)),
+                    ]],
+                    redirect: None,
+                },
+                false
+            )]]
+        );
+
+        assert_eq!(
+            parse_array("x 'y z'\n  [1]=$a # comment\n", 0),
+            Ok(vec![
+                vec![Word::normal("x", location!(1, 1))],
+                vec![Word::quote("y z", location!(3, 1))],
+                vec![
+                    Word::normal("[1]=", location!(3, 2)),
+                    Word::new("a".to_string(), WordKind::Variable, location!(8, 2)),
+                ],
+            ])
+        );
+        assert_eq!(
+            parse_array("if then", 0),
+            Ok(vec![
+                vec![Word::normal("if", location!(1, 1))],
+                vec![Word::normal("then", location!(4, 1))],
+            ])
+        );
+    }
+
+    #[test]
+    fn process_substitution() {
+        assert_parse!(
+            "diff <(sort a) >(wc -l) < <(cat b)",
+            ok![vec![Unit::new(
+                UnitKind::SimpleCommand {
+                    command: vec![
+                        vec![Word::normal("diff", location!())],
+                        vec![Word::new(
+                            "sort a".to_string(),
+                            WordKind::InputProcess,
+                            location!(7)
+                        )],
+                        vec![Word::new(
+                            "wc -l".to_string(),
+                            WordKind::OutputProcess,
+                            location!(17)
+                        )],
+                    ],
+                    redirect: Some(vec![Redirect::read_from(
+                        0,
+                        vec![Word::new(
+                            "cat b".to_string(),
+                            WordKind::InputProcess,
+                            location!(28)
+                        )],
+                        location!(25)
+                    )]),
+                },
+                false
+            )]]
+        );
+    }
+
+    #[test]
+    fn coproc_statement() {
+        assert_parse!(
+            "coproc NAME { cat; }",
+            ok![vec![Unit::new(
+                UnitKind::Coproc {
+                    name: Some(vec![Word::normal("NAME", location!(8))]),
+                    command: Box::new(group_statement!(
+                        Group,
+                        vec![simple_command!("cat", location!(15))]
+                    )),
+                },
+                false
+            )]]
+        );
+        assert_parse!(
+            "coproc cat -n > a",
+            ok![vec![Unit::new(
+                UnitKind::Coproc {
+                    name: None,
+                    command: Box::new(Unit::new(
+                        UnitKind::SimpleCommand {
+                            command: vec![
+                                vec![Word::normal("cat", location!(8))],
+                                vec![Word::normal("-n", location!(12))],
+                            ],
+                            redirect: redirect![write_to("a", location!(15))],
+                        },
+                        false
+                    )),
+                },
+                false
+            )]]
+        );
+        assert_parse!(
+            "coproc cat",
+            ok![vec![Unit::new(
+                UnitKind::Coproc {
+                    name: None,
+                    command: Box::new(simple_command!("cat", location!(8))),
+                },
+                false
+            )]]
+        );
+        assert_parse!(
+            "coproc (cat)",
+            ok![vec![Unit::new(
+                UnitKind::Coproc {
+                    name: None,
+                    command: Box::new(group_statement!(
+                        Subshell,
+                        vec![simple_command!("cat", location!(9))]
+                    )),
+                },
+                false
+            )]]
+        );
+        assert_parse!("coproc", err![Error::eof(location!(7))]);
+    }
+
+    #[test]
+    fn check() {
+        assert_eq!(check_command_line("echo a\necho b", 0), vec![]);
+        assert_eq!(
+            check_command_line("echo a; fi\necho b; then echo c\ndone done\n|| x\nif true", 2),
+            vec![
+                Error::unexpected_token(&Token::keyword("fi", location!(9, 3))),
+                Error::unexpected_token(&Token::keyword("then", location!(9, 4))),
+                Error::unexpected_token(&Token::keyword("done", location!(1, 5))),
+                Error::unexpected_token(&Token::or(location!(1, 6))),
+                Error::eof(location!(8, 7)),
+            ]
+        );
+    }
+
+    #[test]
+    fn comment() {
+        let comment = |text: &str, trailing| {
+            Unit::new(
+                UnitKind::Comment {
+                    text: text.to_string(),
+                    trailing,
+                },
+                false,
+            )
+        };
+        assert_parse!(
+            "# a\necho # b\n\n\n# c",
+            ok![vec![
+                comment(" a", false),
+                simple_command!("echo", location!(1, 2)),
+                comment(" b", true),
+                Unit::new(UnitKind::BlankLine, false),
+                comment(" c", false),
+            ]]
+        );
+        assert_parse!(
+            "{ # a\n  echo\n  # b\n}",
+            ok![vec![group_statement!(
+                Group,
+                vec![
+                    comment(" a", true),
+                    simple_command!("echo", location!(3, 2)),
+                    comment(" b", false),
+                ]
+            )]]
+        );
+        assert_parse!(
+            "case x in\na) echo ;; # a\n# b\nb) ;;\nesac",
+            ok![vec![Unit::new(
+                UnitKind::Case {
+                    word: vec![Word::normal("x", location!(6))],
+                    items: vec![
+                        CaseItem::new(
+                            vec![vec![Word::normal("a", location!(1, 2))]],
+                            vec![
+                                simple_command!("echo", location!(4, 2)),
+                                comment(" a", true),
+                                comment(" b", false),
+                            ],
+                            CaseTerminator::Break
+                        ),
+                        CaseItem::new(
+                            vec![vec![Word::normal("b", location!(1, 4))]],
+                            vec![],
+                            CaseTerminator::Break
+                        ),
+                    ],
+                    else_case: None,
+                    redirect: None,
+                },
+                false
+            )]]
+        );
+    }
+
+    #[test]
+    fn unparse() {
+        macro_rules! assert_unparse {
+            ($s: tt, $style: ident, $expect: tt) => {
+                let (units, _) = parse_command_line(indoc! {$s}, 0).unwrap();
+                let result = super::unparse(&units, FormatStyle::$style);
+                assert_eq!(result, indoc! {$expect});
+
+                // The result is formatted.
+                let (units, _) = parse_command_line(&result, 0).unwrap();
+                assert_eq!(super::unparse(&units, FormatStyle::$style), result);
+            };
+        }
+
+        assert_unparse!(
+            r#"
+            echo   a"b"'c'  "$x"y "$x"'y' a\ b\$c   2>&1 >out # x
+
+
+            if  true;then echo a
+            elif false; then echo b; else
+            echo c; fi"#,
+            Bash,
+            r#"
+            echo a"b"'c' "$x"y "$x"'y' a\ b\$c 2>&1 >out # x
+
+            if true; then
+              echo a
+            elif false; then
+              echo b
+            else
+              echo c
+            fi
+            "#
+        );
+        assert_unparse!(
+            r#"
+            while read l; do cat <<EOF; done
+            $l \$
+            EOF
+            case $x in a|b) echo ;& *) ;; esac"#,
+            Ruby,
+            r#"
+            while read l
+              cat <<EOF
+            $l \$
+            EOF
+            end
+            case $x in
+              a | b)
+                echo
+                ;&
+              *)
+                ;;
+            esac
+            "#
+        );
+        assert_unparse!(
+            r#"
+            def f(a, b)
+              (cd $a; ls) | { cat -n; } &&
+                [[ ! ( -f $a || $b =~ ^x(y|z)$ ) ]]
+            end
+            case $x
+            when a, b
+              echo
+            else
+              for i in 1 2; do echo $i; done
+            end"#,
+            Bash,
+            r#"
+            def f(a, b)
+              (
+                cd $a
+                ls
+              ) | { cat -n; } && [[ ! ( -f $a || $b =~ ^x(y|z)$ ) ]]
+            end
+            case $x in
+              a | b)
+                echo
+                ;;
+              *)
+                for i in 1 2; do
+                  echo $i
+                done
+                ;;
+            esac
+            "#
+        );
+
+        let (units, _) = parse_command_line("a | b &", 0).unwrap();
+        assert_eq!(units[0].to_string(), "a | b &");
+    }
+
+    #[test]
+    fn unparse_to_same_units() {
+        // Clears the locations, which are changed by the layout.
+        struct ClearLocation;
+        impl VisitMut for ClearLocation {
+            fn visit_redirect_mut(&mut self, redirect: &mut Redirect) {
+                redirect.location = Location::default();
+                visit::visit_redirect_mut(self, redirect)
+            }
+
+            fn visit_word_mut(&mut self, word: &mut Word) {
+                word.location = Location::default();
+            }
+        }
+
+        for input in [
+            r#"echo 'a'"b" \x a\ b\$c "a\"$x\$" '"' "'" $'\t' ${x:-'y'}z"#,
+            "cat <<'EOF' >>$f 2>&1\n$x\nEOF\n[[ $x =~ ^a\\.b$ && -n \"$y\" ]]",
+            "for i in *.rs \\*; do case $i in 'a'|\"b\") f \"$i\"'s';; esac; done &",
+        ] {
+            let (mut units, _) = parse_command_line(input, 0).unwrap();
+            let result = super::unparse(&units, FormatStyle::Bash);
+            let (mut reparsed, _) = parse_command_line(&result, 0).unwrap();
+
+            ClearLocation.visit_units_mut(&mut units);
+            ClearLocation.visit_units_mut(&mut reparsed);
+            assert_eq!(reparsed, units, "{result}");
+        }
+    }
+
+    #[test]
+    fn tree() {
+        let (units, _) = parse_command_line("f \"x$y\" >a 2>&1 | g &", 0).unwrap();
+        assert_eq!(
+            ast_tree(&units),
+            indoc! {r#"
+                Pipe
+                  SimpleCommand
+                    Word
+                      Normal "f" at 1:1
+                    Word
+                      Quote "x" at 1:4 quoted
+                      Variable "y" at 1:6 quoted
+                    WriteTo 1
+                      Word
+                        Normal "a" at 1:10
+                    Copy 1 2
+                  SimpleCommand &
+                    Word
+                      Normal "g" at 1:19
+            "#}
+        );
+    }
+
+    #[test]
+    fn visit() {
+        // Collects the names of commands.
+        struct Commands(Vec<String>);
+        impl Visit for Commands {
+            fn visit_unit(&mut self, unit: &Unit) {
+                if let UnitKind::SimpleCommand { command, .. } = &unit.kind {
+                    self.0.push(command[0][0].string.clone());
+                }
+                visit::visit_unit(self, unit)
+            }
+        }
+        let (units, _) =
+            parse_command_line("if a; then b | c; else d && e; fi\nwhile f; do g; done", 0)
+                .unwrap();
+        let mut commands = Commands(vec![]);
+        commands.visit_units(&units);
+        assert_eq!(commands.0, ["a", "b", "c", "d", "e", "f", "g"]);
+
+        // Renames a variable in words, redirects and conditional expressions.
+        struct Rename;
+        impl VisitMut for Rename {
+            fn visit_word_mut(&mut self, word: &mut Word) {
+                if word.kind == WordKind::Variable && word.string == "x" {
+                    word.string = "y".to_string();
+                }
+            }
+        }
+        let (mut units, _) =
+            parse_command_line("echo $x >$x; [[ -n $x ]]; for i in $x; do a; done", 0).unwrap();
+        Rename.visit_units_mut(&mut units);
+        assert_eq!(
+            super::unparse(&units, FormatStyle::Bash),
+            "echo $y >$y\n[[ -n $y ]]\nfor i in $y; do\n  a\ndone\n"
+        );
+
+        // Removes '!' from commands.
+        struct RemoveNot;
+        impl Fold for RemoveNot {
+            fn fold_unit(&mut self, unit: Unit) -> Unit {
+                match unit.kind {
+                    UnitKind::Not { command } => self.fold_unit(*command),
+                    _ => visit::fold_unit(self, unit),
+                }
+            }
+        }
+        let (units, _) = parse_command_line("! a && { ! b; }", 0).unwrap();
+        assert_eq!(
+            super::unparse(&RemoveNot.fold_units(units), FormatStyle::Bash),
+            "a && { b; }\n"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let (units, _) = parse_command_line("if a; then b > c; fi | d &", 0).unwrap();
+        let json = serde_json::to_string(&units).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<Unit>>(&json).unwrap(), units);
+    }
 }
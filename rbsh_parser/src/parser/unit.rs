@@ -1,6 +1,7 @@
-use crate::{Redirect, Word};
+use crate::{ConditionalExpression, Redirect, Word};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Unit {
     pub kind: UnitKind,
     pub background: bool,
@@ -13,12 +14,40 @@ impl Unit {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ConnecterKind {
     And,
     Or,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CaseTerminator {
+    Break,       // ';;'
+    FallThrough, // ';&'
+    Continue,    // ';;&'
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CaseItem {
+    pub patterns: Vec<Vec<Word>>,
+    pub command: Vec<Unit>,
+    pub terminator: CaseTerminator,
+}
+
+impl CaseItem {
+    pub fn new(patterns: Vec<Vec<Word>>, command: Vec<Unit>, terminator: CaseTerminator) -> Self {
+        Self {
+            patterns,
+            command,
+            terminator,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnitKind {
     SimpleCommand {
         command: Vec<Vec<Word>>,
@@ -34,6 +63,13 @@ pub enum UnitKind {
         right: Box<Unit>,
         both: bool,
     },
+    Not {
+        command: Box<Unit>,
+    },
+    Time {
+        command: Box<Unit>,
+        posix: bool,
+    },
     If {
         condition: Box<Unit>,
         true_case: Vec<Unit>,
@@ -62,4 +98,54 @@ pub enum UnitKind {
         command: Vec<Unit>,
         redirect: Option<Vec<Redirect>>,
     },
+    Select {
+        identifier: Vec<Word>,
+        list: Option<Vec<Vec<Word>>>,
+        command: Vec<Unit>,
+        redirect: Option<Vec<Redirect>>,
+    },
+    ArithmeticFor {
+        init: Word,
+        condition: Word,
+        step: Word,
+        command: Vec<Unit>,
+        redirect: Option<Vec<Redirect>>,
+    },
+    Case {
+        word: Vec<Word>,
+        items: Vec<CaseItem>,
+        else_case: Option<Vec<Unit>>,
+        redirect: Option<Vec<Redirect>>,
+    },
+    Function {
+        name: Vec<Word>,
+        params: Vec<Vec<Word>>,
+        body: Box<Unit>,
+    },
+    Group {
+        command: Vec<Unit>,
+        redirect: Option<Vec<Redirect>>,
+    },
+    Subshell {
+        command: Vec<Unit>,
+        redirect: Option<Vec<Redirect>>,
+    },
+    Arithmetic {
+        expression: Word,
+        redirect: Option<Vec<Redirect>>,
+    },
+    Conditional {
+        expression: ConditionalExpression,
+        redirect: Option<Vec<Redirect>>,
+    },
+    Coproc {
+        name: Option<Vec<Word>>,
+        command: Box<Unit>,
+    },
+    // Comments and blank lines are kept in command lists to format the source.
+    Comment {
+        text: String,   // after '#'
+        trailing: bool, // after a command on the same line
+    },
+    BlankLine,
 }
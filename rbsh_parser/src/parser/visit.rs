@@ -0,0 +1,689 @@
+// Traversal of the syntax tree. Each method of the traits recurses into the children by
+// default through the function of the same name, so that an implementation overrides only
+// the nodes it is interested in and calls the function to continue the traversal, e.g.
+//
+//     struct Words(usize);
+//     impl Visit for Words {
+//         fn visit_word(&mut self, _: &Word) {
+//             self.0 += 1;
+//         }
+//     }
+use super::{CaseItem, ConditionalExpression, Redirect, RedirectKind, Unit, UnitKind};
+use crate::Word;
+
+// Walks the tree by reference.
+pub trait Visit {
+    fn visit_units(&mut self, units: &[Unit]) {
+        visit_units(self, units)
+    }
+
+    fn visit_unit(&mut self, unit: &Unit) {
+        visit_unit(self, unit)
+    }
+
+    fn visit_case_item(&mut self, item: &CaseItem) {
+        visit_case_item(self, item)
+    }
+
+    fn visit_conditional(&mut self, expression: &ConditionalExpression) {
+        visit_conditional(self, expression)
+    }
+
+    fn visit_redirect(&mut self, redirect: &Redirect) {
+        visit_redirect(self, redirect)
+    }
+
+    fn visit_wordlist(&mut self, words: &[Word]) {
+        visit_wordlist(self, words)
+    }
+
+    fn visit_word(&mut self, _word: &Word) {}
+}
+
+// Walks the tree by mutable reference to modify it in place. Lists are given as Vec, so that
+// commands and words can be added or removed.
+pub trait VisitMut {
+    fn visit_units_mut(&mut self, units: &mut Vec<Unit>) {
+        visit_units_mut(self, units)
+    }
+
+    fn visit_unit_mut(&mut self, unit: &mut Unit) {
+        visit_unit_mut(self, unit)
+    }
+
+    fn visit_case_item_mut(&mut self, item: &mut CaseItem) {
+        visit_case_item_mut(self, item)
+    }
+
+    fn visit_conditional_mut(&mut self, expression: &mut ConditionalExpression) {
+        visit_conditional_mut(self, expression)
+    }
+
+    fn visit_redirect_mut(&mut self, redirect: &mut Redirect) {
+        visit_redirect_mut(self, redirect)
+    }
+
+    fn visit_wordlist_mut(&mut self, words: &mut Vec<Word>) {
+        visit_wordlist_mut(self, words)
+    }
+
+    fn visit_word_mut(&mut self, _word: &mut Word) {}
+}
+
+// Takes the tree by value and builds a new one, e.g. to replace a node with another kind.
+pub trait Fold {
+    fn fold_units(&mut self, units: Vec<Unit>) -> Vec<Unit> {
+        fold_units(self, units)
+    }
+
+    fn fold_unit(&mut self, unit: Unit) -> Unit {
+        fold_unit(self, unit)
+    }
+
+    fn fold_case_item(&mut self, item: CaseItem) -> CaseItem {
+        fold_case_item(self, item)
+    }
+
+    fn fold_conditional(&mut self, expression: ConditionalExpression) -> ConditionalExpression {
+        fold_conditional(self, expression)
+    }
+
+    fn fold_redirect(&mut self, redirect: Redirect) -> Redirect {
+        fold_redirect(self, redirect)
+    }
+
+    fn fold_wordlist(&mut self, words: Vec<Word>) -> Vec<Word> {
+        fold_wordlist(self, words)
+    }
+
+    fn fold_word(&mut self, word: Word) -> Word {
+        word
+    }
+}
+
+pub fn visit_units<V: Visit + ?Sized>(v: &mut V, units: &[Unit]) {
+    units.iter().for_each(|unit| v.visit_unit(unit));
+}
+
+pub fn visit_unit<V: Visit + ?Sized>(v: &mut V, unit: &Unit) {
+    macro_rules! redirect {
+        ($redirect: expr) => {
+            $redirect.iter().flatten().for_each(|r| v.visit_redirect(r))
+        };
+    }
+
+    match &unit.kind {
+        UnitKind::SimpleCommand { command, redirect } => {
+            command.iter().for_each(|w| v.visit_wordlist(w));
+            redirect!(redirect);
+        }
+        UnitKind::Connecter { left, right, .. } | UnitKind::Pipe { left, right, .. } => {
+            v.visit_unit(left);
+            v.visit_unit(right);
+        }
+        UnitKind::Not { command } | UnitKind::Time { command, .. } => v.visit_unit(command),
+        UnitKind::If {
+            condition,
+            true_case,
+            false_case,
+            redirect,
+        } => {
+            v.visit_unit(condition);
+            v.visit_units(true_case);
+            if let Some(false_case) = false_case {
+                v.visit_units(false_case);
+            }
+            redirect!(redirect);
+        }
+        UnitKind::Unless {
+            condition,
+            false_case,
+            true_case,
+            redirect,
+        } => {
+            v.visit_unit(condition);
+            v.visit_units(false_case);
+            if let Some(true_case) = true_case {
+                v.visit_units(true_case);
+            }
+            redirect!(redirect);
+        }
+        UnitKind::While {
+            condition,
+            command,
+            redirect,
+        }
+        | UnitKind::Until {
+            condition,
+            command,
+            redirect,
+        } => {
+            v.visit_unit(condition);
+            v.visit_units(command);
+            redirect!(redirect);
+        }
+        UnitKind::For {
+            identifier,
+            list,
+            command,
+            redirect,
+        }
+        | UnitKind::Select {
+            identifier,
+            list,
+            command,
+            redirect,
+        } => {
+            v.visit_wordlist(identifier);
+            list.iter().flatten().for_each(|w| v.visit_wordlist(w));
+            v.visit_units(command);
+            redirect!(redirect);
+        }
+        UnitKind::ArithmeticFor {
+            init,
+            condition,
+            step,
+            command,
+            redirect,
+        } => {
+            v.visit_word(init);
+            v.visit_word(condition);
+            v.visit_word(step);
+            v.visit_units(command);
+            redirect!(redirect);
+        }
+        UnitKind::Case {
+            word,
+            items,
+            else_case,
+            redirect,
+        } => {
+            v.visit_wordlist(word);
+            items.iter().for_each(|item| v.visit_case_item(item));
+            if let Some(else_case) = else_case {
+                v.visit_units(else_case);
+            }
+            redirect!(redirect);
+        }
+        UnitKind::Function { name, params, body } => {
+            v.visit_wordlist(name);
+            params.iter().for_each(|w| v.visit_wordlist(w));
+            v.visit_unit(body);
+        }
+        UnitKind::Group { command, redirect } | UnitKind::Subshell { command, redirect } => {
+            v.visit_units(command);
+            redirect!(redirect);
+        }
+        UnitKind::Arithmetic {
+            expression,
+            redirect,
+        } => {
+            v.visit_word(expression);
+            redirect!(redirect);
+        }
+        UnitKind::Conditional {
+            expression,
+            redirect,
+        } => {
+            v.visit_conditional(expression);
+            redirect!(redirect);
+        }
+        UnitKind::Coproc { name, command } => {
+            if let Some(name) = name {
+                v.visit_wordlist(name);
+            }
+            v.visit_unit(command);
+        }
+        UnitKind::Comment { .. } | UnitKind::BlankLine => (),
+    }
+}
+
+pub fn visit_case_item<V: Visit + ?Sized>(v: &mut V, item: &CaseItem) {
+    item.patterns.iter().for_each(|w| v.visit_wordlist(w));
+    v.visit_units(&item.command);
+}
+
+pub fn visit_conditional<V: Visit + ?Sized>(v: &mut V, expression: &ConditionalExpression) {
+    match expression {
+        ConditionalExpression::Word(w) | ConditionalExpression::Unary(_, w) => v.visit_wordlist(w),
+        ConditionalExpression::Binary(_, left, right) => {
+            v.visit_wordlist(left);
+            v.visit_wordlist(right);
+        }
+        ConditionalExpression::Not(e) => v.visit_conditional(e),
+        ConditionalExpression::And(left, right) | ConditionalExpression::Or(left, right) => {
+            v.visit_conditional(left);
+            v.visit_conditional(right);
+        }
+    }
+}
+
+pub fn visit_redirect<V: Visit + ?Sized>(v: &mut V, redirect: &Redirect) {
+    match &redirect.value {
+        RedirectKind::ReadFrom(_, w)
+        | RedirectKind::WriteTo(_, w, _)
+        | RedirectKind::WriteBoth(w)
+        | RedirectKind::Duplicate(_, w)
+        | RedirectKind::Append(_, w)
+        | RedirectKind::AppendBoth(w)
+        | RedirectKind::ReadWrite(_, w)
+        | RedirectKind::HereDocument(_, w)
+        | RedirectKind::HereString(_, w) => v.visit_wordlist(w),
+        RedirectKind::Copy(..) | RedirectKind::Close(_) => (),
+    }
+}
+
+pub fn visit_wordlist<V: Visit + ?Sized>(v: &mut V, words: &[Word]) {
+    words.iter().for_each(|word| v.visit_word(word));
+}
+
+pub fn visit_units_mut<V: VisitMut + ?Sized>(v: &mut V, units: &mut [Unit]) {
+    units.iter_mut().for_each(|unit| v.visit_unit_mut(unit));
+}
+
+pub fn visit_unit_mut<V: VisitMut + ?Sized>(v: &mut V, unit: &mut Unit) {
+    macro_rules! redirect {
+        ($redirect: expr) => {
+            $redirect
+                .iter_mut()
+                .flatten()
+                .for_each(|r| v.visit_redirect_mut(r))
+        };
+    }
+
+    match &mut unit.kind {
+        UnitKind::SimpleCommand { command, redirect } => {
+            command.iter_mut().for_each(|w| v.visit_wordlist_mut(w));
+            redirect!(redirect);
+        }
+        UnitKind::Connecter { left, right, .. } | UnitKind::Pipe { left, right, .. } => {
+            v.visit_unit_mut(left);
+            v.visit_unit_mut(right);
+        }
+        UnitKind::Not { command } | UnitKind::Time { command, .. } => v.visit_unit_mut(command),
+        UnitKind::If {
+            condition,
+            true_case,
+            false_case,
+            redirect,
+        } => {
+            v.visit_unit_mut(condition);
+            v.visit_units_mut(true_case);
+            if let Some(false_case) = false_case {
+                v.visit_units_mut(false_case);
+            }
+            redirect!(redirect);
+        }
+        UnitKind::Unless {
+            condition,
+            false_case,
+            true_case,
+            redirect,
+        } => {
+            v.visit_unit_mut(condition);
+            v.visit_units_mut(false_case);
+            if let Some(true_case) = true_case {
+                v.visit_units_mut(true_case);
+            }
+            redirect!(redirect);
+        }
+        UnitKind::While {
+            condition,
+            command,
+            redirect,
+        }
+        | UnitKind::Until {
+            condition,
+            command,
+            redirect,
+        } => {
+            v.visit_unit_mut(condition);
+            v.visit_units_mut(command);
+            redirect!(redirect);
+        }
+        UnitKind::For {
+            identifier,
+            list,
+            command,
+            redirect,
+        }
+        | UnitKind::Select {
+            identifier,
+            list,
+            command,
+            redirect,
+        } => {
+            v.visit_wordlist_mut(identifier);
+            list.iter_mut()
+                .flatten()
+                .for_each(|w| v.visit_wordlist_mut(w));
+            v.visit_units_mut(command);
+            redirect!(redirect);
+        }
+        UnitKind::ArithmeticFor {
+            init,
+            condition,
+            step,
+            command,
+            redirect,
+        } => {
+            v.visit_word_mut(init);
+            v.visit_word_mut(condition);
+            v.visit_word_mut(step);
+            v.visit_units_mut(command);
+            redirect!(redirect);
+        }
+        UnitKind::Case {
+            word,
+            items,
+            else_case,
+            redirect,
+        } => {
+            v.visit_wordlist_mut(word);
+            items
+                .iter_mut()
+                .for_each(|item| v.visit_case_item_mut(item));
+            if let Some(else_case) = else_case {
+                v.visit_units_mut(else_case);
+            }
+            redirect!(redirect);
+        }
+        UnitKind::Function { name, params, body } => {
+            v.visit_wordlist_mut(name);
+            params.iter_mut().for_each(|w| v.visit_wordlist_mut(w));
+            v.visit_unit_mut(body);
+        }
+        UnitKind::Group { command, redirect } | UnitKind::Subshell { command, redirect } => {
+            v.visit_units_mut(command);
+            redirect!(redirect);
+        }
+        UnitKind::Arithmetic {
+            expression,
+            redirect,
+        } => {
+            v.visit_word_mut(expression);
+            redirect!(redirect);
+        }
+        UnitKind::Conditional {
+            expression,
+            redirect,
+        } => {
+            v.visit_conditional_mut(expression);
+            redirect!(redirect);
+        }
+        UnitKind::Coproc { name, command } => {
+            if let Some(name) = name {
+                v.visit_wordlist_mut(name);
+            }
+            v.visit_unit_mut(command);
+        }
+        UnitKind::Comment { .. } | UnitKind::BlankLine => (),
+    }
+}
+
+pub fn visit_case_item_mut<V: VisitMut + ?Sized>(v: &mut V, item: &mut CaseItem) {
+    item.patterns
+        .iter_mut()
+        .for_each(|w| v.visit_wordlist_mut(w));
+    v.visit_units_mut(&mut item.command);
+}
+
+pub fn visit_conditional_mut<V: VisitMut + ?Sized>(
+    v: &mut V,
+    expression: &mut ConditionalExpression,
+) {
+    match expression {
+        ConditionalExpression::Word(w) | ConditionalExpression::Unary(_, w) => {
+            v.visit_wordlist_mut(w)
+        }
+        ConditionalExpression::Binary(_, left, right) => {
+            v.visit_wordlist_mut(left);
+            v.visit_wordlist_mut(right);
+        }
+        ConditionalExpression::Not(e) => v.visit_conditional_mut(e),
+        ConditionalExpression::And(left, right) | ConditionalExpression::Or(left, right) => {
+            v.visit_conditional_mut(left);
+            v.visit_conditional_mut(right);
+        }
+    }
+}
+
+pub fn visit_redirect_mut<V: VisitMut + ?Sized>(v: &mut V, redirect: &mut Redirect) {
+    match &mut redirect.value {
+        RedirectKind::ReadFrom(_, w)
+        | RedirectKind::WriteTo(_, w, _)
+        | RedirectKind::WriteBoth(w)
+        | RedirectKind::Duplicate(_, w)
+        | RedirectKind::Append(_, w)
+        | RedirectKind::AppendBoth(w)
+        | RedirectKind::ReadWrite(_, w)
+        | RedirectKind::HereDocument(_, w)
+        | RedirectKind::HereString(_, w) => v.visit_wordlist_mut(w),
+        RedirectKind::Copy(..) | RedirectKind::Close(_) => (),
+    }
+}
+
+pub fn visit_wordlist_mut<V: VisitMut + ?Sized>(v: &mut V, words: &mut [Word]) {
+    words.iter_mut().for_each(|word| v.visit_word_mut(word));
+}
+
+pub fn fold_units<F: Fold + ?Sized>(f: &mut F, units: Vec<Unit>) -> Vec<Unit> {
+    units.into_iter().map(|unit| f.fold_unit(unit)).collect()
+}
+
+pub fn fold_unit<F: Fold + ?Sized>(f: &mut F, unit: Unit) -> Unit {
+    macro_rules! redirect {
+        ($redirect: expr) => {
+            $redirect.map(|r| r.into_iter().map(|r| f.fold_redirect(r)).collect())
+        };
+    }
+    macro_rules! wordlists {
+        ($words: expr) => {
+            $words.into_iter().map(|w| f.fold_wordlist(w)).collect()
+        };
+    }
+    macro_rules! boxed {
+        ($unit: expr) => {
+            Box::new(f.fold_unit(*$unit))
+        };
+    }
+
+    let kind = match unit.kind {
+        UnitKind::SimpleCommand { command, redirect } => UnitKind::SimpleCommand {
+            command: wordlists!(command),
+            redirect: redirect!(redirect),
+        },
+        UnitKind::Connecter { left, right, kind } => UnitKind::Connecter {
+            left: boxed!(left),
+            right: boxed!(right),
+            kind,
+        },
+        UnitKind::Pipe { left, right, both } => UnitKind::Pipe {
+            left: boxed!(left),
+            right: boxed!(right),
+            both,
+        },
+        UnitKind::Not { command } => UnitKind::Not {
+            command: boxed!(command),
+        },
+        UnitKind::Time { command, posix } => UnitKind::Time {
+            command: boxed!(command),
+            posix,
+        },
+        UnitKind::If {
+            condition,
+            true_case,
+            false_case,
+            redirect,
+        } => UnitKind::If {
+            condition: boxed!(condition),
+            true_case: f.fold_units(true_case),
+            false_case: false_case.map(|units| f.fold_units(units)),
+            redirect: redirect!(redirect),
+        },
+        UnitKind::Unless {
+            condition,
+            false_case,
+            true_case,
+            redirect,
+        } => UnitKind::Unless {
+            condition: boxed!(condition),
+            false_case: f.fold_units(false_case),
+            true_case: true_case.map(|units| f.fold_units(units)),
+            redirect: redirect!(redirect),
+        },
+        UnitKind::While {
+            condition,
+            command,
+            redirect,
+        } => UnitKind::While {
+            condition: boxed!(condition),
+            command: f.fold_units(command),
+            redirect: redirect!(redirect),
+        },
+        UnitKind::Until {
+            condition,
+            command,
+            redirect,
+        } => UnitKind::Until {
+            condition: boxed!(condition),
+            command: f.fold_units(command),
+            redirect: redirect!(redirect),
+        },
+        UnitKind::For {
+            identifier,
+            list,
+            command,
+            redirect,
+        } => UnitKind::For {
+            identifier: f.fold_wordlist(identifier),
+            list: list.map(|list| wordlists!(list)),
+            command: f.fold_units(command),
+            redirect: redirect!(redirect),
+        },
+        UnitKind::Select {
+            identifier,
+            list,
+            command,
+            redirect,
+        } => UnitKind::Select {
+            identifier: f.fold_wordlist(identifier),
+            list: list.map(|list| wordlists!(list)),
+            command: f.fold_units(command),
+            redirect: redirect!(redirect),
+        },
+        UnitKind::ArithmeticFor {
+            init,
+            condition,
+            step,
+            command,
+            redirect,
+        } => UnitKind::ArithmeticFor {
+            init: f.fold_word(init),
+            condition: f.fold_word(condition),
+            step: f.fold_word(step),
+            command: f.fold_units(command),
+            redirect: redirect!(redirect),
+        },
+        UnitKind::Case {
+            word,
+            items,
+            else_case,
+            redirect,
+        } => UnitKind::Case {
+            word: f.fold_wordlist(word),
+            items: items
+                .into_iter()
+                .map(|item| f.fold_case_item(item))
+                .collect(),
+            else_case: else_case.map(|units| f.fold_units(units)),
+            redirect: redirect!(redirect),
+        },
+        UnitKind::Function { name, params, body } => UnitKind::Function {
+            name: f.fold_wordlist(name),
+            params: wordlists!(params),
+            body: boxed!(body),
+        },
+        UnitKind::Group { command, redirect } => UnitKind::Group {
+            command: f.fold_units(command),
+            redirect: redirect!(redirect),
+        },
+        UnitKind::Subshell { command, redirect } => UnitKind::Subshell {
+            command: f.fold_units(command),
+            redirect: redirect!(redirect),
+        },
+        UnitKind::Arithmetic {
+            expression,
+            redirect,
+        } => UnitKind::Arithmetic {
+            expression: f.fold_word(expression),
+            redirect: redirect!(redirect),
+        },
+        UnitKind::Conditional {
+            expression,
+            redirect,
+        } => UnitKind::Conditional {
+            expression: f.fold_conditional(expression),
+            redirect: redirect!(redirect),
+        },
+        UnitKind::Coproc { name, command } => UnitKind::Coproc {
+            name: name.map(|name| f.fold_wordlist(name)),
+            command: Box::new(f.fold_unit(*command)),
+        },
+        kind @ (UnitKind::Comment { .. } | UnitKind::BlankLine) => kind,
+    };
+    Unit::new(kind, unit.background)
+}
+
+pub fn fold_case_item<F: Fold + ?Sized>(f: &mut F, item: CaseItem) -> CaseItem {
+    CaseItem::new(
+        item.patterns
+            .into_iter()
+            .map(|w| f.fold_wordlist(w))
+            .collect(),
+        f.fold_units(item.command),
+        item.terminator,
+    )
+}
+
+pub fn fold_conditional<F: Fold + ?Sized>(
+    f: &mut F,
+    expression: ConditionalExpression,
+) -> ConditionalExpression {
+    use ConditionalExpression::*;
+
+    macro_rules! boxed {
+        ($e: expr) => {
+            Box::new(f.fold_conditional(*$e))
+        };
+    }
+    match expression {
+        Word(w) => Word(f.fold_wordlist(w)),
+        Unary(op, w) => Unary(op, f.fold_wordlist(w)),
+        Binary(op, left, right) => Binary(op, f.fold_wordlist(left), f.fold_wordlist(right)),
+        Not(e) => Not(boxed!(e)),
+        And(left, right) => And(boxed!(left), boxed!(right)),
+        Or(left, right) => Or(boxed!(left), boxed!(right)),
+    }
+}
+
+pub fn fold_redirect<F: Fold + ?Sized>(f: &mut F, redirect: Redirect) -> Redirect {
+    let mut w = |w: Vec<Word>| f.fold_wordlist(w);
+    let kind = match redirect.value {
+        RedirectKind::ReadFrom(fd, words) => RedirectKind::ReadFrom(fd, w(words)),
+        RedirectKind::WriteTo(fd, words, force) => RedirectKind::WriteTo(fd, w(words), force),
+        RedirectKind::WriteBoth(words) => RedirectKind::WriteBoth(w(words)),
+        RedirectKind::Duplicate(fd, words) => RedirectKind::Duplicate(fd, w(words)),
+        RedirectKind::Append(fd, words) => RedirectKind::Append(fd, w(words)),
+        RedirectKind::AppendBoth(words) => RedirectKind::AppendBoth(w(words)),
+        RedirectKind::ReadWrite(fd, words) => RedirectKind::ReadWrite(fd, w(words)),
+        RedirectKind::HereDocument(fd, words) => RedirectKind::HereDocument(fd, w(words)),
+        RedirectKind::HereString(fd, words) => RedirectKind::HereString(fd, w(words)),
+        kind @ (RedirectKind::Copy(..) | RedirectKind::Close(_)) => kind,
+    };
+    Redirect::new(kind, redirect.location)
+}
+
+pub fn fold_wordlist<F: Fold + ?Sized>(f: &mut F, words: Vec<Word>) -> Vec<Word> {
+    words.into_iter().map(|word| f.fold_word(word)).collect()
+}
@@ -1,33 +1,41 @@
-use super::word::WordKind;
+use super::word::{Word, WordKind};
 use crate::location::{Annotate, Location};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TokenKind {
     Space,
-    Word(String, WordKind),
+    Word(String, WordKind, bool), // the bool is true for an expansion in double quotes
     Number(String),
     Comment(String),
-    Background,   // '&'
-    Pipe,         // '|'
-    PipeBoth,     // '|&'
-    And,          // '&&'
-    Or,           // '||'
-    ReadFrom,     // '<'
-    WriteTo,      // '>'
-    ForceWriteTo, // '>|'
-    WriteBoth,    // '&>', '>&'
-    ReadCopy,     // '<&'
-    WriteCopy,    // 'm>&n'
-    Append,       // '>>'
-    AppendBoth,   // '&>>'
-    ReadClose,    // '<&-'
-    WriteClose,   // '>&-'
-    ReadWrite,    // '<>'
-    HereDocument, // '<<'
-    HereString,   // '<<<'
-    Termination,  // ';'
-    GroupStart,   // '{'
-    GroupEnd,     // '}'
+    Background,              // '&'
+    Pipe,                    // '|'
+    PipeBoth,                // '|&'
+    And,                     // '&&'
+    Or,                      // '||'
+    ReadFrom,                // '<'
+    WriteTo,                 // '>'
+    ForceWriteTo,            // '>|'
+    WriteBoth,               // '&>', '>&'
+    ReadCopy,                // '<&'
+    WriteCopy,               // 'm>&n'
+    Append,                  // '>>'
+    AppendBoth,              // '&>>'
+    ReadClose,               // '<&-'
+    WriteClose,              // '>&-'
+    ReadWrite,               // '<>'
+    HereDocument(Vec<Word>), // '<<'
+    HereString,              // '<<<'
+    Termination,             // ';'
+    CaseBreak,               // ';;'
+    CaseFallThrough,         // ';&'
+    CaseContinue,            // ';;&'
+    GroupStart,              // '{'
+    GroupEnd,                // '}'
+    SubshellStart,           // '('
+    SubshellEnd,             // ')'
+    Arithmetic(String),      // '((expr))'
+    Conditional(String),     // '[[ expr ]]'
     Hyphen,
     NewLine,
     If,
@@ -43,7 +51,16 @@ pub enum TokenKind {
     Done,
     Until,
     For,
+    Select,
     In,
+    Case,
+    Esac,
+    When,
+    Function,
+    Def,
+    Coproc,
+    Bang, // '!'
+    Time,
 }
 pub type Token = Annotate<TokenKind>;
 
@@ -53,7 +70,22 @@ impl Token {
     }
 
     pub fn word<S: AsRef<str>>(s: S, k: WordKind, loc: Location) -> Self {
-        Self::new(TokenKind::Word(String::from(s.as_ref()), k), loc)
+        Self::new(TokenKind::Word(String::from(s.as_ref()), k, false), loc)
+    }
+
+    pub fn quoted_word<S: AsRef<str>>(s: S, k: WordKind, loc: Location) -> Self {
+        Self::new(TokenKind::Word(String::from(s.as_ref()), k, true), loc)
+    }
+
+    // Marks the word as in double quotes. A literal in double quotes is a quoted word.
+    pub fn into_quoted(self) -> Self {
+        match self.value {
+            TokenKind::Word(s, WordKind::Normal, _) => {
+                Self::quoted_word(s, WordKind::Quote, self.location)
+            }
+            TokenKind::Word(s, k, _) => Self::quoted_word(s, k, self.location),
+            _ => self,
+        }
     }
 
     pub fn number<S: AsRef<str>>(n: S, loc: Location) -> Self {
@@ -128,8 +160,8 @@ impl Token {
         Self::new(TokenKind::ReadWrite, loc)
     }
 
-    pub fn here_document(loc: Location) -> Self {
-        Self::new(TokenKind::HereDocument, loc)
+    pub fn here_document(body: Vec<Word>, loc: Location) -> Self {
+        Self::new(TokenKind::HereDocument(body), loc)
     }
 
     pub fn here_string(loc: Location) -> Self {
@@ -140,6 +172,18 @@ impl Token {
         Self::new(TokenKind::Termination, loc)
     }
 
+    pub fn case_break(loc: Location) -> Self {
+        Self::new(TokenKind::CaseBreak, loc)
+    }
+
+    pub fn case_fall_through(loc: Location) -> Self {
+        Self::new(TokenKind::CaseFallThrough, loc)
+    }
+
+    pub fn case_continue(loc: Location) -> Self {
+        Self::new(TokenKind::CaseContinue, loc)
+    }
+
     pub fn group_start(loc: Location) -> Self {
         Self::new(TokenKind::GroupStart, loc)
     }
@@ -148,6 +192,22 @@ impl Token {
         Self::new(TokenKind::GroupEnd, loc)
     }
 
+    pub fn subshell_start(loc: Location) -> Self {
+        Self::new(TokenKind::SubshellStart, loc)
+    }
+
+    pub fn subshell_end(loc: Location) -> Self {
+        Self::new(TokenKind::SubshellEnd, loc)
+    }
+
+    pub fn arithmetic<S: AsRef<str>>(expr: S, loc: Location) -> Self {
+        Self::new(TokenKind::Arithmetic(String::from(expr.as_ref())), loc)
+    }
+
+    pub fn conditional<S: AsRef<str>>(expr: S, loc: Location) -> Self {
+        Self::new(TokenKind::Conditional(String::from(expr.as_ref())), loc)
+    }
+
     pub fn hyphen(loc: Location) -> Self {
         Self::new(TokenKind::Hyphen, loc)
     }
@@ -171,7 +231,16 @@ impl Token {
             "done" => TokenKind::Done,
             "until" => TokenKind::Until,
             "for" => TokenKind::For,
+            "select" => TokenKind::Select,
             "in" => TokenKind::In,
+            "case" => TokenKind::Case,
+            "esac" => TokenKind::Esac,
+            "when" => TokenKind::When,
+            "function" => TokenKind::Function,
+            "def" => TokenKind::Def,
+            "coproc" => TokenKind::Coproc,
+            "!" => TokenKind::Bang,
+            "time" => TokenKind::Time,
             _ => unimplemented![],
         };
         Self::new(kind, loc)
@@ -195,12 +264,6 @@ mod test {
             assert_eq!(token.value, $right($a));
             assert_eq!(token.location, location!());
         }};
-
-        ($left:ident, $a1:expr, $a2:expr, $right:path) => {{
-            let token = Token::$left($a1, $a2, location!());
-            assert_eq!(token.value, $right($a1.to_string(), $a2));
-            assert_eq!(token.location, location!());
-        }};
     }
 
     macro_rules! assert_keyword {
@@ -214,7 +277,19 @@ mod test {
     #[test]
     fn token() {
         assert_token!(space, TokenKind::Space);
-        assert_token!(word, "abc", WordKind::Normal, TokenKind::Word);
+        let word = Token::word("abc", WordKind::Normal, location!());
+        assert_eq!(
+            word.value,
+            TokenKind::Word("abc".to_string(), WordKind::Normal, false)
+        );
+        assert_eq!(word.location, location!());
+
+        let word = Token::quoted_word("abc", WordKind::Variable, location!());
+        assert_eq!(
+            word.value,
+            TokenKind::Word("abc".to_string(), WordKind::Variable, true)
+        );
+        assert_eq!(word.location, location!());
 
         let number = Token::number("1", location!());
         assert_eq!(number.value, TokenKind::Number("1".to_string()));
@@ -237,11 +312,31 @@ mod test {
         assert_token!(read_close, TokenKind::ReadClose);
         assert_token!(write_close, TokenKind::WriteClose);
         assert_token!(read_write, TokenKind::ReadWrite);
-        assert_token!(here_document, TokenKind::HereDocument);
+        assert_token!(
+            here_document,
+            vec![Word::new("foo".to_string(), WordKind::Normal, location!())],
+            TokenKind::HereDocument
+        );
         assert_token!(here_string, TokenKind::HereString);
         assert_token!(termination, TokenKind::Termination);
+        assert_token!(case_break, TokenKind::CaseBreak);
+        assert_token!(case_fall_through, TokenKind::CaseFallThrough);
+        assert_token!(case_continue, TokenKind::CaseContinue);
         assert_token!(group_start, TokenKind::GroupStart);
         assert_token!(group_end, TokenKind::GroupEnd);
+        assert_token!(subshell_start, TokenKind::SubshellStart);
+        assert_token!(subshell_end, TokenKind::SubshellEnd);
+
+        let arithmetic = Token::arithmetic("1 + 2", location!());
+        assert_eq!(arithmetic.value, TokenKind::Arithmetic("1 + 2".to_string()));
+        assert_eq!(arithmetic.location, location!());
+
+        let conditional = Token::conditional(" -f a ", location!());
+        assert_eq!(
+            conditional.value,
+            TokenKind::Conditional(" -f a ".to_string())
+        );
+        assert_eq!(conditional.location, location!());
         assert_token!(hyphen, TokenKind::Hyphen);
         assert_token!(newline, TokenKind::NewLine);
 
@@ -258,6 +353,15 @@ mod test {
         assert_keyword!("done", TokenKind::Done);
         assert_keyword!("until", TokenKind::Until);
         assert_keyword!("for", TokenKind::For);
+        assert_keyword!("select", TokenKind::Select);
         assert_keyword!("in", TokenKind::In);
+        assert_keyword!("case", TokenKind::Case);
+        assert_keyword!("esac", TokenKind::Esac);
+        assert_keyword!("when", TokenKind::When);
+        assert_keyword!("function", TokenKind::Function);
+        assert_keyword!("def", TokenKind::Def);
+        assert_keyword!("coproc", TokenKind::Coproc);
+        assert_keyword!("!", TokenKind::Bang);
+        assert_keyword!("time", TokenKind::Time);
     }
 }
@@ -1,19 +1,48 @@
 use crate::location::Location;
+use std::hash::{Hash, Hasher};
 
+// The characters of a Quote word are literal: unlike a Normal word, it is not subject to
+// brace, tilde and pathname expansion, so the literal parts of "word" are Quote as well.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum WordKind {
-    Normal,    // word or "word"
-    Quote,     // 'word'
-    Command,   // `word` or $(word)
-    Variable,  // $word
-    Parameter, // ${word}
+    Normal,        // word
+    Quote,         // 'word', "word" or \w
+    AnsiC,         // $'word', whose escape sequences are decoded on expansion
+    Command,       // `word` or $(word)
+    Variable,      // $word
+    Parameter,     // ${word}
+    Arithmetic,    // $((word))
+    Array,         // (word ...) of name=(word ...)
+    InputProcess,  // <(word)
+    OutputProcess, // >(word)
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Word {
     pub string: String,
     pub kind: WordKind,
     pub location: Location,
+    pub quoted: bool, // in double quotes, where an expansion is not split into fields
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub source: Option<String>, // an unquoted literal as written, e.g. 'a' or \x, kept by fmt
+}
+
+// The source is not compared, as the same word can be written with different quoting.
+impl PartialEq for Word {
+    fn eq(&self, other: &Self) -> bool {
+        (&self.string, self.kind, self.location, self.quoted)
+            == (&other.string, other.kind, other.location, other.quoted)
+    }
+}
+
+impl Eq for Word {}
+
+impl Hash for Word {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (&self.string, self.kind, self.location, self.quoted).hash(state);
+    }
 }
 
 impl Word {
@@ -22,6 +51,27 @@ impl Word {
             string,
             kind,
             location,
+            quoted: false,
+            source: None,
+        }
+    }
+
+    pub fn quoted(string: String, kind: WordKind, location: Location) -> Self {
+        Word {
+            quoted: true,
+            ..Self::new(string, kind, location)
+        }
+    }
+
+    pub(crate) fn from_token(
+        string: String,
+        kind: WordKind,
+        quoted: bool,
+        location: Location,
+    ) -> Self {
+        match quoted {
+            true => Self::quoted(string, kind, location),
+            false => Self::new(string, kind, location),
         }
     }
 
@@ -30,10 +80,10 @@ impl Word {
         Self::new(str.to_string(), WordKind::Normal, location)
     }
 
-    //#[cfg(test)]
-    //pub(crate) fn quote(str: &str, location: Location) -> Self {
-    //    Self::new(str.to_string(), WordKind::Quote, location)
-    //}
+    #[cfg(test)]
+    pub(crate) fn quote(str: &str, location: Location) -> Self {
+        Self::new(str.to_string(), WordKind::Quote, location)
+    }
 
     //#[cfg(test)]
     //pub(crate) fn command(str: &str, location: Location) -> Self {
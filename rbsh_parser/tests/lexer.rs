@@ -32,7 +32,7 @@ fn it_simple_command() {
         "echo \"foo bar\"",
         Ok(Token::word("echo", WordKind::Normal, location!())),
         Ok(Token::space(location!(5))),
-        Ok(Token::word("foo bar", WordKind::Normal, location!(7)))
+        Ok(Token::quoted_word("foo bar", WordKind::Quote, location!(7)))
     );
 
     assert_lex!(
@@ -1201,7 +1201,7 @@ fn for_statement() {
         Ok(Token::keyword("for", location!(1, 1))),
         Ok(Token::space(location!(4, 1))),
         Ok(Token::word("foo", WordKind::Normal, location!(5, 1))),
-        Ok(Token::word("bar", WordKind::Normal, location!(9, 1))),
+        Ok(Token::quoted_word("bar", WordKind::Quote, location!(9, 1))),
         Ok(Token::word("baz", WordKind::Quote, location!(13, 1))),
         Ok(Token::newline(location!(18, 1))),
         Ok(Token::keyword("do", location!(1, 2))),
@@ -1226,7 +1226,7 @@ fn for_statement() {
         Ok(Token::space(location!(8, 1))),
         Ok(Token::keyword("in", location!(9, 1))),
         Ok(Token::space(location!(11, 1))),
-        Ok(Token::word("bar", WordKind::Normal, location!(13, 1))),
+        Ok(Token::quoted_word("bar", WordKind::Quote, location!(13, 1))),
         Ok(Token::space(location!(17, 1))),
         Ok(Token::word("baz", WordKind::Quote, location!(18, 1))),
         Ok(Token::space(location!(23, 1))),
@@ -1,5 +1,6 @@
 use crate::{
     context::Context,
+    diagnostic::Source,
     exec::Executor,
     read_line::{
         ReadFromFile, ReadFromStdin, ReadFromString, ReadFromTTY, ReadLine, ReadLineError,
@@ -10,8 +11,17 @@ use crate::{
     Config, APP_NAME, VERSION,
 };
 use clap::Parser;
-use rbsh_parser::{parse_command_line, ErrorKind};
-use std::{io, path::Path};
+use rbsh_parser::{
+    ast_tree, check_command_line, parse_command_line, unparse, ErrorKind, FormatStyle,
+};
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+// The name of the source read from the standard input, which is shown in the errors.
+const STDIN_NAME: &str = "<stdin>";
 
 enum InputSource {
     Tty,
@@ -25,17 +35,83 @@ enum InputSource {
     name = APP_NAME,
     version = VERSION,
     about = "Ruby-powerd shell",
+    disable_help_subcommand = true
 )]
 struct ReddishOptions {
     #[clap(short)]
     command: Option<String>,
 
+    /// Read commands and check them for syntax errors without executing them
+    #[clap(short = 'n')]
+    noexec: bool,
+
+    /// The format of syntax errors printed by -n
+    #[clap(long, value_enum, default_value_t = ErrorFormat::Human)]
+    error_format: ErrorFormat,
+
+    /// Print the syntax tree of the commands without executing them
+    #[clap(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "json"
+    )]
+    dump_ast: Option<AstFormat>,
+
+    #[clap(subcommand)]
+    subcommand: Option<Subcommand>,
+
     parameters: Vec<String>,
 }
 
+#[derive(clap::Subcommand, Debug)]
+enum Subcommand {
+    /// Print the commands formatted without executing them
+    Fmt {
+        /// The keywords which end compound commands, e.g. fi or end
+        #[clap(long, value_enum, default_value_t = Style::Bash)]
+        style: Style,
+
+        file: Option<String>,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum ErrorFormat {
+    Human,
+    Json,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum AstFormat {
+    Json,
+    Tree,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum Style {
+    Bash,
+    Ruby,
+}
+
+impl From<Style> for FormatStyle {
+    fn from(style: Style) -> Self {
+        match style {
+            Style::Bash => FormatStyle::Bash,
+            Style::Ruby => FormatStyle::Ruby,
+        }
+    }
+}
+
 struct AppParameter {
     source: InputSource,
     positional_parameters: Vec<String>,
+    noexec: bool,
+    error_format: ErrorFormat,
+    dump_ast: Option<AstFormat>,
+    format: Option<FormatStyle>,
 }
 
 pub struct App {
@@ -65,9 +141,22 @@ impl App {
         let opts = ReddishOptions::parse_from(args);
         let mut positional_parameters = opts.parameters.clone();
 
-        let source = match opts.command {
-            Some(command) => InputSource::Command(command),
-            None => {
+        let mut format = None;
+        let source = match (opts.subcommand, opts.command) {
+            (Some(Subcommand::Fmt { style, file }), _) => {
+                format = Some(style.into());
+                match file {
+                    Some(file) => InputSource::File(file),
+                    None => InputSource::Stdin,
+                }
+            }
+            (None, Some(command)) => {
+                if positional_parameters.is_empty() {
+                    positional_parameters.push(my_name);
+                }
+                InputSource::Command(command)
+            }
+            (None, None) => {
                 if let Some(file) = positional_parameters.first() {
                     InputSource::File(file.to_owned())
                 } else {
@@ -83,6 +172,10 @@ impl App {
         Ok(AppParameter {
             source,
             positional_parameters,
+            noexec: opts.noexec,
+            error_format: opts.error_format,
+            dump_ast: opts.dump_ast,
+            format,
         })
     }
 
@@ -100,6 +193,20 @@ impl App {
         };
         self.set_shell_variables(&params);
 
+        // Ignore SIGPIPE by default
+        // https://github.com/rust-lang/rust/pull/13158
+        recognize_sigpipe().unwrap();
+
+        if params.noexec {
+            return self.check(&params);
+        }
+        if let Some(format) = params.dump_ast {
+            return self.dump_ast(&params, format);
+        }
+        if let Some(style) = params.format {
+            return self.format(&params, style);
+        }
+
         let mut rl: Box<dyn ReadLine> = match &params.source {
             InputSource::Tty => Box::new(ReadFromTTY::new()),
             InputSource::Stdin => Box::new(ReadFromStdin::new()),
@@ -121,10 +228,6 @@ impl App {
             eprintln!("rbsh: load history error: {e:?}");
         }
 
-        // Ignore SIGPIPE by default
-        // https://github.com/rust-lang/rust/pull/13158
-        recognize_sigpipe().unwrap();
-
         if self.isatty() {
             ignore_tty_signals().unwrap();
         }
@@ -136,8 +239,17 @@ impl App {
                 return ExitStatus::failure().code();
             }
         };
+        let name = match &params.source {
+            InputSource::File(path) => path.as_str(),
+            InputSource::Stdin => STDIN_NAME,
+            InputSource::Tty | InputSource::Command(_) => APP_NAME,
+        };
+        let interactive = matches!(params.source, InputSource::Tty);
+        self.ctx.interactive = interactive;
+
         let mut cmdline = String::new();
-        let mut linenumber = 1;
+        let mut linenumber = 0;
+        let mut incomplete = None; // the error of the command which continues to the next line
         loop {
             executor.reap_job();
             let prompt = match cmdline.is_empty() {
@@ -147,6 +259,7 @@ impl App {
             match rl.readline(&prompt) {
                 Ok(line) => {
                     cmdline.push_str(&line);
+                    self.ctx.source = Source::new(name, &cmdline, linenumber);
                     match parse_command_line(&cmdline, linenumber) {
                         Ok((cmds, ignore_history)) => {
                             if !ignore_history && rl.add_history_entry(&cmdline) {
@@ -166,11 +279,18 @@ impl App {
 
                             cmdline.clear()
                         }
+                        Err(e) if e.value == ErrorKind::Eof => {
+                            cmdline.push('\n'); // next line
+                            incomplete = Some(e);
+                        }
                         Err(e) => {
-                            match e.value {
-                                ErrorKind::Eof => cmdline.push('\n'), // next line
-                                _ => eprintln!("Error: {e:?}"),
+                            if !self.syntax_error(&e, interactive) {
+                                break;
+                            }
+                            if rl.keep_linenumer() {
+                                linenumber += cmdline.split('\n').count();
                             }
+                            cmdline.clear()
                         }
                     }
                 }
@@ -178,8 +298,11 @@ impl App {
                 Err(ReadLineError::Eof) => {
                     if cmdline.is_empty() {
                         break;
-                    } else {
-                        cmdline.clear()
+                    }
+                    // The last command is not terminated.
+                    match incomplete.take() {
+                        Some(e) if !self.syntax_error(&e, interactive) => break,
+                        _ => cmdline.clear(),
                     }
                 }
                 Err(err) => {
@@ -193,6 +316,95 @@ impl App {
         self.ctx.status.code()
     }
 
+    // Reads the whole input for -n, --dump-ast and fmt.
+    fn read_source(&self, params: &AppParameter) -> Result<Source, i32> {
+        let (name, input) = match &params.source {
+            InputSource::File(path) => (path.as_str(), fs::read_to_string(path)),
+            InputSource::Command(command) => (APP_NAME, Ok(command.to_string())),
+            InputSource::Tty | InputSource::Stdin => {
+                let mut input = String::new();
+                (
+                    STDIN_NAME,
+                    io::stdin().read_to_string(&mut input).map(|_| input),
+                )
+            }
+        };
+        match input {
+            Ok(input) => Ok(Source::new(name, input, 0)),
+            Err(e) => {
+                eprintln!("rbsh: {name}: {e}");
+                Err(ExitStatus::failure().code())
+            }
+        }
+    }
+
+    // rbsh -n: parses the whole input and prints all syntax errors.
+    fn check(&self, params: &AppParameter) -> i32 {
+        let source = match self.read_source(params) {
+            Ok(source) => source,
+            Err(code) => return code,
+        };
+        let errors = check_command_line(source.input(), 0);
+        match params.error_format {
+            ErrorFormat::Human => errors
+                .iter()
+                .for_each(|e| eprint!("{}", source.syntax_error(e))),
+            ErrorFormat::Json => println!("{}", source.syntax_errors_json(&errors)),
+        }
+
+        match errors.is_empty() {
+            true => ExitStatus::success().code(),
+            false => ExitStatus::new(2).code(),
+        }
+    }
+
+    // rbsh --dump-ast: prints the syntax tree of the input without executing it.
+    fn dump_ast(&self, params: &AppParameter, format: AstFormat) -> i32 {
+        let source = match self.read_source(params) {
+            Ok(source) => source,
+            Err(code) => return code,
+        };
+        let units = match parse_command_line(source.input(), 0) {
+            Ok((units, _)) => units,
+            Err(e) => {
+                eprint!("{}", source.syntax_error(&e));
+                return ExitStatus::new(2).code();
+            }
+        };
+
+        match format {
+            AstFormat::Json => println!("{}", serde_json::to_string_pretty(&units).unwrap()),
+            AstFormat::Tree => print!("{}", ast_tree(&units)),
+        }
+        ExitStatus::success().code()
+    }
+
+    // rbsh fmt: prints the input formatted in the style without executing it.
+    fn format(&self, params: &AppParameter, style: FormatStyle) -> i32 {
+        let source = match self.read_source(params) {
+            Ok(source) => source,
+            Err(code) => return code,
+        };
+        match parse_command_line(source.input(), 0) {
+            Ok((units, _)) => {
+                print!("{}", unparse(&units, style));
+                ExitStatus::success().code()
+            }
+            Err(e) => {
+                eprint!("{}", source.syntax_error(&e));
+                ExitStatus::new(2).code()
+            }
+        }
+    }
+
+    // Prints a syntax error and sets the exit status to 2. Returns false if the shell
+    // should exit, which is the case when it is not interactive.
+    fn syntax_error(&mut self, e: &rbsh_parser::Error, interactive: bool) -> bool {
+        eprint!("{}", self.ctx.source.syntax_error(e));
+        self.ctx.status = ExitStatus::new(2);
+        interactive
+    }
+
     fn set_shell_variables(&mut self, params: &AppParameter) {
         macro_rules! vars {
             ($({$name: tt, $var: expr}, )+) => {
@@ -1,6 +1,9 @@
 mod cd;
+mod declare;
 mod echo;
+mod local;
 mod read;
+mod shopt;
 
 use super::{context::Context, status::ExitStatus};
 use once_cell::sync::Lazy;
@@ -21,8 +24,11 @@ static BUILTIN: Lazy<Vec<Builtin>> = Lazy::new(|| {
     }
     builtin![
         {"cd", cd::cd},
+        {"declare", declare::declare},
         {"echo", echo::echo},
+        {"local", local::local},
         {"read", read::read},
+        {"shopt", shopt::shopt},
     ]
 });
 
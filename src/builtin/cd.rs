@@ -1,8 +1,8 @@
 use crate::{context::Context, status::ExitStatus, syscall};
 use dirs::home_dir;
-use std::path::PathBuf;
+use std::{env::current_dir, path::PathBuf};
 
-pub fn cd(_: &mut Context, args: &[String]) -> ExitStatus {
+pub fn cd(ctx: &mut Context, args: &[String]) -> ExitStatus {
     let path = if args.is_empty() {
         home_dir().unwrap_or_default()
     } else {
@@ -15,8 +15,21 @@ pub fn cd(_: &mut Context, args: &[String]) -> ExitStatus {
         path
     };
 
+    let oldpwd = ctx.get_var("PWD").or_else(|| {
+        current_dir()
+            .ok()
+            .map(|dir| dir.to_string_lossy().to_string())
+    });
     match syscall::set_current_dir(path) {
-        Ok(_) => ExitStatus::success(),
+        Ok(_) => {
+            if let Some(oldpwd) = oldpwd {
+                ctx.set_var("OLDPWD", &oldpwd);
+            }
+            if let Ok(pwd) = current_dir() {
+                ctx.set_var("PWD", &*pwd.to_string_lossy());
+            }
+            ExitStatus::success()
+        }
         Err(e) => {
             eprintln!("rbsh: cd: {e}");
             ExitStatus::failure()
@@ -0,0 +1,132 @@
+use crate::{
+    context::{Context, Value},
+    status::ExitStatus,
+};
+use clap::Parser;
+use std::collections::BTreeMap;
+
+#[derive(Parser, Debug)]
+#[clap(
+    name = "declare",
+    about = "declare variables",
+    no_binary_name = true,
+    help_template = "{bin} - {about}
+
+USAGE:
+    {usage}
+
+{all-args}"
+)]
+struct DeclareOptions {
+    #[clap(
+        short = 'a',
+        conflicts_with = "associative",
+        help = "Make indexed arrays"
+    )]
+    indexed: bool,
+
+    #[clap(short = 'A', help = "Make associative arrays")]
+    associative: bool,
+
+    #[clap(short = 'p', help = "Display the attributes and values")]
+    print: bool,
+
+    names: Vec<String>,
+}
+
+pub fn declare(ctx: &mut Context, args: &[String]) -> ExitStatus {
+    let opts = match DeclareOptions::try_parse_from(args) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitStatus::failure();
+        }
+    };
+
+    if opts.print || opts.names.is_empty() {
+        let names = match opts.names.is_empty() {
+            true => ctx.var_names(),
+            false => opts.names,
+        };
+        let mut status = ExitStatus::success();
+        for name in names {
+            match ctx.get_value(&name) {
+                Some(value) => println!("declare {}", format(&name, &value)),
+                None => {
+                    eprintln!("rbsh: declare: {name}: not found");
+                    status = ExitStatus::failure();
+                }
+            }
+        }
+        return status;
+    }
+
+    for arg in opts.names {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+
+        // An existing array keeps its kind, and a string becomes the element 0.
+        let current = ctx.get_value(name);
+        match current {
+            Some(Value::Associative(_)) if opts.indexed => {
+                eprintln!("rbsh: declare: {name}: cannot convert associative to indexed array");
+                return ExitStatus::failure();
+            }
+            Some(Value::Indexed(_)) if opts.associative => {
+                eprintln!("rbsh: declare: {name}: cannot convert indexed to associative array");
+                return ExitStatus::failure();
+            }
+            Some(Value::String(s)) if opts.indexed || opts.associative => {
+                let array = match opts.associative {
+                    true => Value::Associative(BTreeMap::from([("0".to_string(), s)])),
+                    false => Value::Indexed(BTreeMap::from([(0, s)])),
+                };
+                ctx.set_value(name, array);
+            }
+            None if opts.associative => {
+                ctx.set_value(name, Value::Associative(BTreeMap::new()));
+            }
+            None if opts.indexed => {
+                ctx.set_value(name, Value::Indexed(BTreeMap::new()));
+            }
+            _ => (),
+        }
+
+        if let Some(value) = value {
+            ctx.set_var(name, value);
+        }
+    }
+
+    ExitStatus::success()
+}
+
+// Formats the variable in the form which can be read as input, e.g. -a a=([0]="x").
+fn format(name: &str, value: &Value) -> String {
+    let quote = |s: &str| {
+        let mut result = String::from('"');
+        for c in s.chars() {
+            if matches!(c, '\\' | '"' | '$' | '`') {
+                result.push('\\');
+            }
+            result.push(c);
+        }
+        result.push('"');
+        result
+    };
+    let elements = |value: &Value| {
+        value
+            .keys()
+            .into_iter()
+            .zip(value.values())
+            .map(|(k, v)| format!("[{k}]={}", quote(&v)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    match value {
+        Value::String(s) => format!("-- {name}={}", quote(s)),
+        Value::Indexed(_) => format!("-a {name}=({})", elements(value)),
+        Value::Associative(_) => format!("-A {name}=({})", elements(value)),
+    }
+}
@@ -0,0 +1,18 @@
+use crate::{context::Context, status::ExitStatus};
+
+pub fn local(ctx: &mut Context, args: &[String]) -> ExitStatus {
+    if !ctx.in_function() {
+        eprintln!("rbsh: local: can only be used in a function");
+        return ExitStatus::failure();
+    }
+
+    for arg in args {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        ctx.set_local_var(name, value);
+    }
+
+    ExitStatus::success()
+}
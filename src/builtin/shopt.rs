@@ -0,0 +1,77 @@
+use crate::{
+    context::{Context, ShellOptions},
+    status::ExitStatus,
+};
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[clap(
+    name = "shopt",
+    about = "set and unset shell options",
+    no_binary_name = true,
+    help_template = "{bin} - {about}
+
+USAGE:
+    {usage}
+
+{all-args}"
+)]
+struct ShoptOptions {
+    #[clap(short = 's', conflicts_with = "unset", help = "Enable each option")]
+    set: bool,
+
+    #[clap(short = 'u', help = "Disable each option")]
+    unset: bool,
+
+    #[clap(short = 'q', help = "Suppress output")]
+    quiet: bool,
+
+    names: Vec<String>,
+}
+
+pub fn shopt(ctx: &mut Context, args: &[String]) -> ExitStatus {
+    let opts = match ShoptOptions::try_parse_from(args) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitStatus::failure();
+        }
+    };
+
+    if let Some(name) = opts.names.iter().find(|n| ctx.options.get(n).is_none()) {
+        eprintln!("rbsh: shopt: {name}: invalid shell option name");
+        return ExitStatus::failure();
+    }
+
+    if (opts.set || opts.unset) && !opts.names.is_empty() {
+        for name in opts.names {
+            ctx.options.set(&name, opts.set);
+        }
+        return ExitStatus::success();
+    }
+
+    // Without names, prints all options, or only enabled (-s) or disabled (-u) ones.
+    // With names, the exit status tells whether all of them are enabled.
+    let query = !opts.names.is_empty();
+    let names = match opts.names.is_empty() {
+        true => ShellOptions::NAMES
+            .iter()
+            .map(|s| s.to_string())
+            .filter(|name| !(opts.set || opts.unset) || ctx.options.get(name) == Some(opts.set))
+            .collect(),
+        false => opts.names,
+    };
+
+    let mut all_on = true;
+    for name in names {
+        let value = ctx.options.get(&name).unwrap();
+        all_on &= value;
+        if !opts.quiet {
+            println!("{name:<15} {}", if value { "on" } else { "off" });
+        }
+    }
+    match all_on || !query {
+        true => ExitStatus::success(),
+        false => ExitStatus::failure(),
+    }
+}
@@ -1,11 +1,120 @@
-use super::{status::ExitStatus, syscall};
-use std::collections::HashMap;
+use super::{diagnostic::Source, status::ExitStatus, syscall};
+use nix::unistd::Pid;
+use std::{
+    collections::{BTreeMap, HashMap},
+    os::unix::io::RawFd,
+};
 
 #[derive(Debug, Clone)]
 pub struct Context {
-    local_vars: HashMap<String, String>,
+    local_vars: HashMap<String, Value>,
     pub status: ExitStatus,
     pub positional_parameters: Vec<String>,
+    pub options: ShellOptions,
+    pub process_substitutions: Vec<(RawFd, Pid)>, // the fds and processes of <(...) and >(...)
+    pub source: Source,                           // the input being executed, for error messages
+    pub interactive: bool,                        // whether commands are read from a terminal
+    frames: Vec<Frame>,
+}
+
+// Options which are changed with the shopt builtin.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ShellOptions {
+    pub dotglob: bool,
+    pub failglob: bool,
+    pub globstar: bool,
+    pub nullglob: bool,
+}
+
+impl ShellOptions {
+    pub const NAMES: [&'static str; 4] = ["dotglob", "failglob", "globstar", "nullglob"];
+
+    pub fn get(&self, name: &str) -> Option<bool> {
+        match name {
+            "dotglob" => Some(self.dotglob),
+            "failglob" => Some(self.failglob),
+            "globstar" => Some(self.globstar),
+            "nullglob" => Some(self.nullglob),
+            _ => None,
+        }
+    }
+
+    // Returns false if the name is not a valid option.
+    pub fn set(&mut self, name: &str, value: bool) -> bool {
+        let option = match name {
+            "dotglob" => &mut self.dotglob,
+            "failglob" => &mut self.failglob,
+            "globstar" => &mut self.globstar,
+            "nullglob" => &mut self.nullglob,
+            _ => return false,
+        };
+        *option = value;
+        true
+    }
+}
+
+// The value of a shell variable. A string is the same as an indexed array
+// which has only the element 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    String(String),
+    Indexed(BTreeMap<usize, String>),
+    Associative(BTreeMap<String, String>),
+}
+
+impl Value {
+    // The key of an indexed array is a non-negative integer.
+    pub fn get(&self, key: &str) -> Option<String> {
+        match self {
+            Value::String(s) => (key == "0").then(|| s.to_string()),
+            Value::Indexed(map) => key.parse().ok().and_then(|i| map.get(&i).cloned()),
+            Value::Associative(map) => map.get(key).cloned(),
+        }
+    }
+
+    pub fn set(&mut self, key: &str, value: String) {
+        match self {
+            Value::String(_) if key == "0" => *self = Value::String(value),
+            Value::String(s) => {
+                let mut map = BTreeMap::from([(0, std::mem::take(s))]);
+                if let Ok(i) = key.parse() {
+                    map.insert(i, value);
+                }
+                *self = Value::Indexed(map);
+            }
+            Value::Indexed(map) => {
+                if let Ok(i) = key.parse() {
+                    map.insert(i, value);
+                }
+            }
+            Value::Associative(map) => {
+                map.insert(key.to_string(), value);
+            }
+        }
+    }
+
+    pub fn values(&self) -> Vec<String> {
+        match self {
+            Value::String(s) => vec![s.to_string()],
+            Value::Indexed(map) => map.values().cloned().collect(),
+            Value::Associative(map) => map.values().cloned().collect(),
+        }
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        match self {
+            Value::String(_) => vec!["0".to_string()],
+            Value::Indexed(map) => map.keys().map(|i| i.to_string()).collect(),
+            Value::Associative(map) => map.keys().cloned().collect(),
+        }
+    }
+}
+
+// A call frame of a shell function.
+#[derive(Debug, Clone)]
+struct Frame {
+    positional_parameters: Vec<String>,
+    saved_vars: HashMap<String, (Option<Value>, bool)>, // the value and whether it is exported
 }
 
 impl Context {
@@ -14,7 +123,75 @@ impl Context {
             local_vars: HashMap::new(),
             status: ExitStatus::default(),
             positional_parameters: vec![],
+            options: ShellOptions::default(),
+            process_substitutions: vec![],
+            source: Source::default(),
+            interactive: false,
+            frames: vec![],
+        }
+    }
+
+    pub fn push_frame(&mut self, args: Vec<String>) {
+        let mut positional_parameters = args;
+        if let Some(name) = self.positional_parameters.first() {
+            positional_parameters.insert(0, name.to_string()); // $0 is not changed
         }
+
+        let positional_parameters =
+            std::mem::replace(&mut self.positional_parameters, positional_parameters);
+        self.frames.push(Frame {
+            positional_parameters,
+            saved_vars: HashMap::new(),
+        });
+    }
+
+    pub fn pop_frame(&mut self) {
+        if let Some(frame) = self.frames.pop() {
+            for (name, (value, exported)) in frame.saved_vars {
+                match value {
+                    // An exported variable is put back into the environment, even if it was
+                    // unset in the function.
+                    Some(Value::String(value)) if exported => {
+                        self.local_vars.remove(&name);
+                        syscall::env_set(&name, &value);
+                    }
+                    Some(value) => {
+                        self.set_value(&name, value);
+                    }
+                    None => {
+                        self.unset_var(&name);
+                    }
+                }
+            }
+            self.positional_parameters = frame.positional_parameters;
+        }
+    }
+
+    pub fn in_function(&self) -> bool {
+        !self.frames.is_empty()
+    }
+
+    // Sets a variable which is restored when the current frame is popped.
+    // Returns false when not in a function.
+    pub fn set_local_var<T: AsRef<str>>(&mut self, name: T, value: Option<T>) -> bool {
+        let name = name.as_ref();
+        let old_var = self.get_value(name);
+        let exported = syscall::env_get(name).is_ok();
+        match self.frames.last_mut() {
+            None => return false,
+            Some(frame) => {
+                frame
+                    .saved_vars
+                    .entry(name.to_string())
+                    .or_insert((old_var, exported));
+            }
+        }
+
+        match value {
+            Some(value) => self.set_var(name, value.as_ref()),
+            None => self.unset_var(name),
+        };
+        true
     }
 
     pub fn set_var<T: AsRef<str>>(&mut self, name: T, value: T) -> Option<String> {
@@ -24,19 +201,78 @@ impl Context {
         let old_var = self.get_var(name);
         match syscall::env_get(name) {
             Ok(_) => syscall::env_set(name, value),
-            Err(_) => {
-                self.local_vars.insert(name.to_string(), value.to_string());
-            }
+            Err(_) => match self.local_vars.get_mut(name) {
+                Some(var) => var.set("0", value.to_string()), // the element 0 of an array
+                None => {
+                    self.local_vars
+                        .insert(name.to_string(), Value::String(value.to_string()));
+                }
+            },
         };
 
         old_var
     }
 
+    // Sets a value which may be an array. An array is not exported to the environment.
+    pub fn set_value<T: AsRef<str>>(&mut self, name: T, value: Value) -> Option<Value> {
+        let name = name.as_ref();
+        let old_value = self.get_value(name);
+        match value {
+            Value::String(s) => {
+                self.local_vars.remove(name);
+                self.set_var(name, &*s);
+            }
+            _ => {
+                if syscall::env_get(name).is_ok() {
+                    syscall::env_unset(name);
+                }
+                self.local_vars.insert(name.to_string(), value);
+            }
+        }
+        old_value
+    }
+
+    pub fn get_value<T: AsRef<str>>(&self, name: T) -> Option<Value> {
+        let name = name.as_ref();
+        match self.local_vars.get(name) {
+            Some(value @ (Value::Indexed(_) | Value::Associative(_))) => Some(value.clone()),
+            _ => self.get_var(name).map(Value::String),
+        }
+    }
+
+    pub fn is_associative<T: AsRef<str>>(&self, name: T) -> bool {
+        matches!(
+            self.local_vars.get(name.as_ref()),
+            Some(Value::Associative(_))
+        )
+    }
+
+    // Sets an element of an array. An unset variable becomes an indexed array.
+    pub fn set_element<T: AsRef<str>>(&mut self, name: T, key: T, value: T) {
+        let name = name.as_ref();
+        let mut var = self
+            .get_value(name)
+            .unwrap_or_else(|| Value::Indexed(BTreeMap::new()));
+        var.set(key.as_ref(), value.as_ref().to_string());
+        self.set_value(name, var);
+    }
+
     pub fn get_var<T: AsRef<str>>(&self, name: T) -> Option<String> {
         let name = name.as_ref();
         self.get_special_var(name)
             .or_else(|| syscall::env_get(name).ok())
-            .or_else(|| self.local_vars.get(name).map(|s| s.to_string()))
+            .or_else(|| self.local_vars.get(name).and_then(|v| v.get("0")))
+    }
+
+    // Returns the names of all shell and environment variables in sorted order.
+    pub fn var_names(&self) -> Vec<String> {
+        let mut names = syscall::env_vars()
+            .into_keys()
+            .chain(self.local_vars.keys().cloned())
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        names
     }
 
     pub fn get_var_or_default<T: AsRef<str>>(&self, name: T, default: String) -> String {
@@ -60,6 +296,18 @@ impl Context {
                     .map(|s| s.to_string())
             }
             Some('?') => Some(self.status.code().to_string()),
+            Some('#') => Some(
+                self.positional_parameters
+                    .len()
+                    .saturating_sub(1)
+                    .to_string(),
+            ),
+            Some('@' | '*') => Some(
+                self.positional_parameters
+                    .get(1..)
+                    .unwrap_or_default()
+                    .join(" "),
+            ),
             Some('$') => {
                 let pid = syscall::getpid();
                 Some(format!("{pid}"))
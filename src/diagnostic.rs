@@ -0,0 +1,131 @@
+use crate::error::ShellError;
+use rbsh_parser::{Error, ErrorKind, Location, TokenKind};
+use serde_json::{json, Value};
+
+// The input being executed, which is used to show the line where an error occurred.
+#[derive(Debug, Clone, Default)]
+pub struct Source {
+    name: String,
+    input: String,
+    offset: usize, // the number of lines before the input
+}
+
+impl Source {
+    pub fn new<S: AsRef<str>>(name: &str, input: S, offset: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            input: input.as_ref().to_string(),
+            offset,
+        }
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    // The source of a command in the input, e.g. $(command).
+    pub fn nested<S: AsRef<str>>(&self, input: S) -> Self {
+        Self::new(&self.name, input, 0)
+    }
+
+    // e.g. script.sh:3:5: syntax error: unexpected 'fi'
+    pub fn syntax_error(&self, e: &Error) -> String {
+        let message = self.syntax_error_message(e);
+        self.render(&e.location, &format!("syntax error: {message}"))
+    }
+
+    // e.g. [{"file":"script.sh","line":3,"column":5,"kind":"UnexpectedToken",...}]
+    pub fn syntax_errors_json(&self, errors: &[Error]) -> String {
+        let errors = errors
+            .iter()
+            .map(|e| {
+                json!({
+                    "file": self.name,
+                    "line": e.location.line,
+                    "column": e.location.column,
+                    "end_column": e.location.column + self.text(&e.location).chars().count(),
+                    "kind": kind_name(&e.value),
+                    "message": self.syntax_error_message(e),
+                })
+            })
+            .collect::<Vec<_>>();
+        Value::Array(errors).to_string()
+    }
+
+    fn syntax_error_message(&self, e: &Error) -> String {
+        match &e.value {
+            ErrorKind::Eof => "unexpected end of file".to_string(),
+            ErrorKind::UnterminatedString => "unterminated string".to_string(),
+            ErrorKind::UnexpectedToken(kind) => {
+                format!("unexpected '{}'", self.token_text(kind, &e.location))
+            }
+            ErrorKind::InvalidUtf8Sequence(e) => format!("invalid UTF-8 sequence: {e}"),
+            ErrorKind::InvalidFd(fd) => format!("invalid file descriptor '{fd}'"),
+            ErrorKind::Unimplemented(kind) => {
+                format!(
+                    "'{}' is not implemented",
+                    self.token_text(kind, &e.location)
+                )
+            }
+        }
+    }
+
+    pub fn shell_error(&self, e: &ShellError) -> String {
+        self.render(&e.location(), &e.to_string())
+    }
+
+    fn render(&self, location: &Location, message: &str) -> String {
+        if location.line == 0 {
+            return format!("{}: {message}\n", self.name);
+        }
+
+        let mut result = format!(
+            "{}:{}:{}: {message}\n",
+            self.name, location.line, location.column
+        );
+        let line = location
+            .line
+            .checked_sub(self.offset + 1)
+            .and_then(|i| self.input.split('\n').nth(i));
+        if let Some(line) = line {
+            // Tabs are kept so that the caret is aligned with the token.
+            let indent = line
+                .chars()
+                .take(location.column - 1)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect::<String>();
+            let width = self.text(location).chars().count().max(1);
+            result.push_str(&format!("{line}\n{indent}{}\n", "^".repeat(width)));
+        }
+        result
+    }
+
+    // Returns the first line of the token in the input.
+    fn text(&self, location: &Location) -> &str {
+        let text = self.input.get(location.range()).unwrap_or_default();
+        text.split('\n').next().unwrap_or_default()
+    }
+
+    fn token_text(&self, kind: &TokenKind, location: &Location) -> String {
+        match kind {
+            TokenKind::NewLine => "newline".to_string(),
+            _ => match self.text(location) {
+                "" => format!("{kind:?}"),
+                text => text.to_string(),
+            },
+        }
+    }
+}
+
+fn kind_name(kind: &ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::Eof => "Eof",
+        ErrorKind::UnterminatedString => "UnterminatedString",
+        ErrorKind::UnexpectedToken(_) => "UnexpectedToken",
+        ErrorKind::InvalidUtf8Sequence(_) => "InvalidUtf8Sequence",
+        ErrorKind::InvalidFd(_) => "InvalidFd",
+        ErrorKind::Unimplemented(_) => "Unimplemented",
+    }
+}
+
+include!("diagnostic_test.rs");
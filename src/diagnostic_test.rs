@@ -0,0 +1,68 @@
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::ShellErrorKind;
+    use nix::errno::Errno;
+    use rbsh_parser::{check_command_line, parse_command_line};
+
+    fn syntax_error(input: &str, offset: usize) -> String {
+        let e = parse_command_line(input, offset).unwrap_err();
+        Source::new("script.sh", input, offset).syntax_error(&e)
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        assert_eq!(
+            syntax_error("if true; then\n  echo a; fi fi", 2),
+            "script.sh:4:14: syntax error: unexpected 'fi'\n  echo a; fi fi\n             ^^\n"
+        );
+        assert_eq!(
+            syntax_error("echo a; done", 0),
+            "script.sh:1:9: syntax error: unexpected 'done'\necho a; done\n        ^^^^\n"
+        );
+        assert_eq!(
+            syntax_error("\techo あ)", 0),
+            "script.sh:1:8: syntax error: unexpected ')'\n\techo あ)\n\t      ^\n"
+        );
+    }
+
+    #[test]
+    fn test_syntax_errors_json() {
+        let input = "echo a; done\nif true";
+        let errors = check_command_line(input, 0);
+        assert_eq!(
+            Source::new("script.sh", input, 0).syntax_errors_json(&errors),
+            concat!(
+                r#"[{"column":9,"end_column":13,"file":"script.sh","kind":"UnexpectedToken","line":1,"message":"unexpected 'done'"},"#,
+                r#"{"column":8,"end_column":8,"file":"script.sh","kind":"Eof","line":2,"message":"unexpected end of file"}]"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_shell_error() {
+        let source = Source::new("script.sh", "cat < a", 0);
+        let mut location = Location::new(5, 1);
+        location.offset = 4;
+        location.end = 7;
+        let kind = ShellErrorKind::SysCallError("open".to_string(), Errno::ENOENT);
+        let e = ShellError::new(kind.clone(), location);
+        assert_eq!(
+            source.shell_error(&e),
+            "script.sh:1:5: open: No such file or directory\ncat < a\n    ^^^\n"
+        );
+
+        // The line is not in the input.
+        let e = ShellError::new(kind.clone(), Location::new(5, 2));
+        assert_eq!(
+            source.shell_error(&e),
+            "script.sh:2:5: open: No such file or directory\n"
+        );
+
+        let e = ShellError::new(kind.clone(), Location::new(0, 0));
+        assert_eq!(
+            source.shell_error(&e),
+            "script.sh: open: No such file or directory\n"
+        );
+    }
+}
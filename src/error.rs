@@ -1,9 +1,29 @@
-use super::syscall::SysCallError;
+use super::{syscall::SysCallError, APP_NAME};
 use rbsh_parser::Location;
+use std::{fmt, io::Error as IoError};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ShellErrorKind {
     SysCallError(String, nix::Error),
+    ExpansionError(String), // e.g. $((1/0)) or ${x:?}
+}
+
+impl From<SysCallError> for ShellErrorKind {
+    fn from(e: SysCallError) -> Self {
+        Self::SysCallError(e.name().to_string(), e.errno())
+    }
+}
+
+impl From<IoError> for ShellErrorKind {
+    fn from(e: IoError) -> Self {
+        // The location is prefixed instead of the name of the shell.
+        let message = e.to_string();
+        let prefix = format!("{APP_NAME}: ");
+        match message.strip_prefix(&prefix) {
+            Some(message) => Self::ExpansionError(message.to_string()),
+            None => Self::ExpansionError(message),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -18,9 +38,19 @@ impl ShellError {
     }
 
     pub fn syscall_error(e: SysCallError, loc: Location) -> Self {
-        Self::new(
-            ShellErrorKind::SysCallError(e.name().to_string(), e.errno()),
-            loc,
-        )
+        Self::new(e.into(), loc)
+    }
+
+    pub fn location(&self) -> Location {
+        self.loc
+    }
+}
+
+impl fmt::Display for ShellError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ShellErrorKind::SysCallError(name, errno) => write!(f, "{name}: {}", errno.desc()),
+            ShellErrorKind::ExpansionError(message) => f.write_str(message),
+        }
     }
 }
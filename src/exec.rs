@@ -1,67 +1,111 @@
+#[cfg(test)]
+#[macro_use]
+mod test_helper;
+
+mod arithmetic;
+mod array;
+mod brace;
+mod conditional;
+mod field;
 mod mruby;
 mod option;
+mod parameter;
+mod pathname;
 mod redirect;
+mod tilde;
+mod time;
 
 pub use redirect::SHELL_FDBASE;
 
 use crate::{
     builtin::{builtin_command_exec, is_builtin_command},
-    context::Context,
+    context::{Context, Value},
     signal::{
         change_sa_restart_flag, close_signal_handler, reset_signal_handler, restore_tty_signals,
         JobSignalHandler,
     },
     status::ExitStatus,
     syscall::{self, PrCtlFlag, SysCallError, SysCallResult},
+    utils::Escape,
 };
+use array::Assignment;
+use glob::{MatchOptions, Pattern};
 use is_executable::IsExecutable;
 use mruby::mruby_exec;
 use nix::{
     errno::Errno,
-    sys::signal::Signal,
+    sys::{
+        resource::{getrusage, UsageWho},
+        signal::Signal,
+        time::{TimeVal, TimeValLike},
+    },
     unistd::{ForkResult, Pid},
 };
 use option::{ExecOption, ExecOptionBuilder};
 use rbsh_parser::{
-    parse_command_line, ConnecterKind, Location, Redirect, Unit, UnitKind, Word, WordKind,
+    parse_command_line, CaseItem, CaseTerminator, ConditionalExpression, ConnecterKind, Location,
+    Redirect, Unit, UnitKind, Word, WordKind,
 };
 use redirect::ApplyRedirect;
 use rust_mruby::MRuby;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     env,
     ffi::CString,
     fs::File,
-    io::{Error as IoError, Read},
+    io::{Error as IoError, ErrorKind, Read},
     os::unix::io::{FromRawFd, RawFd},
     path::PathBuf,
+    time::{Duration, Instant},
 };
 
 pub trait WordParser {
-    fn to_string(self, context: &Context) -> Result<String, IoError>;
+    fn to_string(self, context: &mut Context) -> Result<String, IoError>;
+
+    // Expands into fields, which are split by IFS and pathname expansion.
+    fn to_fields(self, context: &mut Context) -> Result<Vec<String>, IoError>;
 }
 
 impl WordParser for Word {
-    fn to_string(self, ctx: &Context) -> Result<String, std::io::Error> {
+    fn to_string(self, ctx: &mut Context) -> Result<String, std::io::Error> {
         match self.kind {
             WordKind::Normal | WordKind::Quote => Ok(self.string),
-            WordKind::Variable | WordKind::Parameter => {
-                Ok(ctx.get_var(self.string).unwrap_or_default())
-            }
+            WordKind::AnsiC => Ok(self.string.escape()),
+            WordKind::Variable => Ok(ctx.get_var(self.string).unwrap_or_default()),
+            WordKind::Parameter => parameter::expand(ctx, &self.string),
             WordKind::Command => Executor::capture_command_output(ctx, self.string),
+            WordKind::Arithmetic => {
+                arithmetic::expand_and_evaluate(ctx, &self.string).map(|value| value.to_string())
+            }
+            WordKind::Array => Ok(format!("({})", self.string)),
+            WordKind::InputProcess => Executor::substitute_process(ctx, self.string, false),
+            WordKind::OutputProcess => Executor::substitute_process(ctx, self.string, true),
         }
     }
+
+    fn to_fields(self, ctx: &mut Context) -> Result<Vec<String>, IoError> {
+        vec![self].to_fields(ctx)
+    }
 }
 
 impl WordParser for Vec<Word> {
-    fn to_string(self, ctx: &Context) -> Result<String, IoError> {
+    fn to_string(self, ctx: &mut Context) -> Result<String, IoError> {
         let mut result = String::new();
-        for word in self {
+        for word in tilde::expand(ctx, self) {
             let s = word.to_string(ctx)?;
             result.push_str(&s);
         }
         Ok(result)
     }
+
+    fn to_fields(self, ctx: &mut Context) -> Result<Vec<String>, IoError> {
+        let words = tilde::expand(ctx, self);
+        let mut result = vec![];
+        for field in field::split(ctx, words)? {
+            result.extend(pathname::expand(ctx, field)?);
+        }
+        Ok(result)
+    }
 }
 
 pub trait ToCString<T> {
@@ -103,6 +147,8 @@ impl IsVarName for Vec<Word> {
                 loop {
                     match c.next() {
                         Some(c) if c == '=' => break true,
+                        Some('+') => break c.next() == Some('='),
+                        Some('[') => break array::parse_assignment(self).is_some(),
                         Some(c) if c.is_alphanumeric() || c == '_' => continue,
                         _ => break false,
                     }
@@ -195,6 +241,14 @@ enum SimpleCommandKind {
     Continue {
         args: Args,
     },
+    Return {
+        args: Args,
+    },
+    Function {
+        env: Env,
+        command: String,
+        args: Args,
+    },
     MRuby {
         env: Env,
         args: Args,
@@ -203,6 +257,7 @@ enum SimpleCommandKind {
         env: Env,
         command: String,
         args: Args,
+        arrays: Vec<Assignment>, // name=(...) of local, assigned after the builtin
     },
     External {
         env: Env,
@@ -214,13 +269,22 @@ enum SimpleCommandKind {
 type Env = HashMap<String, String>;
 type Args = Vec<String>;
 
+#[derive(Debug, Clone)]
+struct Function {
+    params: Vec<String>,
+    body: Unit,
+}
+
 pub struct Executor {
     handler: JobSignalHandler,
     pub job_id: u16,
     jobs: Vec<Job>,
+    substitutions: Vec<Pid>, // processes of <(...) and >(...) which are not reaped yet
     loop_level: usize,
     breaking: usize,
     continuing: usize,
+    returning: Option<ExitStatus>,
+    functions: HashMap<String, Function>,
     mrb: MRuby,
 }
 
@@ -230,9 +294,12 @@ impl Executor {
             handler: JobSignalHandler::start()?,
             job_id: 0,
             jobs: vec![],
+            substitutions: vec![],
             loop_level: 0,
             breaking: 0,
             continuing: 0,
+            returning: None,
+            functions: HashMap::new(),
             mrb: MRuby::new(),
         })
     }
@@ -249,6 +316,7 @@ impl Executor {
         self.loop_level = 0;
         self.breaking = 0;
         self.continuing = 0;
+        self.returning = None;
 
         self.execute_command_internal(ctx, cmd, option)
     }
@@ -260,6 +328,7 @@ impl Executor {
         option: Option<ExecOption>,
     ) -> ExitStatus {
         let option = option.unwrap_or_else(|| ExecOptionBuilder::new().build());
+        let substitutions = ctx.process_substitutions.len();
 
         let ret = match cmd.kind {
             UnitKind::SimpleCommand { command, redirect } => {
@@ -273,62 +342,44 @@ impl Executor {
                 }
                 ret
             }
+            UnitKind::Function { name, params, body } => {
+                self.define_function(ctx, name, params, *body)
+            }
+            // Comments and blank lines do not change the exit status.
+            UnitKind::Comment { .. } | UnitKind::BlankLine => ctx.status,
             kind => {
                 let background = cmd.background
                     || match kind {
                         UnitKind::Pipe { .. } => false,
                         _ => option.piping(),
                     };
-                let option = match background {
-                    false => option,
-                    true => {
-                        let pgid = option.pgid();
-                        match self.fork(pgid) {
-                            Err(e) => {
-                                eprintln!("{}: {}", e.name(), e.desc());
-                                return ExitStatus::failure();
-                            }
-                            Ok(Some(child)) => {
-                                child.start();
-                                let ret = self.start_job(child.pid(), child.pgid());
-                                if cmd.background {
-                                    let job = self.jobs.last().unwrap();
-                                    if option.verbose() {
-                                        println!("[{}] {}", job.id, job.pgid);
-                                    }
-                                }
-                                return ret;
-                            }
-                            Ok(None) => {
-                                self.handler = JobSignalHandler::start().unwrap();
-                                let pgid = pgid.unwrap_or_else(syscall::getpid);
-                                ExecOptionBuilder::from(option).pgid(pgid).build()
-                            }
-                        }
-                    }
+                let option = match self.fork_if_background(background, cmd.background, option) {
+                    Ok(option) => option,
+                    Err(status) => return status,
                 };
 
-                if let Some(fd) = option.leak_fd() {
-                    match close(fd) {
-                        Ok(_) => (),
-                        Err(e) => {
-                            eprintln!("{}, {}", e.name(), e.desc());
-                            return ExitStatus::failure();
-                        }
-                    }
-                }
-
                 let ret = match kind {
                     UnitKind::SimpleCommand {
                         command: _,
                         redirect: _,
                     } => unreachable![],
+                    UnitKind::Function { .. } => unreachable![],
+                    UnitKind::Comment { .. } | UnitKind::BlankLine => unreachable![],
                     UnitKind::Connecter { left, right, kind } => {
                         self.execute_connecter(ctx, *left, *right, kind, option)
                     }
                     UnitKind::Pipe { left, right, both } => {
                         self.execute_pipe(ctx, *left, *right, both, option)
                     }
+                    UnitKind::Not { command } => {
+                        match self.execute_command_internal(ctx, *command, Some(option)) {
+                            status if status.is_success() => ExitStatus::failure(),
+                            _ => ExitStatus::success(),
+                        }
+                    }
+                    UnitKind::Time { command, posix } => {
+                        self.execute_time_command(ctx, *command, posix, option)
+                    }
                     UnitKind::If {
                         condition,
                         true_case,
@@ -364,6 +415,45 @@ impl Executor {
                         command,
                         redirect,
                     } => self.execute_for_command(ctx, identifier, list, command, redirect, option),
+                    UnitKind::Select {
+                        identifier,
+                        list,
+                        command,
+                        redirect,
+                    } => self
+                        .execute_select_command(ctx, identifier, list, command, redirect, option),
+                    UnitKind::ArithmeticFor {
+                        init,
+                        condition,
+                        step,
+                        command,
+                        redirect,
+                    } => self.execute_arithmetic_for_command(
+                        ctx, init, condition, step, command, redirect, option,
+                    ),
+                    UnitKind::Case {
+                        word,
+                        items,
+                        else_case,
+                        redirect,
+                    } => self.execute_case_command(ctx, word, items, else_case, redirect, option),
+                    UnitKind::Group { command, redirect } => {
+                        self.execute_group_command(ctx, command, redirect, option)
+                    }
+                    UnitKind::Subshell { command, redirect } => {
+                        self.execute_subshell_command(ctx, command, redirect, background, option)
+                    }
+                    UnitKind::Arithmetic {
+                        expression,
+                        redirect,
+                    } => self.execute_arithmetic_command(ctx, expression, redirect, option),
+                    UnitKind::Conditional {
+                        expression,
+                        redirect,
+                    } => self.execute_conditional_command(ctx, expression, redirect, option),
+                    UnitKind::Coproc { name, command } => {
+                        self.execute_coproc_command(ctx, name, *command, option)
+                    }
                 };
 
                 match background {
@@ -373,10 +463,61 @@ impl Executor {
             }
         };
 
+        self.close_process_substitutions(ctx, substitutions);
         ctx.status = ret;
         ret
     }
 
+    // Forks a child process to run a compound command in the background or in a pipeline.
+    // Returns Err with the exit status in the parent process.
+    fn fork_if_background(
+        &mut self,
+        background: bool,
+        print_job: bool,
+        option: ExecOption,
+    ) -> std::result::Result<ExecOption, ExitStatus> {
+        let option = match background {
+            false => option,
+            true => {
+                let pgid = option.pgid();
+                match self.fork(pgid) {
+                    Err(e) => {
+                        eprintln!("{}: {}", e.name(), e.desc());
+                        return Err(ExitStatus::failure());
+                    }
+                    Ok(Some(child)) => {
+                        child.start();
+                        let ret = self.start_job(child.pid(), child.pgid());
+                        if print_job {
+                            let job = self.jobs.last().unwrap();
+                            if option.verbose() {
+                                println!("[{}] {}", job.id, job.pgid);
+                            }
+                        }
+                        return Err(ret);
+                    }
+                    Ok(None) => {
+                        self.handler = JobSignalHandler::start().unwrap();
+                        let pgid = pgid.unwrap_or_else(syscall::getpid);
+                        ExecOptionBuilder::from(option).pgid(pgid).build()
+                    }
+                }
+            }
+        };
+
+        if let Some(fd) = option.leak_fd() {
+            match close(fd) {
+                Ok(_) => (),
+                Err(e) => {
+                    eprintln!("{}, {}", e.name(), e.desc());
+                    return Err(ExitStatus::failure());
+                }
+            }
+        }
+
+        Ok(option)
+    }
+
     fn execute_simple_command(
         &mut self,
         ctx: &mut Context,
@@ -389,8 +530,8 @@ impl Executor {
             Ok(ret) => ret,
             Err(e) => {
                 return match e.kind() {
-                    std::io::ErrorKind::Interrupted => ExitStatus::signaled(Signal::SIGINT),
-                    e => {
+                    ErrorKind::Interrupted => ExitStatus::signaled(Signal::SIGINT),
+                    _ => {
                         eprintln!("{e}");
                         ExitStatus::failure()
                     }
@@ -398,6 +539,22 @@ impl Executor {
             }
         };
 
+        // Shell functions take precedence over builtin and external commands.
+        let kind = match kind {
+            SimpleCommandKind::Builtin {
+                env, command, args, ..
+            }
+            | SimpleCommandKind::External { env, command, args }
+                if self.functions.contains_key(&command) =>
+            {
+                SimpleCommandKind::Function { env, command, args }
+            }
+            kind => kind,
+        };
+        if let SimpleCommandKind::Function { env, command, args } = kind {
+            return self.execute_function(ctx, command, env, args, redirect, background, option);
+        }
+
         let background = background || option.piping();
         let need_fork = matches!(
             kind,
@@ -415,36 +572,7 @@ impl Executor {
                         child.start();
                         return self.start_job(child.pid(), child.pgid());
                     }
-                    false => {
-                        let old_pgrp = match syscall::isatty(0).unwrap_or(false) {
-                            false => None,
-                            true => match pgid {
-                                Some(_) => None,
-                                None => syscall::tcgetpgrp(0)
-                                    .map(|old| {
-                                        syscall::tcsetpgrp(0, child.pgid()).ok();
-                                        old
-                                    })
-                                    .ok(),
-                            },
-                        };
-
-                        self.handler.set_forground(child.pgid());
-                        self.start_job(child.pid(), child.pgid());
-                        child.start();
-                        let status = self
-                            .handler
-                            .wait_for(child.pid(), true)
-                            .unwrap_or_else(ExitStatus::failure);
-                        self.handler.reset_forground();
-                        self.jobs.pop(); // remove current job
-
-                        if let Some(pgid) = old_pgrp {
-                            syscall::tcsetpgrp(0, pgid).ok();
-                        }
-
-                        return status;
-                    }
+                    false => return self.wait_forground(child, pgid),
                 },
                 Ok(None) => (),
             }
@@ -468,7 +596,7 @@ impl Executor {
         let restore = match redirect.apply(ctx, !(background || need_fork)) {
             Ok(r) => r,
             Err(e) => {
-                eprintln!("{e:?}");
+                eprint!("{}", ctx.source.shell_error(&e));
                 return match need_fork {
                     false => ExitStatus::failure(),
                     true => syscall::exit(1),
@@ -487,6 +615,8 @@ impl Executor {
             SimpleCommandKind::Break { .. } | SimpleCommandKind::Continue { .. } => {
                 self.do_break_or_continue(kind)
             }
+            SimpleCommandKind::Return { args } => self.do_return(ctx, args),
+            SimpleCommandKind::Function { .. } => unreachable![],
             SimpleCommandKind::MRuby { env, args } => {
                 let name = CString::new("mruby").unwrap();
                 if let Some(e) =
@@ -510,7 +640,12 @@ impl Executor {
                     }
                 }
             }
-            SimpleCommandKind::Builtin { env, command, args } => {
+            SimpleCommandKind::Builtin {
+                env,
+                command,
+                args,
+                arrays,
+            } => {
                 let old_env_vars = env
                     .iter()
                     .map(|(k, v)| {
@@ -519,7 +654,15 @@ impl Executor {
                     })
                     .collect::<Vec<_>>();
 
-                let status = builtin_command_exec(ctx, command, &args);
+                let mut status = builtin_command_exec(ctx, command, &args);
+                if status.is_success() {
+                    for assignment in arrays {
+                        if let Err(e) = array::assign(ctx, assignment) {
+                            eprintln!("{e}");
+                            status = ExitStatus::failure();
+                        }
+                    }
+                }
 
                 old_env_vars.iter().for_each(|(k, v)| {
                     match v {
@@ -587,6 +730,111 @@ impl Executor {
         }
     }
 
+    fn do_return(&mut self, ctx: &Context, args: Args) -> ExitStatus {
+        if !ctx.in_function() {
+            eprintln!("rbsh: return: can only `return' from a function");
+            return ExitStatus::failure();
+        }
+
+        let status = match args.len() {
+            0 => ctx.status,
+            1 => match args[0].parse::<i32>() {
+                Ok(n) => ExitStatus::new(n & 0xff),
+                Err(_) => {
+                    eprintln!("rbsh: return: {}: numeric argument required", args[0]);
+                    ExitStatus::new(2)
+                }
+            },
+            _ => {
+                eprintln!("rbsh: return: too many arguments");
+                return ExitStatus::failure();
+            }
+        };
+
+        self.returning = Some(status);
+        status
+    }
+
+    fn define_function(
+        &mut self,
+        ctx: &mut Context,
+        name: Vec<Word>,
+        params: Vec<Vec<Word>>,
+        body: Unit,
+    ) -> ExitStatus {
+        let name = match name.to_string(ctx) {
+            Ok(name) => name,
+            Err(e) => {
+                eprintln!("{e}");
+                return ExitStatus::failure();
+            }
+        };
+        let params = match params
+            .into_iter()
+            .map(|p| p.to_string(ctx))
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(params) => params,
+            Err(e) => {
+                eprintln!("{e}");
+                return ExitStatus::failure();
+            }
+        };
+
+        self.functions.insert(name, Function { params, body });
+        ExitStatus::success()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn execute_function(
+        &mut self,
+        ctx: &mut Context,
+        name: String,
+        env: Env,
+        args: Args,
+        redirect: Option<Vec<Redirect>>,
+        background: bool,
+        option: ExecOption,
+    ) -> ExitStatus {
+        let forked = background || option.piping();
+        let option = match self.fork_if_background(forked, false, option) {
+            Ok(option) => option,
+            Err(status) => return status,
+        };
+        let (restore, option) = self.update_option_and_apply_redirect(ctx, option, redirect);
+
+        let function = self.functions.get(&name).cloned().unwrap();
+        let old_env_vars = env
+            .iter()
+            .map(|(k, v)| (k, ctx.set_var(k, v)))
+            .collect::<Vec<_>>();
+
+        ctx.push_frame(args.clone());
+        for (i, param) in function.params.iter().enumerate() {
+            ctx.set_local_var(param, Some(&args.get(i).cloned().unwrap_or_default()));
+        }
+
+        // break/continue can not exit loops outside the function.
+        let loop_level = std::mem::replace(&mut self.loop_level, 0);
+        let status = self.execute_command_internal(ctx, function.body, Some(option));
+        let status = self.returning.take().unwrap_or(status);
+        self.loop_level = loop_level;
+
+        ctx.pop_frame();
+        old_env_vars.iter().for_each(|(k, v)| {
+            match v {
+                None => ctx.unset_var(k),
+                Some(v) => ctx.set_var(k, &v),
+            };
+        });
+        restore.apply(ctx, false).ok();
+
+        match forked {
+            true => syscall::exit(status.code()),
+            false => status,
+        }
+    }
+
     fn execute_connecter(
         &mut self,
         ctx: &mut Context,
@@ -597,6 +845,9 @@ impl Executor {
     ) -> ExitStatus {
         let option = ExecOptionBuilder::from(option).piping(false).build();
         let condition = self.execute_command_internal(ctx, left, Some(option));
+        if self.breaking > 0 || self.continuing > 0 || self.returning.is_some() {
+            return condition;
+        }
 
         let option = ExecOptionBuilder::from(option)
             .input(None)
@@ -683,6 +934,51 @@ impl Executor {
         }
     }
 
+    // Reports the real, user and system time of the pipeline to stderr in the format of
+    // TIMEFORMAT. The user and system time include the children reaped in the meantime.
+    fn execute_time_command(
+        &mut self,
+        ctx: &mut Context,
+        command: Unit,
+        posix: bool,
+        option: ExecOption,
+    ) -> ExitStatus {
+        let start = Instant::now();
+        let before = self.cpu_times();
+        let status = self.execute_command_internal(ctx, command, Some(option));
+        let after = self.cpu_times();
+
+        let times = time::Times {
+            real: start.elapsed(),
+            user: after.0.saturating_sub(before.0),
+            sys: after.1.saturating_sub(before.1),
+        };
+        let format = match posix {
+            true => time::POSIX_FORMAT.to_string(),
+            false => ctx.get_var_or_default("TIMEFORMAT", time::DEFAULT_FORMAT.to_string()),
+        };
+        if !format.is_empty() {
+            eprintln!("{}", time::format(&format, &times));
+        }
+
+        status
+    }
+
+    // The user and system time of the shell and its children.
+    fn cpu_times(&self) -> (Duration, Duration) {
+        let (user, sys) = self.handler.children_times();
+        match getrusage(UsageWho::RUSAGE_SELF) {
+            Ok(usage) => {
+                let duration = |t: TimeVal| Duration::from_micros(t.num_microseconds() as u64);
+                (
+                    user + duration(usage.user_time()),
+                    sys + duration(usage.system_time()),
+                )
+            }
+            Err(_) => (user, sys),
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn execute_if_command(
         &mut self,
@@ -698,19 +994,10 @@ impl Executor {
 
         let ret = match self.execute_command_internal(ctx, condition, Some(option)) {
             status if (!inverse && status.is_success()) || (inverse && status.is_error()) => {
-                let s = true_case
-                    .into_iter()
-                    .map(|command| self.execute_command_internal(ctx, command, Some(option)));
-                s.last().unwrap()
+                self.execute_commands(ctx, true_case, option)
             }
             status if false_case.is_none() => status,
-            _ => {
-                let s = false_case
-                    .unwrap()
-                    .into_iter()
-                    .map(|command| self.execute_command_internal(ctx, command, Some(option)));
-                s.last().unwrap()
-            }
+            _ => self.execute_commands(ctx, false_case.unwrap(), option),
         };
 
         restore.apply(ctx, false).unwrap();
@@ -740,6 +1027,10 @@ impl Executor {
             }
             macro_rules! break_or_continue {
                 () => {
+                    if self.returning.is_some() {
+                        break 'exec;
+                    }
+
                     if self.breaking > 0 {
                         self.breaking -= 1;
                         break 'exec;
@@ -786,51 +1077,413 @@ impl Executor {
         redirect: Option<Vec<Redirect>>,
         option: ExecOption,
     ) -> ExitStatus {
-        // ToDo: References all items.
-        let identifier = match identifier.first() {
-            Some(word) if word.kind == WordKind::Normal => word.string.clone(),
-            _ => {
-                eprintln!("error: invalid identifier");
-                return ExitStatus::failure();
-            }
+        let (identifier, words) = match expand_for_list(ctx, identifier, list) {
+            Some(expanded) => expanded,
+            None => return ExitStatus::failure(),
         };
 
-        let list = match list {
-            None => vec![], // Normally, it returns $@.
-            Some(list) => list
-                .into_iter()
-                .map(|w| w.to_string(ctx).unwrap())
-                .collect::<Vec<_>>(),
+        let (restore, option) = self.update_option_and_apply_redirect(ctx, option, redirect);
+        self.loop_level += 1;
+        for word in words.iter() {
+            ctx.set_var(&identifier, word);
+            if !self.execute_loop_body(ctx, &command, option) {
+                break;
+            }
+        }
+        self.loop_level -= 1;
+        restore.apply(ctx, false).ok();
+
+        ExitStatus::new(0)
+    }
+
+    // Prints the numbered menu to stderr and reads the reply with PS3 as the prompt until
+    // EOF or break. The menu is printed again when the reply is empty.
+    fn execute_select_command(
+        &mut self,
+        ctx: &mut Context,
+        identifier: Vec<Word>,
+        list: Option<Vec<Vec<Word>>>,
+        command: Vec<Unit>,
+        redirect: Option<Vec<Redirect>>,
+        option: ExecOption,
+    ) -> ExitStatus {
+        let (identifier, words) = match expand_for_list(ctx, identifier, list) {
+            Some(expanded) => expanded,
+            None => return ExitStatus::failure(),
         };
+        if words.is_empty() {
+            return ExitStatus::success();
+        }
 
         let (restore, option) = self.update_option_and_apply_redirect(ctx, option, redirect);
         self.loop_level += 1;
-        'exec: for word in list.iter() {
-            ctx.set_var(&identifier, word);
-            for c in command.iter().cloned() {
-                if self.handler.is_interrupt() {
-                    break 'exec;
+        let width = words.len().to_string().len();
+        let mut print_menu = true;
+        let mut status = ExitStatus::success();
+        while !self.handler.is_interrupt() {
+            if print_menu {
+                for (i, word) in words.iter().enumerate() {
+                    eprintln!("{:>width$}) {word}", i + 1);
                 }
+            }
+            eprint!("{}", ctx.get_var_or_default("PS3", "#? ".to_string()));
+
+            let reply = match read_line(0) {
+                Ok(Some(reply)) => reply,
+                // Like read, the loop fails at EOF.
+                Ok(None) => {
+                    eprintln!();
+                    status = ExitStatus::failure();
+                    break;
+                }
+                Err(e) => {
+                    if e.kind() != ErrorKind::Interrupted {
+                        eprintln!("{e}");
+                    }
+                    status = ExitStatus::failure();
+                    break;
+                }
+            };
+            print_menu = reply.is_empty();
+            if print_menu {
+                continue;
+            }
 
-                self.execute_command_internal(ctx, c, Some(option));
+            let selected = match reply.trim().parse::<usize>() {
+                Ok(n) if n >= 1 => words.get(n - 1).map_or("", String::as_str),
+                _ => "",
+            };
+            ctx.set_var("REPLY", &reply);
+            ctx.set_var(identifier.as_str(), selected);
+            if !self.execute_loop_body(ctx, &command, option) {
+                break;
+            }
+        }
+        self.loop_level -= 1;
+        restore.apply(ctx, false).ok();
 
-                if self.breaking > 0 {
-                    self.breaking -= 1;
-                    break 'exec;
-                }
+        status
+    }
 
-                if self.continuing > 0 {
-                    self.continuing -= 1;
-                    if self.continuing > 0 {
-                        break 'exec;
+    #[allow(clippy::too_many_arguments)]
+    fn execute_arithmetic_for_command(
+        &mut self,
+        ctx: &mut Context,
+        init: Word,
+        condition: Word,
+        step: Word,
+        command: Vec<Unit>,
+        redirect: Option<Vec<Redirect>>,
+        option: ExecOption,
+    ) -> ExitStatus {
+        let (restore, option) = self.update_option_and_apply_redirect(ctx, option, redirect);
+        self.loop_level += 1;
+
+        let mut status = match evaluate_arithmetic(ctx, &init.string) {
+            Some(_) => ExitStatus::success(),
+            None => ExitStatus::failure(),
+        };
+        while status.is_success() && !self.handler.is_interrupt() {
+            // An empty condition is always true.
+            let result = match condition.string.trim().is_empty() {
+                true => Some(1),
+                false => evaluate_arithmetic(ctx, &condition.string),
+            };
+            match result {
+                None => status = ExitStatus::failure(),
+                Some(0) => break,
+                Some(_) => {
+                    if !self.execute_loop_body(ctx, &command, option) {
+                        break;
+                    }
+                    if evaluate_arithmetic(ctx, &step.string).is_none() {
+                        status = ExitStatus::failure();
                     }
                 }
             }
         }
+
         self.loop_level -= 1;
         restore.apply(ctx, false).ok();
+        status
+    }
 
-        ExitStatus::new(0)
+    // Runs the body of a for loop once.
+    // Returns false when the loop should be exited by break, continue n, return or interrupt.
+    fn execute_loop_body(
+        &mut self,
+        ctx: &mut Context,
+        command: &[Unit],
+        option: ExecOption,
+    ) -> bool {
+        for c in command.iter().cloned() {
+            if self.handler.is_interrupt() {
+                return false;
+            }
+
+            self.execute_command_internal(ctx, c, Some(option));
+
+            if self.returning.is_some() {
+                return false;
+            }
+
+            if self.breaking > 0 {
+                self.breaking -= 1;
+                return false;
+            }
+
+            if self.continuing > 0 {
+                self.continuing -= 1;
+                return self.continuing == 0;
+            }
+        }
+        true
+    }
+
+    fn execute_case_command(
+        &mut self,
+        ctx: &mut Context,
+        word: Vec<Word>,
+        items: Vec<CaseItem>,
+        else_case: Option<Vec<Unit>>,
+        redirect: Option<Vec<Redirect>>,
+        option: ExecOption,
+    ) -> ExitStatus {
+        let word = match word.to_string(ctx) {
+            Ok(word) => word,
+            Err(e) => {
+                eprintln!("{e}");
+                return ExitStatus::failure();
+            }
+        };
+
+        let (restore, option) = self.update_option_and_apply_redirect(ctx, option, redirect);
+
+        let mut status = ExitStatus::success();
+        let mut matched = false;
+        let mut fall_through = false;
+        for item in items {
+            if !fall_through && !item.patterns.into_iter().any(|p| is_match(ctx, p, &word)) {
+                continue;
+            }
+
+            matched = true;
+            status = self.execute_commands(ctx, item.command, option);
+            match item.terminator {
+                CaseTerminator::Break => break,
+                CaseTerminator::FallThrough => fall_through = true,
+                CaseTerminator::Continue => fall_through = false,
+            }
+        }
+
+        if let (false, Some(command)) = (matched, else_case) {
+            status = self.execute_commands(ctx, command, option);
+        }
+
+        restore.apply(ctx, false).ok();
+        status
+    }
+
+    fn execute_arithmetic_command(
+        &mut self,
+        ctx: &mut Context,
+        expression: Word,
+        redirect: Option<Vec<Redirect>>,
+        option: ExecOption,
+    ) -> ExitStatus {
+        let (restore, _) = self.update_option_and_apply_redirect(ctx, option, redirect);
+        let status = match evaluate_arithmetic(ctx, &expression.string) {
+            Some(0) | None => ExitStatus::failure(),
+            Some(_) => ExitStatus::success(),
+        };
+        restore.apply(ctx, false).ok();
+        status
+    }
+
+    fn execute_conditional_command(
+        &mut self,
+        ctx: &mut Context,
+        expression: ConditionalExpression,
+        redirect: Option<Vec<Redirect>>,
+        option: ExecOption,
+    ) -> ExitStatus {
+        let (restore, _) = self.update_option_and_apply_redirect(ctx, option, redirect);
+        let status = match conditional::evaluate(ctx, expression) {
+            Ok(true) => ExitStatus::success(),
+            Ok(false) => ExitStatus::failure(),
+            Err(e) => {
+                eprintln!("{e}");
+                ExitStatus::new(2)
+            }
+        };
+        restore.apply(ctx, false).ok();
+        status
+    }
+
+    // Starts the command as a background job which is connected with two pipes. The fds of
+    // the pipes are set to NAME[0] (the output of the command) and NAME[1] (the input).
+    fn execute_coproc_command(
+        &mut self,
+        ctx: &mut Context,
+        name: Option<Vec<Word>>,
+        command: Unit,
+        option: ExecOption,
+    ) -> ExitStatus {
+        let name = match name {
+            Some(name) => match name.to_string(ctx) {
+                Ok(name) => name,
+                Err(e) => {
+                    eprintln!("{e}");
+                    return ExitStatus::failure();
+                }
+            },
+            None => "COPROC".to_string(),
+        };
+
+        let pipes = pipe().and_then(|output| match pipe() {
+            Ok(input) => Ok((output, input)),
+            Err(e) => {
+                close(output.0).ok();
+                close(output.1).ok();
+                Err(e)
+            }
+        });
+        let ((output_read, output_write), (input_read, input_write)) = match pipes {
+            Ok(pipes) => pipes,
+            Err(e) => {
+                eprintln!("{}: {}", e.name(), e.desc());
+                return ExitStatus::failure();
+            }
+        };
+
+        match self.fork_if_background(true, true, option) {
+            Err(status) => {
+                close(output_write).ok();
+                close(input_read).ok();
+                if status.is_success() {
+                    let pid = self.jobs.last().unwrap().pgid;
+                    let fds = BTreeMap::from([
+                        (0, output_read.to_string()),
+                        (1, input_write.to_string()),
+                    ]);
+                    ctx.set_value(&name, Value::Indexed(fds));
+                    ctx.set_var(format!("{name}_PID"), pid.to_string());
+                } else {
+                    close(output_read).ok();
+                    close(input_write).ok();
+                }
+                status
+            }
+            Ok(option) => {
+                let result =
+                    syscall::dup2(input_read, 0).and_then(|_| syscall::dup2(output_write, 1));
+                for fd in [output_read, output_write, input_read, input_write] {
+                    close(fd).ok();
+                }
+                let status = match result {
+                    Ok(_) => self.execute_command_internal(ctx, command, Some(option)),
+                    Err(e) => {
+                        eprintln!("{}: {}", e.name(), e.desc());
+                        ExitStatus::failure()
+                    }
+                };
+                syscall::exit(status.code())
+            }
+        }
+    }
+
+    fn execute_group_command(
+        &mut self,
+        ctx: &mut Context,
+        command: Vec<Unit>,
+        redirect: Option<Vec<Redirect>>,
+        option: ExecOption,
+    ) -> ExitStatus {
+        let (restore, option) = self.update_option_and_apply_redirect(ctx, option, redirect);
+        let status = self.execute_commands(ctx, command, option);
+        restore.apply(ctx, false).ok();
+        status
+    }
+
+    fn execute_commands(
+        &mut self,
+        ctx: &mut Context,
+        command: Vec<Unit>,
+        option: ExecOption,
+    ) -> ExitStatus {
+        let mut status = ExitStatus::success();
+        for c in command {
+            if self.handler.is_interrupt()
+                || self.breaking > 0
+                || self.continuing > 0
+                || self.returning.is_some()
+            {
+                break;
+            }
+            status = self.execute_command_internal(ctx, c, Some(option));
+        }
+        status
+    }
+
+    fn execute_subshell_command(
+        &mut self,
+        ctx: &mut Context,
+        command: Vec<Unit>,
+        redirect: Option<Vec<Redirect>>,
+        forked: bool,
+        option: ExecOption,
+    ) -> ExitStatus {
+        // Already running in a child process (e.g. background or pipeline).
+        if forked {
+            return self.execute_group_command(ctx, command, redirect, option);
+        }
+
+        let pgid = option.pgid();
+        match self.fork(pgid) {
+            Err(e) => {
+                eprintln!("{}: {}", e.name(), e.desc());
+                ExitStatus::failure()
+            }
+            Ok(Some(child)) => self.wait_forground(child, pgid),
+            Ok(None) => {
+                self.handler = JobSignalHandler::start().unwrap();
+                let pgid = pgid.unwrap_or_else(syscall::getpid);
+                let option = ExecOptionBuilder::from(option).pgid(pgid).build();
+                let status = self.execute_group_command(ctx, command, redirect, option);
+                syscall::exit(status.code())
+            }
+        }
+    }
+
+    fn wait_forground(&mut self, child: ChildProcess, pgid: Option<Pid>) -> ExitStatus {
+        let old_pgrp = match syscall::isatty(0).unwrap_or(false) {
+            false => None,
+            true => match pgid {
+                Some(_) => None,
+                None => syscall::tcgetpgrp(0)
+                    .map(|old| {
+                        syscall::tcsetpgrp(0, child.pgid()).ok();
+                        old
+                    })
+                    .ok(),
+            },
+        };
+
+        self.handler.set_forground(child.pgid());
+        self.start_job(child.pid(), child.pgid());
+        child.start();
+        let status = self
+            .handler
+            .wait_for(child.pid(), true)
+            .unwrap_or_else(ExitStatus::failure);
+        self.handler.reset_forground();
+        self.jobs.pop(); // remove current job
+
+        if let Some(pgid) = old_pgrp {
+            syscall::tcsetpgrp(0, pgid).ok();
+        }
+
+        status
     }
 
     fn fork(
@@ -893,7 +1546,20 @@ impl Executor {
         ExitStatus::success()
     }
 
+    // Closes the fds of process substitutions which were expanded by the command.
+    // The processes are reaped later in reap_job() or close().
+    fn close_process_substitutions(&mut self, ctx: &mut Context, len: usize) {
+        for (fd, pid) in ctx.process_substitutions.drain(len..) {
+            syscall::close(fd).ok();
+            self.substitutions.push(pid);
+        }
+    }
+
     pub fn reap_job(&mut self) {
+        let handler = &mut self.handler;
+        self.substitutions
+            .retain(|pid| handler.wait_for(*pid, false).is_none());
+
         self.jobs = self
             .jobs
             .iter()
@@ -928,7 +1594,7 @@ impl Executor {
 
     fn update_option_and_apply_redirect(
         &self,
-        ctx: &Context,
+        ctx: &mut Context,
         option: ExecOption,
         mut redirect: Option<Vec<Redirect>>,
     ) -> (Vec<Redirect>, ExecOption) {
@@ -973,7 +1639,7 @@ impl Executor {
                 Err(IoError::from_raw_os_error(e.errno() as i32))
             }
             Ok(ForkResult::Parent { child }) => {
-                syscall::setpgid(child, child).unwrap();
+                syscall::setpgid(child, child).ok();
                 syscall::close(pipe_write).unwrap();
                 change_sa_restart_flag(false)?;
 
@@ -992,7 +1658,7 @@ impl Executor {
                         Err(e) => break Err(e),
                     }
                 };
-                syscall::close(pipe_read).unwrap();
+                drop(pipe);
                 change_sa_restart_flag(true)?;
 
                 let pgid = Pid::from_raw(-child.as_raw());
@@ -1027,8 +1693,11 @@ impl Executor {
 
                 let mut e = Executor::new().unwrap();
                 let option = ExecOptionBuilder::new().quiet(true).pgid(pid).build();
-                let status = match parse_command_line(command, 0) {
-                    Err(_) => ExitStatus::failure(),
+                let status = match parse_command_line(command.as_ref(), 0) {
+                    Err(e) => {
+                        eprint!("{}", ctx.source.nested(command).syntax_error(&e));
+                        ExitStatus::failure()
+                    }
                     Ok((cmds, _)) => {
                         for cmd in cmds {
                             e.execute_command(&mut ctx.clone(), cmd, Some(option));
@@ -1046,12 +1715,87 @@ impl Executor {
         }
     }
 
-    pub fn close(self) {
+    // Runs the command of <(command) or >(command) with a pipe, and returns the path of
+    // the other end of the pipe, e.g. /dev/fd/10.
+    fn substitute_process<I: AsRef<str>>(
+        ctx: &mut Context,
+        command: I,
+        output: bool,
+    ) -> Result<String, std::io::Error> {
+        let to_io_error = |e: SysCallError| IoError::from_raw_os_error(e.errno() as i32);
+        let (pipe_read, pipe_write) = pipe().map_err(to_io_error)?;
+
+        match syscall::fork() {
+            Err(e) => {
+                syscall::close(pipe_read).ok();
+                syscall::close(pipe_write).ok();
+                eprintln!("{}: {}", e.name(), e.desc());
+                Err(to_io_error(e))
+            }
+            Ok(ForkResult::Parent { child }) => {
+                syscall::setpgid(child, child).ok();
+                let (fd, other) = match output {
+                    false => (pipe_read, pipe_write),
+                    true => (pipe_write, pipe_read),
+                };
+                syscall::close(other).ok();
+                let result = syscall::dup_fd_inheritable(fd, SHELL_FDBASE);
+                syscall::close(fd).ok();
+
+                let fd = result.map_err(to_io_error)?;
+                ctx.process_substitutions.push((fd, child));
+                Ok(format!("/dev/fd/{fd}"))
+            }
+            Ok(ForkResult::Child) => {
+                close_signal_handler();
+
+                let pid = syscall::getpid();
+                syscall::setpgid(pid, pid).ok();
+
+                let (fd, dest) = match output {
+                    false => (pipe_write, 1),
+                    true => (pipe_read, 0),
+                };
+                syscall::dup2(fd, dest).unwrap();
+                syscall::close(pipe_read).ok();
+                syscall::close(pipe_write).ok();
+
+                let mut e = Executor::new().unwrap();
+                let option = ExecOptionBuilder::new().quiet(true).pgid(pid).build();
+                let status = match parse_command_line(command.as_ref(), 0) {
+                    Err(e) => {
+                        eprint!("{}", ctx.source.nested(command).syntax_error(&e));
+                        ExitStatus::failure()
+                    }
+                    Ok((cmds, _)) => {
+                        let mut status = ExitStatus::success();
+                        for cmd in cmds {
+                            status = e.execute_command(ctx, cmd, Some(option));
+                        }
+                        status
+                    }
+                };
+                syscall::exit(status.code());
+
+                unreachable![]
+            }
+        }
+    }
+
+    pub fn close(mut self) {
+        // Waits for the processes of <(...) and >(...), as SIGCHLD must not be sent to
+        // the signal handler after it is closed.
+        for pid in std::mem::take(&mut self.substitutions) {
+            self.handler.wait_for(pid, true);
+        }
         self.handler.close()
     }
 }
 
-fn expand_command_line(ctx: &Context, list: Vec<Vec<Word>>) -> Result<SimpleCommandKind, IoError> {
+fn expand_command_line(
+    ctx: &mut Context,
+    list: Vec<Vec<Word>>,
+) -> Result<SimpleCommandKind, IoError> {
     if list.is_empty() {
         return Ok(SimpleCommandKind::Noop);
     }
@@ -1062,9 +1806,21 @@ fn expand_command_line(ctx: &Context, list: Vec<Vec<Word>>) -> Result<SimpleComm
         match iter.peek() {
             Some(wl) if wl.is_var_name() => {
                 let wl = iter.next().unwrap();
+                if let Some(assignment) = array::parse_assignment(&wl) {
+                    array::assign(ctx, assignment)?;
+                    continue;
+                }
                 let s = wl.to_string(ctx)?;
                 let (k, v) = s.split_once('=').unwrap();
-                env.insert(k.to_string(), v.to_string());
+                // name+=word appends the word to the current value.
+                let (k, v) = match k.strip_suffix('+') {
+                    Some(k) => {
+                        let current = env.get(k).cloned().or_else(|| ctx.get_var(k));
+                        (k, current.unwrap_or_default() + v)
+                    }
+                    None => (k, v.to_string()),
+                };
+                env.insert(k.to_string(), v);
             }
             _ => break,
         }
@@ -1074,10 +1830,37 @@ fn expand_command_line(ctx: &Context, list: Vec<Vec<Word>>) -> Result<SimpleComm
         return Ok(SimpleCommandKind::SetEnv { env });
     }
 
+    // local a=(x y) and declare a=(x y) pass the name to the builtin, and the array is
+    // assigned after it.
+    let declaration = matches!(
+        iter.peek().map(|wl| wl.as_slice()),
+        Some([word]) if word.kind == WordKind::Normal && ["local", "declare"].contains(&word.string.as_str())
+    );
     let mut cmds = vec![];
+    let mut arrays = vec![];
     for wl in iter {
-        let s = wl.to_string(ctx)?;
-        cmds.push(s);
+        match array::parse_assignment(&wl) {
+            Some(Assignment::Array {
+                name,
+                elements,
+                append,
+            }) if declaration => {
+                cmds.push(name.clone());
+                arrays.push(Assignment::Array {
+                    name,
+                    elements,
+                    append,
+                });
+            }
+            _ => {
+                for wl in brace::expand(wl) {
+                    cmds.extend(wl.to_fields(ctx)?);
+                }
+            }
+        }
+    }
+    if cmds.is_empty() {
+        return Ok(SimpleCommandKind::SetEnv { env });
     }
 
     let command = cmds.remove(0);
@@ -1086,14 +1869,100 @@ fn expand_command_line(ctx: &Context, list: Vec<Vec<Word>>) -> Result<SimpleComm
     match &*command {
         "break" => Ok(SimpleCommandKind::Break { args }),
         "continue" | "next" => Ok(SimpleCommandKind::Continue { args }),
+        "return" => Ok(SimpleCommandKind::Return { args }),
         "mruby" => Ok(SimpleCommandKind::MRuby { env, args }),
         _ => match is_builtin_command(&command) {
-            true => Ok(SimpleCommandKind::Builtin { env, command, args }),
+            true => Ok(SimpleCommandKind::Builtin {
+                env,
+                command,
+                args,
+                arrays,
+            }),
             false => Ok(SimpleCommandKind::External { env, command, args }),
         },
     }
 }
 
+// Expands the identifier and the word list of for and select. Prints the error and returns
+// None if it fails.
+fn expand_for_list(
+    ctx: &mut Context,
+    identifier: Vec<Word>,
+    list: Option<Vec<Vec<Word>>>,
+) -> Option<(String, Vec<String>)> {
+    // ToDo: References all items.
+    let identifier = match identifier.first() {
+        Some(word) if word.kind == WordKind::Normal => word.string.clone(),
+        _ => {
+            eprintln!("error: invalid identifier");
+            return None;
+        }
+    };
+
+    // Without "in", the positional parameters are the list.
+    let list = match list {
+        Some(list) => list,
+        None => {
+            let words = ctx.positional_parameters.get(1..).unwrap_or_default();
+            return Some((identifier, words.to_vec()));
+        }
+    };
+
+    let mut words = vec![];
+    for wl in list.into_iter().flat_map(brace::expand) {
+        match wl.to_fields(ctx) {
+            Ok(w) => words.extend(w),
+            Err(e) => {
+                eprintln!("{e}");
+                return None;
+            }
+        }
+    }
+    Some((identifier, words))
+}
+
+// Evaluates an arithmetic expression. Prints the error and returns None if it fails.
+fn evaluate_arithmetic(ctx: &mut Context, expr: &str) -> Option<i64> {
+    match arithmetic::expand_and_evaluate(ctx, expr) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            eprintln!("{e}");
+            None
+        }
+    }
+}
+
+// Matches the string against a shell pattern.
+fn is_match(ctx: &mut Context, pattern: Vec<Word>, s: &str) -> bool {
+    match to_pattern(ctx, pattern) {
+        Ok(pattern) => pattern.matches_with(s, MATCH_OPTIONS),
+        Err(_) => false,
+    }
+}
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: false,
+    require_literal_leading_dot: false,
+};
+
+// Expands the words into a shell pattern. Quoted parts of the pattern match literally,
+// and so does an invalid pattern.
+fn to_pattern(ctx: &mut Context, pattern: Vec<Word>) -> Result<Pattern, IoError> {
+    let mut result = String::new();
+    for word in pattern {
+        match word.kind {
+            WordKind::Quote => result.push_str(&Pattern::escape(&word.string)),
+            _ if word.quoted || word.kind == WordKind::AnsiC => {
+                result.push_str(&Pattern::escape(&word.to_string(ctx)?))
+            }
+            _ => result.push_str(&word.to_string(ctx)?),
+        }
+    }
+
+    Ok(Pattern::new(&result).unwrap_or_else(|_| Pattern::new(&Pattern::escape(&result)).unwrap()))
+}
+
 fn assume_command(command: &str) -> PathBuf {
     let mut buf = PathBuf::new();
     buf.push(command);
@@ -1163,6 +2032,26 @@ fn pipe() -> SysCallResult<(RawFd, RawFd)> {
     Ok((read, write))
 }
 
+// Reads a line from the file descriptor a byte at a time, so that the rest of the input
+// is left for the following commands. Returns None at EOF.
+fn read_line(fd: RawFd) -> Result<Option<String>, IoError> {
+    change_sa_restart_flag(false)?;
+    let mut line = Vec::new();
+    let result = loop {
+        let mut buf = [0u8; 1];
+        match syscall::read(fd, &mut buf) {
+            Ok(0) if line.is_empty() => break Ok(None),
+            Ok(0) => break Ok(Some(line)),
+            Ok(_) if buf[0] == b'\n' => break Ok(Some(line)),
+            Ok(_) => line.push(buf[0]),
+            Err(e) => break Err(IoError::from_raw_os_error(e.errno() as i32)),
+        }
+    };
+    change_sa_restart_flag(true)?;
+
+    result.map(|line| line.map(|line| String::from_utf8_lossy(&line).to_string()))
+}
+
 fn close(fd: RawFd) -> SysCallResult<()> {
     match syscall::close(fd) {
         Ok(_) => Ok(()),
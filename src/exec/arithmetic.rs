@@ -0,0 +1,556 @@
+use super::{array, WordParser};
+use crate::context::Context;
+use rbsh_parser::parse_word;
+use std::{
+    fmt,
+    io::{Error as IoError, ErrorKind},
+};
+
+// Limits the recursion of variables which refer to themselves, e.g. x=x.
+const MAX_RECURSION_LEVEL: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArithmeticError {
+    expr: String,
+    message: &'static str,
+    token: String,
+}
+
+impl ArithmeticError {
+    fn new(expr: &str, message: &'static str, token: &str) -> Self {
+        Self {
+            expr: expr.trim().to_string(),
+            message,
+            token: token.trim().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ArithmeticError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "rbsh: {}: {} (error token is \"{}\")",
+            self.expr, self.message, self.token
+        )
+    }
+}
+
+type Result<T> = std::result::Result<T, ArithmeticError>;
+
+// The name of a variable and the key if it is an element of an array, e.g. a[1].
+type Element<'a> = (&'a str, Option<String>);
+
+// Evaluates an arithmetic expression like `$((expr))`.
+pub fn evaluate(ctx: &mut Context, expr: &str) -> Result<i64> {
+    Evaluator::new(ctx, expr, 0, 0)?.run()
+}
+
+// Evaluates the expression of $((expr)) or ((expr)). Like in double quotes, the parameters
+// and command substitutions in it are expanded before it is evaluated.
+pub fn expand_and_evaluate(ctx: &mut Context, expr: &str) -> std::result::Result<i64, IoError> {
+    let words = parse_word(expr, 0).map_err(|_| {
+        let message = format!("rbsh: {}: bad substitution", expr.trim());
+        IoError::new(ErrorKind::InvalidInput, message)
+    })?;
+    let mut expanded = String::new();
+    for word in words {
+        expanded.push_str(&word.to_string(ctx)?);
+    }
+    evaluate(ctx, &expanded).map_err(|e| IoError::new(ErrorKind::InvalidInput, e.to_string()))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TokenKind {
+    Number(String),
+    Identifier(String),
+    Operator(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    kind: TokenKind,
+    offset: usize,
+}
+
+// Longer operators must come first.
+const OPERATORS: [&str; 39] = [
+    "<<=", ">>=", "**", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "*=", "/=",
+    "%=", "+=", "-=", "&=", "^=", "|=", "+", "-", "*", "/", "%", "<", ">", "&", "^", "|", "!", "~",
+    "?", ":", ",", "=", "(", ")",
+];
+
+const ASSIGNMENT_OPERATORS: [&str; 11] = [
+    "=", "*=", "/=", "%=", "+=", "-=", "<<=", ">>=", "&=", "^=", "|=",
+];
+
+// Binary operators and their precedence. Higher binds tighter.
+fn precedence(op: &str) -> Option<u8> {
+    let prec = match op {
+        "||" => 1,
+        "&&" => 2,
+        "|" => 3,
+        "^" => 4,
+        "&" => 5,
+        "==" | "!=" => 6,
+        "<" | ">" | "<=" | ">=" => 7,
+        "<<" | ">>" => 8,
+        "+" | "-" => 9,
+        "*" | "/" | "%" => 10,
+        "**" => 11,
+        _ => return None,
+    };
+    Some(prec)
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = expr.char_indices().peekable();
+
+    macro_rules! take_while {
+        ($start: expr, $f: expr) => {{
+            let mut end = $start;
+            while let Some((i, c)) = chars.next_if(|(_, c)| $f(c)) {
+                end = i + c.len_utf8();
+            }
+            &expr[$start..end]
+        }};
+    }
+
+    while let Some(&(offset, c)) = chars.peek() {
+        let kind = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            c if c.is_ascii_digit() => TokenKind::Number(
+                take_while!(offset, |c: &char| c.is_ascii_alphanumeric()
+                    || matches!(c, '#' | '@' | '_'))
+                .to_string(),
+            ),
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let name = take_while!(offset, |c: &char| c.is_ascii_alphanumeric() || c == &'_');
+                let mut end = offset + name.len();
+
+                // name[subscript] is an element of an array.
+                if chars.next_if(|(_, c)| c == &'[').is_some() {
+                    let mut depth = 1;
+                    for (i, c) in chars.by_ref() {
+                        match c {
+                            '[' => depth += 1,
+                            ']' => depth -= 1,
+                            _ => (),
+                        }
+                        if depth == 0 {
+                            end = i + 1;
+                            break;
+                        }
+                    }
+                    if depth > 0 {
+                        return Err(ArithmeticError::new(
+                            expr,
+                            "bad array subscript",
+                            &expr[offset..],
+                        ));
+                    }
+                }
+                TokenKind::Identifier(expr[offset..end].to_string())
+            }
+            _ => match OPERATORS.iter().find(|op| expr[offset..].starts_with(*op)) {
+                Some(op) => {
+                    for _ in 0..op.len() {
+                        chars.next();
+                    }
+                    TokenKind::Operator(op)
+                }
+                None => {
+                    return Err(ArithmeticError::new(
+                        expr,
+                        "syntax error: invalid arithmetic operator",
+                        &expr[offset..],
+                    ))
+                }
+            },
+        };
+        tokens.push(Token { kind, offset });
+    }
+
+    Ok(tokens)
+}
+
+// Converts an integer constant: decimal, octal (0755), hex (0xff) or base#number.
+fn parse_number(s: &str) -> std::result::Result<i64, &'static str> {
+    let (base, digits) = match s.split_once('#') {
+        Some((base, digits)) => match base.parse::<u32>() {
+            Ok(base) if (2..=64).contains(&base) => (base, digits),
+            _ => return Err("invalid arithmetic base"),
+        },
+        None if s.starts_with("0x") || s.starts_with("0X") => (16, &s[2..]),
+        None if s.starts_with('0') && s.len() > 1 => (8, &s[1..]),
+        None => (10, s),
+    };
+    if digits.is_empty() {
+        return Err("invalid integer constant");
+    }
+
+    digits.chars().try_fold(0i64, |value, c| {
+        let digit = match c {
+            '0'..='9' => c as u32 - '0' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 10,
+            'A'..='Z' if base > 36 => c as u32 - 'A' as u32 + 36,
+            'A'..='Z' => c as u32 - 'A' as u32 + 10,
+            '@' => 62,
+            '_' => 63,
+            _ => return Err("invalid number"),
+        };
+        match digit < base {
+            true => Ok(value.wrapping_mul(base as i64).wrapping_add(digit as i64)),
+            false => Err("value too great for base"),
+        }
+    })
+}
+
+struct Evaluator<'a> {
+    ctx: &'a mut Context,
+    expr: &'a str,
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+    noeval: usize, // evaluating without side effects, e.g. the right side of `0 && expr`
+}
+
+impl<'a> Evaluator<'a> {
+    fn new(ctx: &'a mut Context, expr: &'a str, depth: usize, noeval: usize) -> Result<Self> {
+        Ok(Self {
+            ctx,
+            expr,
+            tokens: tokenize(expr)?,
+            position: 0,
+            depth,
+            noeval,
+        })
+    }
+
+    fn run(&mut self) -> Result<i64> {
+        if self.depth > MAX_RECURSION_LEVEL {
+            return Err(self.error("expression recursion level exceeded"));
+        }
+        if self.tokens.is_empty() {
+            return Ok(0);
+        }
+
+        let value = self.comma()?;
+        match self.peek() {
+            None => Ok(value),
+            Some(_) => Err(self.error("syntax error in expression")),
+        }
+    }
+
+    fn error(&self, message: &'static str) -> ArithmeticError {
+        self.error_at(self.position, message)
+    }
+
+    fn error_at(&self, position: usize, message: &'static str) -> ArithmeticError {
+        let token = match self.tokens.get(position) {
+            Some(token) => &self.expr[token.offset..],
+            None => "",
+        };
+        ArithmeticError::new(self.expr, message, token)
+    }
+
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.position).map(|t| &t.kind)
+    }
+
+    fn peek_operator(&self) -> Option<&'static str> {
+        match self.peek() {
+            Some(TokenKind::Operator(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn next_if_operator(&mut self, op: &str) -> bool {
+        match self.peek_operator() {
+            Some(o) if o == op => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn need_operator(&mut self, op: &str, message: &'static str) -> Result<()> {
+        match self.next_if_operator(op) {
+            true => Ok(()),
+            false => Err(self.error(message)),
+        }
+    }
+
+    // expr , expr
+    fn comma(&mut self) -> Result<i64> {
+        let mut value = self.assignment()?;
+        while self.next_if_operator(",") {
+            value = self.assignment()?;
+        }
+        Ok(value)
+    }
+
+    // name = expr, name += expr, ...
+    fn assignment(&mut self) -> Result<i64> {
+        let is_assignment = |t: Option<&Token>| matches!(t, Some(Token { kind: TokenKind::Operator(op), .. }) if ASSIGNMENT_OPERATORS.contains(op));
+
+        let name = match (
+            self.peek(),
+            is_assignment(self.tokens.get(self.position + 1)),
+        ) {
+            (Some(TokenKind::Identifier(name)), true) => name.clone(),
+            _ => {
+                let value = self.conditional()?;
+                return match is_assignment(self.tokens.get(self.position)) {
+                    true => Err(self.error("attempted assignment to non-variable")),
+                    false => Ok(value),
+                };
+            }
+        };
+        let element = self.element(&name)?;
+        self.position += 1;
+        let op = self.peek_operator().unwrap();
+        self.position += 1;
+
+        let rhs_position = self.position;
+        let rhs = self.assignment()?;
+        let value = match op {
+            "=" => rhs,
+            op => {
+                let lhs = self.value(&element)?;
+                self.binary_operation(&op[..op.len() - 1], lhs, rhs, rhs_position)?
+            }
+        };
+        self.assign(&element, value);
+        Ok(value)
+    }
+
+    // expr ? expr : expr
+    fn conditional(&mut self) -> Result<i64> {
+        let condition = self.binary(1)?;
+        if !self.next_if_operator("?") {
+            return Ok(condition);
+        }
+
+        let true_case = self.evaluate_if(condition != 0, Self::comma)?;
+        self.need_operator(":", "`:' expected for conditional expression")?;
+        let false_case = self.evaluate_if(condition == 0, Self::conditional)?;
+        Ok(if condition != 0 {
+            true_case
+        } else {
+            false_case
+        })
+    }
+
+    fn binary(&mut self, min_prec: u8) -> Result<i64> {
+        let mut lhs = self.unary()?;
+
+        while let Some(op) = self.peek_operator() {
+            let prec = match precedence(op) {
+                Some(prec) if prec >= min_prec => prec,
+                _ => break,
+            };
+            self.position += 1;
+
+            // `**` is right associative.
+            let next_prec = if op == "**" { prec } else { prec + 1 };
+            let evaluate = match op {
+                "&&" => lhs != 0,
+                "||" => lhs == 0,
+                _ => true,
+            };
+            let rhs_position = self.position;
+            let rhs = self.evaluate_if(evaluate, |s| s.binary(next_prec))?;
+
+            lhs = self.binary_operation(op, lhs, rhs, rhs_position)?;
+        }
+
+        Ok(lhs)
+    }
+
+    // Parses the operand without side effects unless `evaluate` is true.
+    fn evaluate_if(
+        &mut self,
+        evaluate: bool,
+        f: impl FnOnce(&mut Self) -> Result<i64>,
+    ) -> Result<i64> {
+        if !evaluate {
+            self.noeval += 1;
+        }
+        let value = f(self);
+        if !evaluate {
+            self.noeval -= 1;
+        }
+        value
+    }
+
+    // Errors are reported at the right operand.
+    fn binary_operation(&self, op: &str, lhs: i64, rhs: i64, position: usize) -> Result<i64> {
+        let value = match op {
+            "||" => (lhs != 0 || rhs != 0) as i64,
+            "&&" => (lhs != 0 && rhs != 0) as i64,
+            "|" => lhs | rhs,
+            "^" => lhs ^ rhs,
+            "&" => lhs & rhs,
+            "==" => (lhs == rhs) as i64,
+            "!=" => (lhs != rhs) as i64,
+            "<" => (lhs < rhs) as i64,
+            ">" => (lhs > rhs) as i64,
+            "<=" => (lhs <= rhs) as i64,
+            ">=" => (lhs >= rhs) as i64,
+            "<<" => lhs.wrapping_shl(rhs as u32),
+            ">>" => lhs.wrapping_shr(rhs as u32),
+            "+" => lhs.wrapping_add(rhs),
+            "-" => lhs.wrapping_sub(rhs),
+            "*" => lhs.wrapping_mul(rhs),
+            "/" | "%" if rhs == 0 => match self.noeval {
+                0 => return Err(self.error_at(position, "division by 0")),
+                _ => 0,
+            },
+            "/" => lhs.wrapping_div(rhs),
+            "%" => lhs.wrapping_rem(rhs),
+            "**" if rhs < 0 => match self.noeval {
+                0 => return Err(self.error_at(position, "exponent less than 0")),
+                _ => 0,
+            },
+            "**" => lhs.wrapping_pow(rhs.min(u32::MAX as i64) as u32),
+            _ => unreachable![],
+        };
+        Ok(value)
+    }
+
+    // -expr, +expr, !expr, ~expr, ++name, --name
+    fn unary(&mut self) -> Result<i64> {
+        let op = match self.peek_operator() {
+            Some(op @ ("-" | "+" | "!" | "~" | "++" | "--")) => op,
+            _ => return self.postfix(),
+        };
+        self.position += 1;
+
+        if op == "++" || op == "--" {
+            let name = match self.peek() {
+                Some(TokenKind::Identifier(name)) => name.clone(),
+                _ => return Err(self.error("syntax error: operand expected")),
+            };
+            self.position += 1;
+            return self.increment(&name, op).map(|(_, new_value)| new_value);
+        }
+
+        let value = self.unary()?;
+        Ok(match op {
+            "-" => value.wrapping_neg(),
+            "+" => value,
+            "!" => (value == 0) as i64,
+            _ => !value,
+        })
+    }
+
+    // name++, name--
+    fn postfix(&mut self) -> Result<i64> {
+        let name = match self.peek() {
+            Some(TokenKind::Identifier(name)) => name.clone(),
+            _ => return self.primary(),
+        };
+        self.position += 1;
+
+        if let Some(op @ ("++" | "--")) = self.peek_operator() {
+            self.position += 1;
+            return self.increment(&name, op).map(|(old_value, _)| old_value);
+        }
+        self.variable(&name)
+    }
+
+    // Returns the old and new values.
+    fn increment(&mut self, name: &str, op: &str) -> Result<(i64, i64)> {
+        let element = self.element(name)?;
+        let value = self.value(&element)?;
+        let new_value = match op {
+            "++" => value.wrapping_add(1),
+            _ => value.wrapping_sub(1),
+        };
+        self.assign(&element, new_value);
+        Ok((value, new_value))
+    }
+
+    fn primary(&mut self) -> Result<i64> {
+        let token = match self.tokens.get(self.position) {
+            Some(token) => token.kind.clone(),
+            None => return Err(self.error("syntax error: operand expected")),
+        };
+
+        match token {
+            TokenKind::Number(number) => match parse_number(&number) {
+                Ok(value) => {
+                    self.position += 1;
+                    Ok(value)
+                }
+                Err(message) => Err(self.error(message)),
+            },
+            TokenKind::Operator("(") => {
+                self.position += 1;
+                let value = self.comma()?;
+                self.need_operator(")", "missing `)'")?;
+                Ok(value)
+            }
+            _ => Err(self.error("syntax error: operand expected")),
+        }
+    }
+
+    fn variable(&mut self, name: &str) -> Result<i64> {
+        let element = self.element(name)?;
+        self.value(&element)
+    }
+
+    // The value of a variable is evaluated as an expression too.
+    // An unset or empty variable is 0.
+    fn value(&mut self, (name, key): &Element) -> Result<i64> {
+        let value = match key {
+            None => self.ctx.get_var(name),
+            Some(key) => self.ctx.get_value(name).and_then(|value| value.get(key)),
+        };
+        match value {
+            None => Ok(0),
+            Some(value) if value.trim().is_empty() => Ok(0),
+            Some(value) => Evaluator::new(self.ctx, &value, self.depth + 1, self.noeval)?.run(),
+        }
+    }
+
+    fn assign(&mut self, (name, key): &Element, value: i64) {
+        if self.noeval == 0 {
+            match key {
+                None => {
+                    self.ctx.set_var(*name, value.to_string().as_str());
+                }
+                Some(key) => self
+                    .ctx
+                    .set_element(*name, key.as_str(), value.to_string().as_str()),
+            }
+        }
+    }
+
+    // Splits name[subscript] into the name and the key of the element. The subscript is
+    // evaluated here only once, even if the element is read and assigned, e.g. a[i++] += 1.
+    fn element<'b>(&mut self, token: &'b str) -> Result<Element<'b>> {
+        let (name, subscript) = match token.strip_suffix(']').and_then(|s| s.split_once('[')) {
+            Some(_) if self.noeval > 0 => return Ok((token, None)),
+            Some((name, subscript)) => (name, subscript),
+            None => return Ok((token, None)),
+        };
+        let value = self.ctx.get_value(name);
+        match array::key(self.ctx, value.as_ref(), subscript) {
+            Ok(key) => Ok((name, Some(key))),
+            Err(_) => Err(ArithmeticError::new(
+                self.expr,
+                "bad array subscript",
+                token,
+            )),
+        }
+    }
+}
+
+include!("arithmetic_test.rs");
@@ -0,0 +1,148 @@
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::Value;
+    use std::collections::BTreeMap;
+
+    macro_rules! assert_eval {
+        ($ctx: expr, $expr: expr, $value: expr) => {
+            assert_eq!(evaluate(&mut $ctx, $expr), Ok($value))
+        };
+    }
+
+    macro_rules! assert_eval_error {
+        ($ctx: expr, $expr: expr, $message: expr, $token: expr) => {
+            assert_eq!(
+                evaluate(&mut $ctx, $expr),
+                Err(ArithmeticError::new($expr, $message, $token))
+            )
+        };
+    }
+
+    #[test]
+    fn test_operators() {
+        let (mut ctx, _c) = ctx!();
+        assert_eval!(ctx, "", 0);
+        assert_eval!(ctx, "1 + 2 * 3", 7);
+        assert_eval!(ctx, "(1 + 2) * 3", 9);
+        assert_eval!(ctx, "7 / 2, 7 % 2", 1);
+        assert_eval!(ctx, "-7 / 2", -3);
+        assert_eval!(ctx, "2 ** 3 ** 2", 512);
+        assert_eval!(ctx, "-2 ** 2", 4);
+        assert_eval!(ctx, "1 << 4 | 3 & 2 ^ 1", 19);
+        assert_eval!(ctx, "-16 >> 2", -4);
+        assert_eval!(ctx, "!0 + !5 + ~0", 0);
+        assert_eval!(ctx, "1 < 2 && 2 <= 2 && 3 > 2 && 3 >= 4", 0);
+        assert_eval!(ctx, "1 == 2 || 1 != 2", 1);
+        assert_eval!(ctx, "0 ? 1 : 2 ? 3 : 4", 3);
+        assert_eval!(ctx, "9223372036854775807 + 1", i64::MIN);
+    }
+
+    #[test]
+    fn test_numbers() {
+        let (mut ctx, _c) = ctx!();
+        assert_eval!(ctx, "010", 8);
+        assert_eval!(ctx, "0x1F", 31);
+        assert_eval!(ctx, "16#ff", 255);
+        assert_eval!(ctx, "2#1010", 10);
+        assert_eval!(ctx, "36#Z", 35);
+        assert_eval!(ctx, "64#_", 63);
+        assert_eval_error!(ctx, "09", "value too great for base", "09");
+        assert_eval_error!(ctx, "65#1", "invalid arithmetic base", "65#1");
+        assert_eval_error!(ctx, "12abc + 1", "value too great for base", "12abc + 1");
+    }
+
+    #[test]
+    fn test_variables() {
+        let (mut ctx, _c) = ctx!();
+        assert_eval!(ctx, "x", 0);
+        assert_eval!(ctx, "x = 3", 3);
+        assert_eq!(ctx.get_var("x"), Some("3".to_string()));
+        assert_eval!(ctx, "x += 2, x *= 2", 10);
+        assert_eval!(ctx, "x <<= 1", 20);
+        assert_eval!(ctx, "x++ + x", 41);
+        assert_eval!(ctx, "--x", 20);
+        assert_eval!(ctx, "y = z = 1", 1);
+        assert_eq!(ctx.get_var("y"), Some("1".to_string()));
+
+        // the value is evaluated as an expression
+        ctx.set_var("e", "x + 1");
+        assert_eval!(ctx, "e * 2", 42);
+        ctx.set_var("r", "r");
+        assert_eval_error!(ctx, "r", "expression recursion level exceeded", "r");
+    }
+
+    #[test]
+    fn test_array_elements() {
+        let (mut ctx, _c) = ctx!();
+        ctx.set_element("a", "0", "1");
+        ctx.set_element("a", "1", "2");
+        assert_eval!(ctx, "a[1]", 2);
+        assert_eval!(ctx, "a[0] + a[1] * 3", 7);
+        assert_eval!(ctx, "a[-1]", 2);
+        assert_eval!(ctx, "a[1 + 1] = 5", 5);
+        assert_eq!(ctx.get_value("a").unwrap().get("2"), Some("5".to_string()));
+        assert_eval!(ctx, "i = 0, a[i++] += 10, i", 1);
+        assert_eq!(ctx.get_value("a").unwrap().get("0"), Some("11".to_string()));
+        assert_eval!(ctx, "a", 11);
+
+        ctx.set_value("m", Value::Associative(BTreeMap::from([("k".to_string(), "3".to_string())])));
+        assert_eval!(ctx, "m[k] * 2", 6);
+
+        assert_eval_error!(ctx, "a[1", "bad array subscript", "a[1");
+        assert_eval_error!(ctx, "a[-5]", "bad array subscript", "a[-5]");
+    }
+
+    #[test]
+    fn test_expansion() {
+        let (mut ctx, _c) = ctx!();
+        ctx.set_var("x", "20");
+        ctx.set_var("s", "abc");
+        ctx.set_element("a", "1", "5");
+        assert_eq!(expand_and_evaluate(&mut ctx, "$x + ${x}").unwrap(), 40);
+        assert_eq!(expand_and_evaluate(&mut ctx, "${a[1]} + 1").unwrap(), 6);
+        assert_eq!(expand_and_evaluate(&mut ctx, "${#s} + 1").unwrap(), 4);
+        assert_eq!(expand_and_evaluate(&mut ctx, "${u:-2} * 3").unwrap(), 6);
+        assert_eq!(expand_and_evaluate(&mut ctx, "$((x / 2)) + 1").unwrap(), 11);
+
+        // the expanded value is evaluated as a part of the expression
+        ctx.set_var("e", "1 + 2");
+        assert_eq!(expand_and_evaluate(&mut ctx, "$e * 2").unwrap(), 5);
+        assert_eq!(expand_and_evaluate(&mut ctx, "e * 2").unwrap(), 6);
+        ctx.interactive = true;
+        assert_eq!(
+            expand_and_evaluate(&mut ctx, "${u:?} + 1")
+                .unwrap_err()
+                .to_string(),
+            "rbsh: u: parameter null or not set"
+        );
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        let (mut ctx, _c) = ctx!();
+        assert_eval!(ctx, "0 && (x = 1)", 0);
+        assert_eval!(ctx, "1 || x++", 1);
+        assert_eval!(ctx, "1 ? x += 2 : (x = 5)", 2);
+        assert_eval!(ctx, "0 ? x = 1 / 0 : x", 2);
+        assert_eq!(ctx.get_var("x"), Some("2".to_string()));
+    }
+
+    #[test]
+    fn test_errors() {
+        let (mut ctx, _c) = ctx!();
+        assert_eval_error!(ctx, "1 +", "syntax error: operand expected", "");
+        assert_eval_error!(ctx, "1 / 0", "division by 0", "0");
+        assert_eval_error!(ctx, "x /= 0", "division by 0", "0");
+        assert_eval_error!(ctx, "2 ** -1 + 1", "exponent less than 0", "-1 + 1");
+        assert_eval_error!(ctx, "(1 + 2", "missing `)'", "");
+        assert_eval_error!(ctx, "1 ? 2", "`:' expected for conditional expression", "");
+        assert_eval_error!(ctx, "1 = 2", "attempted assignment to non-variable", "= 2");
+        assert_eval_error!(ctx, "1 2", "syntax error in expression", "2");
+        assert_eval_error!(ctx, "1 @ 2", "syntax error: invalid arithmetic operator", "@ 2");
+        assert_eq!(
+            ArithmeticError::new("1 / 0", "division by 0", "0").to_string(),
+            "rbsh: 1 / 0: division by 0 (error token is \"0\")"
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use super::{arithmetic, brace, WordParser};
+use crate::context::{Context, Value};
+use rbsh_parser::{parse_array, Word, WordKind};
+use std::{
+    collections::BTreeMap,
+    io::{Error as IoError, ErrorKind},
+};
+
+type Result<T> = std::result::Result<T, IoError>;
+
+// An assignment to an array variable.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Assignment {
+    Array {
+        name: String,
+        elements: Word,
+        append: bool,
+    }, // name=(word ...) or name+=(word ...)
+    Element {
+        name: String,
+        subscript: Vec<Word>,
+        value: Vec<Word>,
+    }, // name[subscript]=word
+}
+
+// Returns the assignment if the words assign to an array.
+pub fn parse_assignment(words: &[Word]) -> Option<Assignment> {
+    let first = words.first().filter(|word| word.kind == WordKind::Normal)?;
+    let s = &first.string;
+    let len = s.find(|c: char| !c.is_ascii_alphanumeric() && c != '_')?;
+    let name = &s[..len];
+    if !matches!(name.chars().next(), Some(c) if c.is_ascii_alphabetic() || c == '_') {
+        return None;
+    }
+
+    match &s[len..] {
+        op @ ("=" | "+=") if words.len() == 2 && words[1].kind == WordKind::Array => {
+            Some(Assignment::Array {
+                name: name.to_string(),
+                elements: words[1].clone(),
+                append: op == "+=",
+            })
+        }
+        rest if rest.starts_with('[') => {
+            let mut words = words.to_vec();
+            words[0].string = rest[1..].to_string();
+            split_subscript(words).map(|(subscript, value)| Assignment::Element {
+                name: name.to_string(),
+                subscript,
+                value,
+            })
+        }
+        _ => None,
+    }
+}
+
+pub fn assign(ctx: &mut Context, assignment: Assignment) -> Result<()> {
+    match assignment {
+        Assignment::Array {
+            name,
+            elements,
+            append,
+        } => {
+            let elements = parse_array(&elements.string, 0)
+                .map_err(|_| error(format!("{name}=({}): syntax error", elements.string)))?;
+            // name+=(...) adds the elements to the current value, in which a string is
+            // the element 0.
+            let mut value = match ctx.get_value(&name) {
+                Some(Value::String(s)) if append => Value::Indexed(BTreeMap::from([(0, s)])),
+                Some(value) if append => value,
+                _ => match ctx.is_associative(&name) {
+                    true => Value::Associative(BTreeMap::new()),
+                    false => Value::Indexed(BTreeMap::new()),
+                },
+            };
+
+            // An element without a subscript is assigned to the next index.
+            let mut index = match &value {
+                Value::Indexed(map) => map.keys().last().map_or(0, |i| i + 1),
+                _ => 0,
+            };
+            for element in elements {
+                let subscript = match element.first() {
+                    Some(word) if word.kind == WordKind::Normal && word.string.starts_with('[') => {
+                        let mut words = element.clone();
+                        words[0].string.remove(0);
+                        split_subscript(words)
+                    }
+                    _ => None,
+                };
+                let (subscript, words) = match subscript {
+                    Some((subscript, words)) => (Some(subscript), words),
+                    None => (None, element),
+                };
+
+                match subscript {
+                    Some(subscript) => {
+                        let subscript = subscript.to_string(ctx)?;
+                        let k = key(ctx, Some(&value), &subscript)?;
+                        value.set(&k, words.to_string(ctx)?);
+                        index = k.parse::<usize>().map_or(index, |i| i + 1);
+                    }
+                    None if matches!(value, Value::Associative(_)) => {
+                        let word = words.to_string(ctx)?;
+                        return Err(error(format!(
+                            "{name}: {word}: must use subscript when assigning associative array"
+                        )));
+                    }
+                    None => {
+                        for words in brace::expand(words) {
+                            for field in words.to_fields(ctx)? {
+                                value.set(&index.to_string(), field);
+                                index += 1;
+                            }
+                        }
+                    }
+                }
+            }
+            ctx.set_value(&name, value);
+        }
+        Assignment::Element {
+            name,
+            subscript,
+            value,
+        } => {
+            let subscript = subscript.to_string(ctx)?;
+            let k = key(ctx, ctx.get_value(&name).as_ref(), &subscript)?;
+            let value = value.to_string(ctx)?;
+            ctx.set_element(&name, &k, &value);
+        }
+    }
+    Ok(())
+}
+
+// Evaluates the subscript into the key of the array. The subscript of an indexed array
+// is an arithmetic expression, and a negative index counts back from the end.
+pub fn key(ctx: &mut Context, value: Option<&Value>, subscript: &str) -> Result<String> {
+    if let Some(Value::Associative(_)) = value {
+        return Ok(subscript.to_string());
+    }
+
+    let index = arithmetic::evaluate(ctx, subscript)
+        .map_err(|e| IoError::new(ErrorKind::InvalidInput, e.to_string()))?;
+    let index = match index < 0 {
+        true => {
+            let len = match value {
+                Some(Value::Indexed(map)) => map.keys().last().map_or(0, |i| i + 1),
+                Some(_) => 1,
+                None => 0,
+            };
+            len as i64 + index
+        }
+        false => index,
+    };
+    match index < 0 {
+        true => Err(error(format!("{subscript}: bad array subscript"))),
+        false => Ok(index.to_string()),
+    }
+}
+
+// Splits the words after '[' into the subscript and the words after "]=".
+fn split_subscript(words: Vec<Word>) -> Option<(Vec<Word>, Vec<Word>)> {
+    let mut subscript = vec![];
+    let mut depth = 0;
+    let mut iter = words.into_iter();
+    while let Some(word) = iter.next() {
+        if word.kind == WordKind::Normal {
+            for (i, c) in word.string.char_indices() {
+                match c {
+                    '[' => depth += 1,
+                    ']' if depth > 0 => depth -= 1,
+                    ']' if word.string[i + 1..].starts_with('=') => {
+                        let piece =
+                            |s: &str| Word::new(s.to_string(), WordKind::Normal, word.location);
+                        if i > 0 {
+                            subscript.push(piece(&word.string[..i]));
+                        }
+                        let mut value = vec![];
+                        if i + 2 < word.string.len() {
+                            value.push(piece(&word.string[i + 2..]));
+                        }
+                        value.extend(iter);
+                        return Some((subscript, value));
+                    }
+                    ']' => return None,
+                    _ => (),
+                }
+            }
+        }
+        subscript.push(word);
+    }
+    None
+}
+
+fn error(message: String) -> IoError {
+    IoError::new(ErrorKind::InvalidInput, format!("rbsh: {message}"))
+}
+
+include!("array_test.rs");
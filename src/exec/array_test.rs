@@ -0,0 +1,94 @@
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::exec::parameter;
+    use rbsh_parser::parse_command_line;
+
+    // Runs an assignment such as a=(x y) or a[1]=x.
+    fn assign_str(ctx: &mut Context, s: &str) -> Result<()> {
+        let (units, _) = parse_command_line(s, 0).unwrap();
+        let words = match units.into_iter().next().unwrap().kind {
+            rbsh_parser::UnitKind::SimpleCommand { mut command, .. } => command.remove(0),
+            _ => unreachable!(),
+        };
+        assign(ctx, parse_assignment(&words).unwrap())
+    }
+
+    macro_rules! assert_expand {
+        ($ctx: expr, $s: expr, $expected: expr) => {
+            assert_eq!(
+                parameter::expand_fields(&mut $ctx, $s).unwrap(),
+                $expected as Vec<&str>
+            )
+        };
+    }
+
+    #[test]
+    fn test_parse_assignment() {
+        let words = |s: &str| rbsh_parser::parse_word(s, 0).unwrap();
+        assert!(parse_assignment(&words("a[1]=x")).is_some());
+        assert!(parse_assignment(&words("a[$i]=x")).is_some());
+        assert!(parse_assignment(&words("a[b[1]]=x")).is_some());
+        assert!(parse_assignment(&words("a=x")).is_none());
+        assert!(parse_assignment(&words("a[1]x")).is_none());
+        assert!(parse_assignment(&words("1a[1]=x")).is_none());
+        assert!(parse_assignment(&words("a+x")).is_none());
+    }
+
+    #[test]
+    fn test_indexed_array() {
+        let (mut ctx, _c) = ctx!();
+        assign_str(&mut ctx, "a=(x 'y z' [5]=v w)").unwrap();
+        assert_expand!(ctx, "a[@]", vec!["x", "y z", "v", "w"]);
+        assert_expand!(ctx, "a[*]", vec!["x y z v w"]);
+        assert_expand!(ctx, "a", vec!["x"]);
+        assert_expand!(ctx, "a[1]", vec!["y z"]);
+        assert_expand!(ctx, "a[2+3]", vec!["v"]);
+        assert_expand!(ctx, "a[-1]", vec!["w"]);
+        assert_expand!(ctx, "#a[@]", vec!["4"]);
+        assert_expand!(ctx, "#a[1]", vec!["3"]);
+        assert_expand!(ctx, "!a[@]", vec!["0", "1", "5", "6"]);
+        assert_expand!(ctx, "a[@]:1:2", vec!["y z", "v"]);
+        assert_expand!(ctx, "a[@]/[xy]/_", vec!["_", "_ z", "v", "w"]);
+        assert_expand!(ctx, "b[@]", vec![]);
+
+        assign_str(&mut ctx, "a[3]=u").unwrap();
+        assert_expand!(ctx, "a[@]", vec!["x", "y z", "u", "v", "w"]);
+        ctx.set_var("a", "X");
+        assert_expand!(ctx, "a[@]", vec!["X", "y z", "u", "v", "w"]);
+
+        ctx.set_var("s", "t");
+        assign_str(&mut ctx, "s[1]=u").unwrap();
+        assert_expand!(ctx, "s[@]", vec!["t", "u"]);
+
+        assert!(assign_str(&mut ctx, "a[-10]=x").is_err());
+
+        // the elements are added after the last index
+        assign_str(&mut ctx, "a+=(p [10]=q r)").unwrap();
+        assert_expand!(ctx, "!a[@]", vec!["0", "1", "3", "5", "6", "7", "10", "11"]);
+        assign_str(&mut ctx, "s+=(v)").unwrap();
+        assert_expand!(ctx, "s[@]", vec!["t", "u", "v"]);
+        ctx.set_var("t", "x");
+        assign_str(&mut ctx, "t+=(y)").unwrap();
+        assert_expand!(ctx, "t[@]", vec!["x", "y"]);
+        assign_str(&mut ctx, "n+=(y)").unwrap();
+        assert_expand!(ctx, "n[@]", vec!["y"]);
+    }
+
+    #[test]
+    fn test_associative_array() {
+        let (mut ctx, _c) = ctx!();
+        ctx.set_value("m", Value::Associative(BTreeMap::new()));
+        assign_str(&mut ctx, "m[key]=v").unwrap();
+        assign_str(&mut ctx, "m['a b']=w").unwrap();
+        assert_expand!(ctx, "m[key]", vec!["v"]);
+        assert_expand!(ctx, "!m[@]", vec!["a b", "key"]);
+        assert_expand!(ctx, "m[@]", vec!["w", "v"]);
+
+        assign_str(&mut ctx, "m=([x]=1 [y]=2)").unwrap();
+        assert_expand!(ctx, "!m[@]", vec!["x", "y"]);
+        assert!(assign_str(&mut ctx, "m=(1)").is_err());
+        assign_str(&mut ctx, "m+=([z]=3)").unwrap();
+        assert_expand!(ctx, "!m[@]", vec!["x", "y", "z"]);
+    }
+}
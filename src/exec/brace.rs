@@ -0,0 +1,191 @@
+use rbsh_parser::{Location, Word, WordKind};
+
+// A word is split into the characters of the unquoted parts and the other words,
+// which are never expanded by brace expansion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Char(char, Location),
+    Word(Word),
+}
+
+// Expands {a,b,c} and {x..y[..incr]} in the words into multiple words.
+pub fn expand(words: Vec<Word>) -> Vec<Vec<Word>> {
+    let pieces = words
+        .into_iter()
+        .flat_map(|word| match word.kind {
+            WordKind::Normal => word
+                .string
+                .chars()
+                .map(|c| Piece::Char(c, word.location))
+                .collect::<Vec<_>>(),
+            _ => vec![Piece::Word(word)],
+        })
+        .collect::<Vec<_>>();
+
+    expand_pieces(&pieces)
+        .into_iter()
+        .map(to_words)
+        .filter(|words| !words.is_empty())
+        .collect()
+}
+
+fn expand_pieces(pieces: &[Piece]) -> Vec<Vec<Piece>> {
+    for (start, piece) in pieces.iter().enumerate() {
+        if !matches!(piece, Piece::Char('{', _)) {
+            continue;
+        }
+        let end = match find_close_brace(pieces, start) {
+            Some(end) => end,
+            None => continue,
+        };
+        let alternatives = match split_alternatives(&pieces[start + 1..end]) {
+            Some(alternatives) => alternatives,
+            None => match sequence(&pieces[start + 1..end]) {
+                Some(sequence) => sequence,
+                None => continue,
+            },
+        };
+
+        let prefix = &pieces[..start];
+        let suffixes = expand_pieces(&pieces[end + 1..]);
+        return alternatives
+            .iter()
+            .flat_map(|alternative| expand_pieces(alternative))
+            .flat_map(|alternative| {
+                suffixes.iter().map(move |suffix| {
+                    let mut result = prefix.to_vec();
+                    result.extend(alternative.iter().cloned());
+                    result.extend(suffix.iter().cloned());
+                    result
+                })
+            })
+            .collect();
+    }
+
+    vec![pieces.to_vec()]
+}
+
+fn find_close_brace(pieces: &[Piece], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, piece) in pieces.iter().enumerate().skip(start) {
+        match piece {
+            Piece::Char('{', _) => depth += 1,
+            Piece::Char('}', _) if depth == 1 => return Some(i),
+            Piece::Char('}', _) => depth -= 1,
+            _ => (),
+        }
+    }
+    None
+}
+
+// Splits the inside of braces at commas which are not nested.
+// Returns None if there is no comma.
+fn split_alternatives(pieces: &[Piece]) -> Option<Vec<Vec<Piece>>> {
+    let mut result = vec![vec![]];
+    let mut depth = 0;
+    for piece in pieces {
+        match piece {
+            Piece::Char(',', _) if depth == 0 => {
+                result.push(vec![]);
+                continue;
+            }
+            Piece::Char('{', _) => depth += 1,
+            Piece::Char('}', _) => depth -= 1,
+            _ => (),
+        }
+        result.last_mut().unwrap().push(piece.clone());
+    }
+
+    match result.len() {
+        1 => None,
+        _ => Some(result),
+    }
+}
+
+// Expands a sequence expression such as 1..10, 01..10..2 or a..z.
+fn sequence(pieces: &[Piece]) -> Option<Vec<Vec<Piece>>> {
+    let location = match pieces.first() {
+        Some(Piece::Char(_, location)) => *location,
+        _ => return None,
+    };
+    let s = pieces
+        .iter()
+        .map(|piece| match piece {
+            Piece::Char(c, _) => Some(*c),
+            Piece::Word(_) => None,
+        })
+        .collect::<Option<String>>()?;
+
+    let items = s.split("..").collect::<Vec<_>>();
+    let (start, end, step) = match items[..] {
+        [start, end] => (start, end, 1),
+        [start, end, step] => (start, end, step.parse::<i64>().ok()?),
+        _ => return None,
+    };
+    let step = step.unsigned_abs().max(1) as usize;
+
+    let result = match (start.parse::<i64>(), end.parse::<i64>()) {
+        (Ok(x), Ok(y)) => {
+            let is_padded = |s: &str| s.trim_start_matches('-').starts_with('0') && s.len() > 1;
+            let width = match is_padded(start) || is_padded(end) {
+                true => start.len().max(end.len()),
+                false => 0,
+            };
+            range(x, y, step)
+                .map(|n| match n < 0 {
+                    true => format!("-{:0width$}", -n, width = width.saturating_sub(1)),
+                    false => format!("{:0width$}", n, width = width),
+                })
+                .collect::<Vec<_>>()
+        }
+        _ => {
+            let (x, y) = match (single_char(start), single_char(end)) {
+                (Some(x), Some(y)) => (x as i64, y as i64),
+                _ => return None,
+            };
+            range(x, y, step)
+                .filter_map(|n| char::from_u32(n as u32))
+                .map(String::from)
+                .collect::<Vec<_>>()
+        }
+    };
+
+    Some(
+        result
+            .into_iter()
+            .map(|s| s.chars().map(|c| Piece::Char(c, location)).collect())
+            .collect(),
+    )
+}
+
+fn range(x: i64, y: i64, step: usize) -> Box<dyn Iterator<Item = i64>> {
+    match x <= y {
+        true => Box::new((x..=y).step_by(step)),
+        false => Box::new((y..=x).rev().step_by(step)),
+    }
+}
+
+fn single_char(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphabetic() => Some(c),
+        _ => None,
+    }
+}
+
+// Joins adjacent characters back into normal words.
+fn to_words(pieces: Vec<Piece>) -> Vec<Word> {
+    let mut result: Vec<Word> = vec![];
+    for piece in pieces {
+        match piece {
+            Piece::Char(c, location) => match result.last_mut() {
+                Some(word) if word.kind == WordKind::Normal => word.string.push(c),
+                _ => result.push(Word::new(c.to_string(), WordKind::Normal, location)),
+            },
+            Piece::Word(word) => result.push(word),
+        }
+    }
+    result
+}
+
+include!("brace_test.rs");
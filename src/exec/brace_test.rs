@@ -0,0 +1,51 @@
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rbsh_parser::parse_word;
+
+    macro_rules! assert_expand {
+        ($s: expr, $expected: expr) => {
+            let result = expand(parse_word($s, 0).unwrap())
+                .into_iter()
+                .map(|words| words.into_iter().map(|w| w.string).collect::<String>())
+                .collect::<Vec<_>>();
+            assert_eq!(result, $expected)
+        };
+    }
+
+    #[test]
+    fn test_alternatives() {
+        assert_expand!("a{b,c}d", vec!["abd", "acd"]);
+        assert_expand!("{a,b}{1,2}", vec!["a1", "a2", "b1", "b2"]);
+        assert_expand!("a{b,c{d,e}}f", vec!["abf", "acdf", "acef"]);
+        assert_expand!("x{,y}", vec!["x", "xy"]);
+        assert_expand!("{,}", Vec::<String>::new());
+        assert_expand!("{a,$v}", vec!["a", "v"]);
+    }
+
+    #[test]
+    fn test_sequence() {
+        assert_expand!("{1..5}", vec!["1", "2", "3", "4", "5"]);
+        assert_expand!("{3..1}", vec!["3", "2", "1"]);
+        assert_expand!("{1..10..3}", vec!["1", "4", "7", "10"]);
+        assert_expand!("{10..1..-4}", vec!["10", "6", "2"]);
+        assert_expand!("{08..11}", vec!["08", "09", "10", "11"]);
+        assert_expand!("{-2..02}", vec!["-2", "-1", "00", "01", "02"]);
+        assert_expand!("{a..e..2}", vec!["a", "c", "e"]);
+        assert_expand!("{C..A}", vec!["C", "B", "A"]);
+        assert_expand!("a{1..2}{x,y}", vec!["a1x", "a1y", "a2x", "a2y"]);
+    }
+
+    #[test]
+    fn test_literal() {
+        assert_expand!("{}", vec!["{}"]);
+        assert_expand!("{a}", vec!["{a}"]);
+        assert_expand!("{a,b", vec!["{a,b"]);
+        assert_expand!("{1..a}", vec!["{1..a}"]);
+        assert_expand!("{ab..c}", vec!["{ab..c}"]);
+        assert_expand!("'{a,b}'", vec!["{a,b}"]);
+        assert_expand!("{a\\,b}", vec!["{a,b}"]);
+        assert_expand!("{a'{'b,c}", vec!["a{b", "c"]);
+        assert_expand!("{{a,b}", vec!["{a", "{b"]);
+    }
+}
@@ -0,0 +1,144 @@
+use super::{arithmetic, to_pattern, WordParser, MATCH_OPTIONS};
+use crate::context::{Context, Value};
+use nix::unistd::{self, AccessFlags};
+use rbsh_parser::{ConditionalExpression, Word, WordKind};
+use regex::Regex;
+use std::{
+    collections::BTreeMap,
+    fs::{self, Metadata},
+    io::{Error as IoError, ErrorKind},
+    os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt},
+};
+
+type Result<T> = std::result::Result<T, IoError>;
+
+// Evaluates the expression of [[ ... ]]. The words are neither split into fields nor
+// expanded as pathnames.
+pub fn evaluate(ctx: &mut Context, expression: ConditionalExpression) -> Result<bool> {
+    match expression {
+        ConditionalExpression::Word(word) => Ok(!word.to_string(ctx)?.is_empty()),
+        ConditionalExpression::Unary(op, word) => {
+            let s = word.to_string(ctx)?;
+            unary(ctx, &op, &s)
+        }
+        ConditionalExpression::Binary(op, left, right) => binary(ctx, &op, left, right),
+        ConditionalExpression::Not(expr) => evaluate(ctx, *expr).map(|b| !b),
+        ConditionalExpression::And(left, right) => {
+            Ok(evaluate(ctx, *left)? && evaluate(ctx, *right)?)
+        }
+        ConditionalExpression::Or(left, right) => {
+            Ok(evaluate(ctx, *left)? || evaluate(ctx, *right)?)
+        }
+    }
+}
+
+fn unary(ctx: &mut Context, op: &str, s: &str) -> Result<bool> {
+    let metadata = || fs::metadata(s).ok();
+    let mode = |mask: u32| metadata().map_or(false, |m| m.permissions().mode() & mask != 0);
+    let access = |flags: AccessFlags| unistd::access(s, flags).is_ok();
+
+    Ok(match op {
+        "-n" => !s.is_empty(),
+        "-z" => s.is_empty(),
+        "-v" => ctx.get_var(s).is_some() || ctx.get_value(s).is_some(),
+        "-a" | "-e" => metadata().is_some(),
+        "-b" => metadata().map_or(false, |m| m.file_type().is_block_device()),
+        "-c" => metadata().map_or(false, |m| m.file_type().is_char_device()),
+        "-d" => metadata().map_or(false, |m| m.is_dir()),
+        "-f" => metadata().map_or(false, |m| m.is_file()),
+        "-p" => metadata().map_or(false, |m| m.file_type().is_fifo()),
+        "-S" => metadata().map_or(false, |m| m.file_type().is_socket()),
+        "-h" | "-L" => fs::symlink_metadata(s).map_or(false, |m| m.file_type().is_symlink()),
+        "-s" => metadata().map_or(false, |m| m.len() > 0),
+        "-g" => mode(0o2000),
+        "-u" => mode(0o4000),
+        "-k" => mode(0o1000),
+        "-r" => access(AccessFlags::R_OK),
+        "-w" => access(AccessFlags::W_OK),
+        "-x" => access(AccessFlags::X_OK),
+        "-O" => metadata().map_or(false, |m| m.uid() == unistd::geteuid().as_raw()),
+        "-G" => metadata().map_or(false, |m| m.gid() == unistd::getegid().as_raw()),
+        "-N" => metadata().map_or(false, |m| m.mtime() > m.atime()),
+        "-t" => s
+            .parse()
+            .map_or(false, |fd| unistd::isatty(fd).unwrap_or(false)),
+        _ => unreachable![],
+    })
+}
+
+fn binary(ctx: &mut Context, op: &str, left: Vec<Word>, right: Vec<Word>) -> Result<bool> {
+    let s = left.to_string(ctx)?;
+    match op {
+        "=" | "==" => Ok(to_pattern(ctx, right)?.matches_with(&s, MATCH_OPTIONS)),
+        "!=" => Ok(!to_pattern(ctx, right)?.matches_with(&s, MATCH_OPTIONS)),
+        "=~" => regex_match(ctx, &s, right),
+        "<" => Ok(s < right.to_string(ctx)?),
+        ">" => Ok(s > right.to_string(ctx)?),
+        "-nt" | "-ot" | "-ef" => {
+            let t = right.to_string(ctx)?;
+            let (left, right) = (fs::metadata(s).ok(), fs::metadata(t).ok());
+            Ok(compare_files(op, left, right))
+        }
+        _ => {
+            let t = right.to_string(ctx)?;
+            let (left, right) = (integer(ctx, &s)?, integer(ctx, &t)?);
+            Ok(match op {
+                "-eq" => left == right,
+                "-ne" => left != right,
+                "-lt" => left < right,
+                "-le" => left <= right,
+                "-gt" => left > right,
+                "-ge" => left >= right,
+                _ => unreachable![],
+            })
+        }
+    }
+}
+
+// Matches the string against an extended regular expression. Quoted parts of the pattern
+// match literally. The matched string and the substrings are set to BASH_REMATCH.
+fn regex_match(ctx: &mut Context, s: &str, pattern: Vec<Word>) -> Result<bool> {
+    let mut regex = String::new();
+    for word in pattern {
+        let quoted = matches!(word.kind, WordKind::Quote | WordKind::AnsiC) || word.quoted;
+        let part = word.to_string(ctx)?;
+        match quoted {
+            true => regex.push_str(&regex::escape(&part)),
+            false => regex.push_str(&part),
+        }
+    }
+    let regex = Regex::new(&regex).map_err(|_| error(format!("{regex}: invalid regex")))?;
+
+    let mut rematch = BTreeMap::new();
+    if let Some(captures) = regex.captures(s) {
+        for (i, capture) in captures.iter().enumerate() {
+            let capture = capture.map_or("", |m| m.as_str());
+            rematch.insert(i, capture.to_string());
+        }
+    }
+    let matched = !rematch.is_empty();
+    ctx.set_value("BASH_REMATCH", Value::Indexed(rematch));
+    Ok(matched)
+}
+
+fn compare_files(op: &str, left: Option<Metadata>, right: Option<Metadata>) -> bool {
+    match (op, left, right) {
+        ("-nt", Some(l), Some(r)) => l.modified().ok() > r.modified().ok(),
+        ("-nt", Some(_), None) => true,
+        ("-ot", Some(l), Some(r)) => l.modified().ok() < r.modified().ok(),
+        ("-ot", None, Some(_)) => true,
+        ("-ef", Some(l), Some(r)) => l.dev() == r.dev() && l.ino() == r.ino(),
+        _ => false,
+    }
+}
+
+// An operand of the integer comparison is an arithmetic expression.
+fn integer(ctx: &mut Context, s: &str) -> Result<i64> {
+    arithmetic::evaluate(ctx, s).map_err(|e| IoError::new(ErrorKind::InvalidInput, e.to_string()))
+}
+
+fn error(message: String) -> IoError {
+    IoError::new(ErrorKind::InvalidInput, format!("rbsh: {message}"))
+}
+
+include!("conditional_test.rs");
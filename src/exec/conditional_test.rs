@@ -0,0 +1,75 @@
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rbsh_parser::{parse_command_line, UnitKind};
+
+    // Evaluates the command line [[ s ]].
+    fn eval(ctx: &mut Context, s: &str) -> Result<bool> {
+        let (units, _) = parse_command_line(format!("[[ {s} ]]"), 0).unwrap();
+        match units.into_iter().next().unwrap().kind {
+            UnitKind::Conditional { expression, .. } => evaluate(ctx, expression),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_string() {
+        let (mut ctx, _c) = ctx!();
+        ctx.set_var("a", "foo bar");
+        ctx.set_var("p", "f*");
+        assert!(eval(&mut ctx, "$a == foo*").unwrap());
+        assert!(eval(&mut ctx, "$a = $p").unwrap());
+        assert!(!eval(&mut ctx, "$a == \"$p\"").unwrap());
+        assert!(!eval(&mut ctx, "$a == 'foo*'").unwrap());
+        assert!(eval(&mut ctx, "$a != x*").unwrap());
+        assert!(eval(&mut ctx, "abc < abd && b > a").unwrap());
+        assert!(eval(&mut ctx, "-n $a && -z $b").unwrap());
+        assert!(eval(&mut ctx, "$a").unwrap());
+        assert!(!eval(&mut ctx, "$b").unwrap());
+        assert!(eval(&mut ctx, "-v a && ! -v b").unwrap());
+    }
+
+    #[test]
+    fn test_logical() {
+        let (mut ctx, _c) = ctx!();
+        assert!(eval(&mut ctx, "a || b && ''").unwrap());
+        assert!(!eval(&mut ctx, "(a || b) && ''").unwrap());
+        assert!(eval(&mut ctx, "! ( '' )").unwrap());
+        assert!(eval(&mut ctx, "!").unwrap());
+    }
+
+    #[test]
+    fn test_integer() {
+        let (mut ctx, _c) = ctx!();
+        ctx.set_var("i", "3");
+        assert!(eval(&mut ctx, "$i -eq 3 && i -gt 2 && 1+1 -le 2").unwrap());
+        assert!(eval(&mut ctx, "$i -ne 4 && $i -lt 4 && $i -ge 3").unwrap());
+        assert!(eval(&mut ctx, "1 -eq 1+").is_err());
+    }
+
+    #[test]
+    fn test_regex() {
+        let (mut ctx, _c) = ctx!();
+        ctx.set_var("s", "key=value");
+        assert!(eval(&mut ctx, "$s =~ ^([a-z]+)=(x|v.*)$").unwrap());
+        assert_eq!(
+            ctx.get_value("BASH_REMATCH").unwrap().values(),
+            vec!["key=value", "key", "value"]
+        );
+        assert!(!eval(&mut ctx, "$s =~ '^.*$'").unwrap());
+        assert!(ctx.get_value("BASH_REMATCH").unwrap().values().is_empty());
+        assert!(eval(&mut ctx, "a.c =~ a\\.c && ! abc =~ a\\.c").unwrap());
+        assert!(eval(&mut ctx, "a =~ (").is_err());
+    }
+
+    #[test]
+    fn test_file() {
+        let (mut ctx, _c) = ctx!();
+        assert!(eval(&mut ctx, "-e Cargo.toml && -f Cargo.toml && ! -d Cargo.toml").unwrap());
+        assert!(eval(&mut ctx, "-d src && -r src && -x src && -s Cargo.toml").unwrap());
+        assert!(eval(&mut ctx, "! -e no_such_file && ! -L Cargo.toml").unwrap());
+        assert!(eval(&mut ctx, "Cargo.toml -ef ./Cargo.toml").unwrap());
+        assert!(eval(&mut ctx, "Cargo.toml -nt no_such_file").unwrap());
+        assert!(eval(&mut ctx, "no_such_file -ot Cargo.toml").unwrap());
+    }
+}
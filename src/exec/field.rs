@@ -0,0 +1,155 @@
+use super::{
+    parameter::{self, Substitution},
+    tilde, WordParser,
+};
+use crate::{context::Context, utils::Escape};
+use glob::Pattern;
+use rbsh_parser::{Word, WordKind};
+use std::io::Error as IoError;
+
+type Result<T> = std::result::Result<T, IoError>;
+
+const DEFAULT_IFS: &str = " \t\n";
+
+// A field becomes one argument of a command. The pattern is used for pathname expansion,
+// in which the quoted characters are escaped.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Field {
+    pub string: String,
+    pub pattern: String,
+    pub has_pattern: bool,
+    quoted: bool,
+}
+
+impl Field {
+    fn push(&mut self, s: &str) {
+        self.string.push_str(s);
+        self.pattern.push_str(s);
+        self.has_pattern |= s.contains(['*', '?', '[']);
+    }
+
+    fn push_quoted(&mut self, s: &str) {
+        self.string.push_str(s);
+        self.pattern.push_str(&Pattern::escape(s));
+        self.quoted = true;
+    }
+
+    // An empty field is removed unless it is quoted.
+    fn is_empty(&self) -> bool {
+        self.string.is_empty() && !self.quoted
+    }
+}
+
+// Expands the words and splits the results of unquoted expansions into fields with IFS.
+pub fn split(ctx: &mut Context, words: Vec<Word>) -> Result<Vec<Field>> {
+    let mut result = vec![];
+    let mut field = Field::default();
+    split_words(ctx, words, false, &mut result, &mut field)?;
+    if !field.is_empty() {
+        result.push(field);
+    }
+
+    Ok(result)
+}
+
+// The words of ${name:-word} are substituted, in which the unquoted literals are also split
+// but the quoted parts are not.
+fn split_words(
+    ctx: &mut Context,
+    words: Vec<Word>,
+    substituted: bool,
+    result: &mut Vec<Field>,
+    field: &mut Field,
+) -> Result<()> {
+    for word in words {
+        match word.kind {
+            WordKind::Normal if substituted => split_value(ctx, &word.string, result, field),
+            WordKind::Normal => field.push(&word.string),
+            WordKind::Quote => field.push_quoted(&word.string),
+            WordKind::AnsiC => field.push_quoted(&word.string.escape()),
+
+            // "$@" and "${name[@]}" expand to a separate field for each element.
+            _ if word.quoted => {
+                let values = match word.kind {
+                    WordKind::Variable if word.string == "@" => ctx
+                        .positional_parameters
+                        .get(1..)
+                        .unwrap_or_default()
+                        .to_vec(),
+                    WordKind::Parameter => parameter::expand_fields(ctx, &word.string)?,
+                    _ => vec![word.to_string(ctx)?],
+                };
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        result.push(std::mem::take(field));
+                    }
+                    field.push_quoted(value);
+                }
+            }
+            WordKind::Parameter => match parameter::substitute(ctx, &word.string)? {
+                Substitution::Values(values) => split_value(ctx, &values.join(" "), result, field),
+                Substitution::Word(words) => {
+                    let words = tilde::expand(ctx, words);
+                    split_words(ctx, words, true, result, field)?
+                }
+            },
+            _ => {
+                let value = word.to_string(ctx)?;
+                split_value(ctx, &value, result, field)
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn split_value(ctx: &Context, value: &str, result: &mut Vec<Field>, field: &mut Field) {
+    let ifs = ctx
+        .get_var("IFS")
+        .unwrap_or_else(|| DEFAULT_IFS.to_string());
+    let (parts, delimiters) = split_ifs(value, &ifs);
+    for (i, part) in parts.into_iter().enumerate() {
+        if i > 0 {
+            // A non-whitespace delimiter makes an empty field.
+            let field = std::mem::take(field);
+            if delimiters[i - 1] || !field.is_empty() {
+                result.push(field);
+            }
+        }
+        field.push(&part);
+    }
+}
+
+// Splits the value at the IFS delimiters. A delimiter is a sequence of IFS whitespace
+// with at most one other IFS character. Also returns whether each delimiter has
+// the other character.
+fn split_ifs(value: &str, ifs: &str) -> (Vec<String>, Vec<bool>) {
+    let is_whitespace = |c: char| ifs.contains(c) && DEFAULT_IFS.contains(c);
+    let is_other = |c: char| ifs.contains(c) && !DEFAULT_IFS.contains(c);
+
+    let mut parts = vec![String::new()];
+    let mut delimiters = vec![];
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        if !is_whitespace(c) && !is_other(c) {
+            parts.last_mut().unwrap().push(c);
+            continue;
+        }
+
+        let mut other = is_other(c);
+        while let Some(&c) = chars.peek() {
+            match c {
+                _ if is_whitespace(c) => (),
+                _ if is_other(c) && !other => other = true,
+                _ => break,
+            }
+            chars.next();
+        }
+        parts.push(String::new());
+        delimiters.push(other);
+    }
+
+    (parts, delimiters)
+}
+
+include!("field_test.rs");
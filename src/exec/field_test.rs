@@ -0,0 +1,74 @@
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rbsh_parser::parse_word;
+
+    macro_rules! assert_split {
+        ($ctx: expr, $s: expr, $expected: expr) => {
+            let result = split(&mut $ctx, parse_word($s, 0).unwrap())
+                .unwrap()
+                .into_iter()
+                .map(|field| field.string)
+                .collect::<Vec<_>>();
+            assert_eq!(result, $expected as Vec<&str>)
+        };
+    }
+
+    #[test]
+    fn test_split() {
+        let (mut ctx, _c) = ctx!("x" => " a  b ", "e" => "");
+        assert_split!(ctx, "$x", vec!["a", "b"]);
+        assert_split!(ctx, "1${x}2", vec!["1", "a", "b", "2"]);
+        assert_split!(ctx, "\"$x\"", vec![" a  b "]);
+        assert_split!(ctx, "'$x'", vec!["$x"]);
+        assert_split!(ctx, "$e", vec![]);
+        assert_split!(ctx, "\"$e\"", vec![""]);
+        assert_split!(ctx, "''$e", vec![""]);
+
+        // only the unquoted parts of the substituted word are split
+        assert_split!(ctx, "${u:-\"a b\"}", vec!["a b"]);
+        assert_split!(ctx, "${u:-a b}", vec!["a", "b"]);
+        assert_split!(ctx, "${u:-\"a b\"$x}", vec!["a b", "a", "b"]);
+        assert_split!(ctx, "${x:+'c d'}", vec!["c d"]);
+        assert_split!(ctx, "a$e", vec!["a"]);
+
+        ctx.set_var("IFS", ":");
+        ctx.set_var("x", "a::b:");
+        assert_split!(ctx, "$x", vec!["a", "", "b"]);
+        ctx.set_var("x", ":a b");
+        assert_split!(ctx, "$x", vec!["", "a b"]);
+
+        ctx.set_var("IFS", " :");
+        ctx.set_var("x", " a : b  c ");
+        assert_split!(ctx, "$x", vec!["a", "b", "c"]);
+
+        ctx.set_var("IFS", "");
+        ctx.set_var("x", " a b ");
+        assert_split!(ctx, "$x", vec![" a b "]);
+    }
+
+    #[test]
+    fn test_positional_parameters() {
+        let (mut ctx, _c) = ctx!();
+        ctx.positional_parameters = vec!["rbsh", "a b", "c"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        assert_split!(ctx, "\"$@\"", vec!["a b", "c"]);
+        assert_split!(ctx, "\"1$@2\"", vec!["1a b", "c2"]);
+        assert_split!(ctx, "$@", vec!["a", "b", "c"]);
+        assert_split!(ctx, "\"$*\"", vec!["a b c"]);
+
+        ctx.positional_parameters.truncate(1);
+        assert_split!(ctx, "\"$@\"", vec![]);
+    }
+
+    #[test]
+    fn test_pattern() {
+        let (mut ctx, _c) = ctx!("p" => "*.rs");
+        let field = split(&mut ctx, parse_word("$p", 0).unwrap()).unwrap().remove(0);
+        assert_eq!((field.pattern.as_str(), field.has_pattern), ("*.rs", true));
+        let field = split(&mut ctx, parse_word("\"$p\"", 0).unwrap()).unwrap().remove(0);
+        assert_eq!((field.pattern.as_str(), field.has_pattern), ("[*].rs", false));
+    }
+}
@@ -0,0 +1,535 @@
+use super::{arithmetic, array, to_pattern, WordParser, MATCH_OPTIONS};
+use crate::{context::Context, status::ExitStatus, syscall};
+use glob::Pattern;
+use rbsh_parser::{parse_word, Word};
+use std::io::{Error as IoError, ErrorKind};
+
+type Result<T> = std::result::Result<T, IoError>;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Operator<'a> {
+    None,   // ${name}
+    Length, // ${#name}
+    Names,  // ${!prefix*}
+    Keys,   // ${!name[@]}
+    Default {
+        colon: bool,
+        word: &'a str,
+    }, // ${name:-word}
+    Assign {
+        colon: bool,
+        word: &'a str,
+    }, // ${name:=word}
+    Error {
+        colon: bool,
+        word: &'a str,
+    }, // ${name:?word}
+    Alternative {
+        colon: bool,
+        word: &'a str,
+    }, // ${name:+word}
+    RemovePrefix {
+        longest: bool,
+        pattern: &'a str,
+    }, // ${name#pattern}
+    RemoveSuffix {
+        longest: bool,
+        pattern: &'a str,
+    }, // ${name%pattern}
+    Replace {
+        mode: Replace,
+        pattern: &'a str,
+        string: &'a str,
+    }, // ${name/pattern/string}
+    Substring {
+        offset: &'a str,
+        length: Option<&'a str>,
+    }, // ${name:offset:length}
+    Upper {
+        all: bool,
+        pattern: &'a str,
+    }, // ${name^pattern}
+    Lower {
+        all: bool,
+        pattern: &'a str,
+    }, // ${name,pattern}
+}
+
+pub enum Substitution {
+    Values(Vec<String>),
+    Word(Vec<Word>), // the word of ${name:-word} or ${name:+word}
+}
+
+// A parameter name with an optional subscript of an array, e.g. ${name[subscript]}.
+#[derive(Debug, PartialEq, Eq)]
+struct Parameter<'a> {
+    name: &'a str,
+    subscript: Option<&'a str>,
+}
+
+impl Parameter<'_> {
+    // Whether all elements are expanded with [@] or [*].
+    fn is_all(&self) -> bool {
+        matches!(self.subscript, Some("@" | "*"))
+    }
+
+    fn values(&self, ctx: &mut Context) -> Result<Option<Vec<String>>> {
+        match self.subscript {
+            None => Ok(ctx.get_var(self.name).map(|value| vec![value])),
+            Some(_) if self.is_all() => Ok(ctx.get_value(self.name).map(|value| value.values())),
+            Some(subscript) => {
+                let subscript = expand_word(ctx, subscript)?;
+                let value = ctx.get_value(self.name);
+                let key = array::key(ctx, value.as_ref(), &subscript)?;
+                Ok(value
+                    .and_then(|value| value.get(&key))
+                    .map(|value| vec![value]))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Replace {
+    First,  // ${name/pattern/string}
+    All,    // ${name//pattern/string}
+    Prefix, // ${name/#pattern/string}
+    Suffix, // ${name/%pattern/string}
+}
+
+// Expands the inside of ${...}.
+pub fn expand(ctx: &mut Context, s: &str) -> Result<String> {
+    expand_fields(ctx, s).map(|values| values.join(" "))
+}
+
+// Expands the inside of ${...} into the values. Each element of an array expanded with [@]
+// is a separate value, and the others are a single value.
+pub fn expand_fields(ctx: &mut Context, s: &str) -> Result<Vec<String>> {
+    match substitute(ctx, s)? {
+        Substitution::Values(values) => Ok(values),
+        Substitution::Word(words) => Ok(vec![words.to_string(ctx)?]),
+    }
+}
+
+// Like expand_fields, but the word of ${name:-word} or ${name:+word} is returned without
+// being expanded when it is used, so that its quoted parts are not split into fields.
+pub fn substitute(ctx: &mut Context, s: &str) -> Result<Substitution> {
+    let (parameter, op) = parse(s).ok_or_else(|| error(format!("${{{s}}}: bad substitution")))?;
+    let name = parameter.name;
+    let values = parameter.values(ctx)?;
+    let value = values.as_ref().map(|values| values.join(" "));
+
+    // Whether the default value is used.
+    let is_null = |colon: bool| match &value {
+        None => true,
+        Some(v) => colon && v.is_empty(),
+    };
+
+    // The operators which change a string are applied to each element.
+    let elements = values.clone().unwrap_or_else(|| match parameter.is_all() {
+        true => vec![],
+        false => vec![String::new()],
+    });
+
+    let mut result = match op {
+        Operator::None => elements,
+        Operator::Length => {
+            let len = match parameter.is_all() {
+                true => elements.len(),
+                false => value.unwrap_or_default().chars().count(),
+            };
+            vec![len.to_string()]
+        }
+        Operator::Names => vec![ctx
+            .var_names()
+            .into_iter()
+            .filter(|n| n.starts_with(name))
+            .collect::<Vec<_>>()
+            .join(" ")],
+        Operator::Keys => ctx
+            .get_value(name)
+            .map(|value| value.keys())
+            .unwrap_or_default(),
+        Operator::Default { colon, word } => match is_null(colon) {
+            true => return Ok(Substitution::Word(parse_words(word)?)),
+            false => elements,
+        },
+        Operator::Assign { colon, word } => match is_null(colon) {
+            true if !is_identifier(name) || parameter.subscript.is_some() => {
+                return Err(error(format!("${name}: cannot assign in this way")))
+            }
+            true => {
+                let word = expand_word(ctx, word)?;
+                ctx.set_var(name, &*word);
+                vec![word]
+            }
+            false => elements,
+        },
+        Operator::Error { colon, word } => match is_null(colon) {
+            true => {
+                let message = match (word.is_empty(), colon) {
+                    (false, _) => expand_word(ctx, word)?,
+                    (true, true) => "parameter null or not set".to_string(),
+                    (true, false) => "parameter not set".to_string(),
+                };
+                let e = error(format!("{name}: {message}"));
+                // Like bash, a shell which is not interactive exits.
+                if !ctx.interactive {
+                    eprintln!("{e}");
+                    syscall::exit(ExitStatus::failure().code());
+                }
+                return Err(e);
+            }
+            false => elements,
+        },
+        Operator::Alternative { colon, word } => match is_null(colon) {
+            true => vec![String::new()],
+            false => return Ok(Substitution::Word(parse_words(word)?)),
+        },
+        Operator::RemovePrefix { longest, pattern } => {
+            let pattern = expand_pattern(ctx, pattern)?;
+            elements
+                .into_iter()
+                .map(
+                    |value| match find_match(&value, &pattern, true, false, longest) {
+                        Some((_, end)) => value[end..].to_string(),
+                        None => value,
+                    },
+                )
+                .collect()
+        }
+        Operator::RemoveSuffix { longest, pattern } => {
+            let pattern = expand_pattern(ctx, pattern)?;
+            elements
+                .into_iter()
+                .map(
+                    |value| match find_match(&value, &pattern, false, true, longest) {
+                        Some((start, _)) => value[..start].to_string(),
+                        None => value,
+                    },
+                )
+                .collect()
+        }
+        Operator::Replace {
+            mode,
+            pattern,
+            string,
+        } => {
+            let pattern = expand_pattern(ctx, pattern)?;
+            let string = expand_word(ctx, string)?;
+            elements
+                .iter()
+                .map(|value| replace(value, &pattern, &string, mode))
+                .collect()
+        }
+        // ${name[@]:offset:length} is a slice of the array.
+        Operator::Substring { offset, length } if parameter.is_all() => {
+            slice(ctx, &elements, offset, length)?
+        }
+        Operator::Substring { offset, length } => {
+            let mut result = vec![];
+            for value in elements {
+                result.push(substring(ctx, &value, offset, length)?);
+            }
+            result
+        }
+        Operator::Upper { all, pattern } | Operator::Lower { all, pattern } => {
+            let upper = matches!(op, Operator::Upper { .. });
+            let pattern = match pattern.is_empty() {
+                true => None,
+                false => Some(expand_pattern(ctx, pattern)?),
+            };
+            elements
+                .iter()
+                .map(|value| convert_case(value, pattern.clone(), upper, all))
+                .collect()
+        }
+    };
+
+    // The elements expanded with [*] are joined into a single value.
+    if parameter.subscript == Some("*") {
+        result = vec![result.join(" ")];
+    }
+    Ok(Substitution::Values(result))
+}
+
+fn error(message: String) -> IoError {
+    IoError::new(ErrorKind::InvalidInput, format!("rbsh: {message}"))
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Splits ${...} into the parameter and the operator. Returns None for a bad substitution.
+fn parse(s: &str) -> Option<(Parameter<'_>, Operator<'_>)> {
+    if let Some(name) = s.strip_prefix('#') {
+        if !name.is_empty() {
+            return parse_name(name)
+                .filter(|(_, rest)| rest.is_empty())
+                .map(|(parameter, _)| (parameter, Operator::Length));
+        }
+    }
+
+    if let Some(prefix) = s.strip_prefix('!') {
+        if let Some((parameter, "")) = parse_name(prefix).filter(|(p, _)| p.is_all()) {
+            return Some((parameter, Operator::Keys));
+        }
+        return prefix
+            .strip_suffix(['*', '@'])
+            .filter(|prefix| is_identifier(prefix))
+            .map(|name| {
+                let parameter = Parameter {
+                    name,
+                    subscript: None,
+                };
+                (parameter, Operator::Names)
+            });
+    }
+
+    let (name, rest) = parse_name(s)?;
+    let mut chars = rest.chars();
+    let (colon, c) = match chars.next() {
+        None => return Some((name, Operator::None)),
+        Some(':') => match chars.next() {
+            Some(c @ ('-' | '=' | '?' | '+')) => (true, c),
+            _ => {
+                let (offset, length) = match rest[1..].split_once(':') {
+                    Some((offset, length)) => (offset, Some(length)),
+                    None => (&rest[1..], None),
+                };
+                return Some((name, Operator::Substring { offset, length }));
+            }
+        },
+        Some(c) => (false, c),
+    };
+
+    let word = &rest[c.len_utf8() + colon as usize..];
+    let double = |c| word.starts_with(c);
+    let op = match c {
+        '-' => Operator::Default { colon, word },
+        '=' => Operator::Assign { colon, word },
+        '?' => Operator::Error { colon, word },
+        '+' => Operator::Alternative { colon, word },
+        '#' | '%' | '^' | ',' => {
+            let all = double(c);
+            let pattern = if all { &word[1..] } else { word };
+            match c {
+                '#' => Operator::RemovePrefix {
+                    longest: all,
+                    pattern,
+                },
+                '%' => Operator::RemoveSuffix {
+                    longest: all,
+                    pattern,
+                },
+                '^' => Operator::Upper { all, pattern },
+                _ => Operator::Lower { all, pattern },
+            }
+        }
+        '/' => {
+            let (mode, word) = match word.chars().next() {
+                Some('/') => (Replace::All, &word[1..]),
+                Some('#') => (Replace::Prefix, &word[1..]),
+                Some('%') => (Replace::Suffix, &word[1..]),
+                _ => (Replace::First, word),
+            };
+            let (pattern, string) = split_replacement(word);
+            Operator::Replace {
+                mode,
+                pattern,
+                string,
+            }
+        }
+        _ => return None,
+    };
+
+    Some((name, op))
+}
+
+// Returns the parameter and the rest. The name is a variable name, a positional parameter
+// or a special parameter. A variable name may have a subscript.
+fn parse_name(s: &str) -> Option<(Parameter<'_>, &str)> {
+    let c = s.chars().next()?;
+    let len = match c {
+        '0'..='9' => s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()),
+        c if c.is_ascii_alphabetic() || c == '_' => s
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(s.len()),
+        '?' | '$' | '#' | '@' | '*' | '!' | '-' => 1,
+        _ => return None,
+    };
+    let (name, rest) = s.split_at(len);
+    if !is_identifier(name) || !rest.starts_with('[') {
+        let subscript = None;
+        return Some((Parameter { name, subscript }, rest));
+    }
+
+    // The subscript is up to the matching ']'.
+    let mut depth = 0;
+    for (i, c) in rest.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' if depth > 1 => depth -= 1,
+            ']' => {
+                let subscript = Some(&rest[1..i]);
+                return Some((Parameter { name, subscript }, &rest[i + 1..]));
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+// Splits "pattern/string" at the first slash which is not escaped.
+fn split_replacement(s: &str) -> (&str, &str) {
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '/' if !escaped => return (&s[..i], &s[i + 1..]),
+            _ => escaped = false,
+        }
+    }
+    (s, "")
+}
+
+fn expand_word(ctx: &mut Context, word: &str) -> Result<String> {
+    parse_words(word)?.to_string(ctx)
+}
+
+fn expand_pattern(ctx: &mut Context, pattern: &str) -> Result<Pattern> {
+    let words = parse_words(pattern)?;
+    to_pattern(ctx, words)
+}
+
+fn parse_words(word: &str) -> Result<Vec<Word>> {
+    parse_word(word, 0).map_err(|_| error(format!("{word}: bad substitution")))
+}
+
+// Finds the part of the value which matches the pattern and returns its byte range.
+// An unanchored match is the longest one at the leftmost position.
+fn find_match(
+    value: &str,
+    pattern: &Pattern,
+    anchor_start: bool,
+    anchor_end: bool,
+    longest: bool,
+) -> Option<(usize, usize)> {
+    let mut boundaries = value.char_indices().map(|(i, _)| i).collect::<Vec<_>>();
+    boundaries.push(value.len());
+    let is_match =
+        |start: usize, end: usize| pattern.matches_with(&value[start..end], MATCH_OPTIONS);
+
+    match (anchor_start, anchor_end) {
+        (true, _) => {
+            if longest {
+                boundaries.reverse();
+            }
+            boundaries
+                .into_iter()
+                .find(|end| is_match(0, *end))
+                .map(|end| (0, end))
+        }
+        (false, true) => {
+            if !longest {
+                boundaries.reverse();
+            }
+            boundaries
+                .into_iter()
+                .find(|start| is_match(*start, value.len()))
+                .map(|start| (start, value.len()))
+        }
+        (false, false) => boundaries.iter().find_map(|start| {
+            boundaries
+                .iter()
+                .rev()
+                .take_while(|end| *end >= start)
+                .find(|end| is_match(*start, **end))
+                .map(|end| (*start, *end))
+        }),
+    }
+}
+
+fn replace(value: &str, pattern: &Pattern, string: &str, mode: Replace) -> String {
+    let find = |s: &str| match mode {
+        Replace::Prefix => find_match(s, pattern, true, false, true),
+        Replace::Suffix => find_match(s, pattern, false, true, true),
+        Replace::First | Replace::All => {
+            find_match(s, pattern, false, false, true).filter(|(start, end)| start < end)
+        }
+    };
+
+    let mut result = String::new();
+    let mut rest = value;
+    while let Some((start, end)) = find(rest) {
+        result.push_str(&rest[..start]);
+        result.push_str(string);
+        rest = &rest[end..];
+        if mode != Replace::All {
+            break;
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+// ${name:offset:length}. A negative offset counts from the end of the value,
+// and so does a negative length.
+fn substring(ctx: &mut Context, value: &str, offset: &str, length: Option<&str>) -> Result<String> {
+    let chars = value.chars().collect::<Vec<_>>();
+    Ok(slice(ctx, &chars, offset, length)?.into_iter().collect())
+}
+
+// Returns the items from the offset. A negative offset counts back from the end,
+// and a negative length is the end counted back from the end.
+fn slice<T: Clone>(
+    ctx: &mut Context,
+    items: &[T],
+    offset: &str,
+    length: Option<&str>,
+) -> Result<Vec<T>> {
+    let len = items.len() as i64;
+    let offset = arithmetic::expand_and_evaluate(ctx, offset)?;
+    let start = if offset < 0 { len + offset } else { offset };
+    if start < 0 || start > len {
+        return Ok(vec![]);
+    }
+
+    let end = match length {
+        None => len,
+        Some(length) => match arithmetic::expand_and_evaluate(ctx, length)? {
+            l if l < 0 && len + l < start => {
+                return Err(error(format!("{l}: substring expression < 0")))
+            }
+            l if l < 0 => len + l,
+            l => (start + l).min(len),
+        },
+    };
+
+    Ok(items[start as usize..end as usize].to_vec())
+}
+
+// ${name^pattern}, ${name^^pattern}, ${name,pattern} and ${name,,pattern}.
+// Only the characters which match the pattern are converted.
+fn convert_case(value: &str, pattern: Option<Pattern>, upper: bool, all: bool) -> String {
+    value
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let convert = (all || i == 0)
+                && pattern
+                    .as_ref()
+                    .map_or(true, |p| p.matches_with(&c.to_string(), MATCH_OPTIONS));
+            match (convert, upper) {
+                (false, _) => c.to_string(),
+                (true, true) => c.to_uppercase().collect(),
+                (true, false) => c.to_lowercase().collect(),
+            }
+        })
+        .collect()
+}
+
+include!("parameter_test.rs");
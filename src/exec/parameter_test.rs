@@ -0,0 +1,151 @@
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::syscall;
+    use mockall::predicate::eq;
+    use std::collections::HashMap;
+
+    macro_rules! assert_expand {
+        ($ctx: expr, $s: expr, $value: expr) => {
+            assert_eq!(expand(&mut $ctx, $s).unwrap(), $value)
+        };
+    }
+
+    macro_rules! assert_expand_error {
+        ($ctx: expr, $s: expr, $message: expr) => {
+            assert_eq!(expand(&mut $ctx, $s).unwrap_err().to_string(), $message)
+        };
+    }
+
+    #[test]
+    fn test_default_value() {
+        let (mut ctx, _c) = ctx!();
+        ctx.set_var("empty", "");
+        ctx.set_var("v", "value");
+
+        assert_expand!(ctx, "v", "value");
+        assert_expand!(ctx, "unset:-default", "default");
+        assert_expand!(ctx, "empty:-default", "default");
+        assert_expand!(ctx, "empty-default", "");
+        assert_expand!(ctx, "v:-default", "value");
+        assert_expand!(ctx, "unset:-$v", "value");
+
+        assert_expand!(ctx, "unset:+alt", "");
+        assert_expand!(ctx, "empty+alt", "alt");
+        assert_expand!(ctx, "v:+alt", "alt");
+
+        assert_expand!(ctx, "x:=assigned", "assigned");
+        assert_eq!(ctx.get_var("x"), Some("assigned".to_string()));
+        assert_expand!(ctx, "x:=other", "assigned");
+        assert_expand_error!(ctx, "1:=x", "rbsh: $1: cannot assign in this way");
+
+        // an interactive shell does not exit
+        ctx.interactive = true;
+        assert_expand!(ctx, "v:?message", "value");
+        assert_expand_error!(ctx, "unset:?message", "rbsh: unset: message");
+        assert_expand_error!(ctx, "empty:?", "rbsh: empty: parameter null or not set");
+        assert_expand_error!(ctx, "unset?", "rbsh: unset: parameter not set");
+    }
+
+    #[test]
+    fn test_error_exit() {
+        let (mut ctx, _c) = ctx!();
+        let exit_context = syscall::exit_context();
+        exit_context
+            .expect()
+            .with(eq(1))
+            .times(1)
+            .returning(ExitStatus::new);
+        assert_expand_error!(ctx, "unset:?message", "rbsh: unset: message");
+    }
+
+    #[test]
+    fn test_length() {
+        let (mut ctx, _c) = ctx!();
+        ctx.set_var("v", "あいう");
+        assert_expand!(ctx, "#v", "3");
+        assert_expand!(ctx, "#unset", "0");
+    }
+
+    #[test]
+    fn test_remove_pattern() {
+        let (mut ctx, _c) = ctx!();
+        ctx.set_var("f", "dir/file.tar.gz");
+
+        assert_expand!(ctx, "f#*/", "file.tar.gz");
+        assert_expand!(ctx, "f#*.", "tar.gz");
+        assert_expand!(ctx, "f##*.", "gz");
+        assert_expand!(ctx, "f%.*", "dir/file.tar");
+        assert_expand!(ctx, "f%%.*", "dir/file");
+        assert_expand!(ctx, "f%.tar.gz", "dir/file");
+        assert_expand!(ctx, "f%x", "dir/file.tar.gz");
+        assert_expand!(ctx, "f#'dir/'", "file.tar.gz");
+        assert_expand!(ctx, "f%'.*'", "dir/file.tar.gz");
+    }
+
+    #[test]
+    fn test_replace() {
+        let (mut ctx, _c) = ctx!();
+        ctx.set_var("v", "banana");
+
+        assert_expand!(ctx, "v/a/o", "bonana");
+        assert_expand!(ctx, "v//a/o", "bonono");
+        assert_expand!(ctx, "v/#b/B", "Banana");
+        assert_expand!(ctx, "v/%a/A", "bananA");
+        assert_expand!(ctx, "v/#a/A", "banana");
+        assert_expand!(ctx, "v/n*/", "ba");
+        assert_expand!(ctx, "v//a", "bnn");
+        assert_expand!(ctx, "v/a\\/x/y", "banana");
+    }
+
+    #[test]
+    fn test_substring() {
+        let (mut ctx, _c) = ctx!();
+        ctx.set_var("v", "abcdef");
+
+        assert_expand!(ctx, "v:2", "cdef");
+        assert_expand!(ctx, "v:1:3", "bcd");
+        assert_expand!(ctx, "v:1+1:1*2", "cd");
+        assert_expand!(ctx, "v: -2", "ef");
+        assert_expand!(ctx, "v:1:-2", "bcd");
+        assert_expand!(ctx, "v:10", "");
+        assert_expand!(ctx, "v:4:10", "ef");
+        assert_expand_error!(ctx, "v:4:-3", "rbsh: -3: substring expression < 0");
+    }
+
+    #[test]
+    fn test_convert_case() {
+        let (mut ctx, _c) = ctx!();
+        ctx.set_var("v", "hello World");
+
+        assert_expand!(ctx, "v^", "Hello World");
+        assert_expand!(ctx, "v^^", "HELLO WORLD");
+        assert_expand!(ctx, "v^^[lo]", "heLLO WOrLd");
+        assert_expand!(ctx, "v,,", "hello world");
+        assert_expand!(ctx, "v,", "hello World");
+        assert_expand!(ctx, "v,,W", "hello world");
+    }
+
+    #[test]
+    fn test_names() {
+        let (mut ctx, _c) = ctx!();
+        let env_vars_context = syscall::env_vars_context();
+        env_vars_context
+            .expect()
+            .returning(|| HashMap::from([("PATH".to_string(), String::new())]));
+        ctx.set_var("prefix_b", "");
+        ctx.set_var("prefix_a", "");
+        ctx.set_var("other", "");
+
+        assert_expand!(ctx, "!prefix_*", "prefix_a prefix_b");
+        assert_expand!(ctx, "!P@", "PATH");
+    }
+
+    #[test]
+    fn test_bad_substitution() {
+        let (mut ctx, _c) = ctx!();
+        assert_expand_error!(ctx, "", "rbsh: ${}: bad substitution");
+        assert_expand_error!(ctx, "v@", "rbsh: ${v@}: bad substitution");
+        assert_expand_error!(ctx, "!v", "rbsh: ${!v}: bad substitution");
+    }
+}
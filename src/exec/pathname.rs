@@ -0,0 +1,100 @@
+use super::field::Field;
+use crate::context::{Context, ShellOptions};
+use glob::{glob_with, MatchOptions};
+use std::io::{Error as IoError, ErrorKind};
+
+type Result<T> = std::result::Result<T, IoError>;
+
+// Expands the field into the sorted list of matching pathnames. Only unquoted parts
+// of the field work as a pattern. If nothing matches, the result depends on
+// the nullglob and failglob options.
+pub fn expand(ctx: &Context, field: Field) -> Result<Vec<String>> {
+    let Field {
+        string: literal,
+        pattern,
+        has_pattern,
+        ..
+    } = field;
+    if !has_pattern {
+        return Ok(vec![literal]);
+    }
+
+    let options = ctx.options;
+    let pattern = normalize_globstar(&pattern, options.globstar);
+    let mut result = vec![];
+    for (pattern, suffix) in globstar_patterns(&pattern, options.globstar) {
+        let paths = match glob_with(&pattern, match_options(options)) {
+            Ok(paths) => paths,
+            Err(_) => return Ok(vec![literal]), // an invalid pattern is not a pattern
+        };
+        result.extend(
+            paths
+                .filter_map(|path| path.ok())
+                .map(|path| format!("{}{suffix}", path.to_string_lossy())),
+        );
+    }
+    result.sort();
+    result.dedup();
+
+    match result.is_empty() {
+        false => Ok(result),
+        true if options.failglob => Err(IoError::new(
+            ErrorKind::NotFound,
+            format!("rbsh: no match: {literal}"),
+        )),
+        true if options.nullglob => Ok(vec![]),
+        true => Ok(vec![literal]),
+    }
+}
+
+fn match_options(options: ShellOptions) -> MatchOptions {
+    MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: !options.dotglob,
+    }
+}
+
+// `**` matches directories recursively only when globstar is set and it is a whole
+// path component. Otherwise it is the same as `*`.
+fn normalize_globstar(pattern: &str, globstar: bool) -> String {
+    pattern
+        .split('/')
+        .map(|component| match component {
+            "**" if globstar => component.to_string(),
+            _ => {
+                let mut result = String::new();
+                for c in component.chars() {
+                    if !(c == '*' && result.ends_with('*')) {
+                        result.push(c);
+                    }
+                }
+                result
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// A trailing `**` or `**/` matches all files or directories under the directory, and the
+// directory itself with a trailing slash, while the glob crate only matches the subdirectories.
+// A trailing slash matches only directories, which the glob crate returns without it.
+// Returns the patterns with the suffix appended to their matches.
+fn globstar_patterns(pattern: &str, globstar: bool) -> Vec<(String, &'static str)> {
+    if let Some(dir) = pattern.strip_suffix("**/") {
+        if globstar && dir.ends_with('/') {
+            return vec![(dir.to_string(), "/"), (pattern.to_string(), "/")];
+        }
+    }
+
+    match pattern.strip_suffix("**") {
+        Some("") if globstar => vec![("**/*".to_string(), "")],
+        Some(dir) if globstar && dir.ends_with('/') => {
+            vec![(dir.to_string(), "/"), (format!("{pattern}/*"), "")]
+        }
+        _ if pattern.ends_with('/') => vec![(pattern.to_string(), "/")],
+        _ => vec![(pattern.to_string(), "")],
+    }
+}
+
+include!("pathname_test.rs");
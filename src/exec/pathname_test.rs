@@ -0,0 +1,104 @@
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::exec::field;
+    use rbsh_parser::{parse_word, Location, Word, WordKind};
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    // Creates a directory tree for the test and returns its path.
+    fn setup(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rbsh-pathname-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for path in ["a.rs", "b.rs", "c.txt", ".hidden.rs", "sub/d.rs", "sub/deep/e.rs"] {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        dir
+    }
+
+    // The directory part is quoted, so that only the pattern part is expanded.
+    fn expand_in(ctx: &mut Context, dir: &Path, pattern: &str) -> Result<Vec<String>> {
+        let dir = dir.to_string_lossy().to_string();
+        let mut words = vec![Word::new(format!("{dir}/"), WordKind::Quote, Location::default())];
+        words.extend(parse_word(pattern, 0).unwrap());
+        let mut result = vec![];
+        for field in field::split(ctx, words)? {
+            for path in expand(ctx, field)? {
+                result.push(path.replacen(&dir, "", 1));
+            }
+        }
+        Ok(result)
+    }
+
+    #[test]
+    fn test_expand() {
+        let (mut ctx, _c) = ctx!();
+        let dir = setup("expand");
+
+        assert_eq!(expand_in(&mut ctx, &dir, "*.rs").unwrap(), vec!["/a.rs", "/b.rs"]);
+        assert_eq!(expand_in(&mut ctx, &dir, "?.*").unwrap(), vec!["/a.rs", "/b.rs", "/c.txt"]);
+        assert_eq!(expand_in(&mut ctx, &dir, "[ac].*").unwrap(), vec!["/a.rs", "/c.txt"]);
+        assert_eq!(expand_in(&mut ctx, &dir, "*/*.rs").unwrap(), vec!["/sub/d.rs"]);
+        assert_eq!(expand_in(&mut ctx, &dir, "*/").unwrap(), vec!["/sub/"]);
+        assert_eq!(expand_in(&mut ctx, &dir, "*/*/").unwrap(), vec!["/sub/deep/"]);
+        assert_eq!(expand_in(&mut ctx, &dir, "'*'.rs").unwrap(), vec!["/*.rs"]);
+        assert_eq!(expand_in(&mut ctx, &dir, "\\*.rs").unwrap(), vec!["/*.rs"]);
+        assert_eq!(expand_in(&mut ctx, &dir, "a.rs").unwrap(), vec!["/a.rs"]);
+        assert_eq!(expand_in(&mut ctx, &dir, "[").unwrap(), vec!["/["]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_options() {
+        let (mut ctx, _c) = ctx!();
+        let dir = setup("options");
+
+        assert_eq!(expand_in(&mut ctx, &dir, "*.x").unwrap(), vec!["/*.x"]);
+        ctx.options.nullglob = true;
+        assert_eq!(expand_in(&mut ctx, &dir, "*.x").unwrap(), Vec::<String>::new());
+        ctx.options.failglob = true;
+        assert_eq!(
+            expand_in(&mut ctx, &dir, "*.x").unwrap_err().to_string(),
+            format!("rbsh: no match: {}/*.x", dir.to_string_lossy())
+        );
+
+        ctx.options.dotglob = true;
+        assert_eq!(
+            expand_in(&mut ctx, &dir, "*.rs").unwrap(),
+            vec!["/.hidden.rs", "/a.rs", "/b.rs"]
+        );
+
+        assert_eq!(expand_in(&mut ctx, &dir, "**/*.rs").unwrap(), vec!["/sub/d.rs"]);
+        ctx.options.globstar = true;
+        assert_eq!(
+            expand_in(&mut ctx, &dir, "**/*.rs").unwrap(),
+            vec!["/.hidden.rs", "/a.rs", "/b.rs", "/sub/d.rs", "/sub/deep/e.rs"]
+        );
+        assert_eq!(
+            expand_in(&mut ctx, &dir, "sub/**").unwrap(),
+            vec!["/sub/", "/sub/d.rs", "/sub/deep", "/sub/deep/e.rs"]
+        );
+        assert_eq!(
+            expand_in(&mut ctx, &dir, "**/").unwrap(),
+            vec!["/", "/sub/", "/sub/deep/"]
+        );
+        assert_eq!(
+            expand_in(&mut ctx, &dir, "sub/**/").unwrap(),
+            vec!["/sub/", "/sub/deep/"]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_normalize_globstar() {
+        assert_eq!(normalize_globstar("a/**/b", true), "a/**/b");
+        assert_eq!(normalize_globstar("a/**/b", false), "a/*/b");
+        assert_eq!(normalize_globstar("a**/b***", true), "a*/b*");
+    }
+}
@@ -1,27 +1,33 @@
 use crate::{
     context::Context,
-    error::ShellError,
+    error::{ShellError, ShellErrorKind},
     exec::WordParser,
     status::Result,
-    syscall::{self, SysCallResult},
+    syscall::{self, SysCallError, SysCallResult},
 };
-use nix::{fcntl::OFlag, sys::stat::Mode};
+use nix::{errno::Errno, fcntl::OFlag, sys::stat::Mode, unistd::Whence};
 use rbsh_parser::{Location, Redirect, RedirectKind, Word};
 use std::collections::HashSet;
-use std::os::unix::io::RawFd;
+use std::{env::temp_dir, os::unix::io::RawFd};
+
+type RedirectResult = std::result::Result<(), ShellErrorKind>;
 
 pub trait ApplyRedirect {
-    fn apply(self, ctx: &Context, save: bool) -> Result<Vec<Redirect>>;
+    fn apply(self, ctx: &mut Context, save: bool) -> Result<Vec<Redirect>>;
 }
 
 impl ApplyRedirect for Vec<Redirect> {
-    fn apply(self, ctx: &Context, save: bool) -> Result<Vec<Redirect>> {
+    fn apply(self, ctx: &mut Context, save: bool) -> Result<Vec<Redirect>> {
         RedirectApplier::new(save).exec(ctx, self)
     }
 }
 
 pub const SHELL_FDBASE: RawFd = 10;
 
+// Larger here-documents and here-strings are written to a temporary file instead of a pipe,
+// so that writing them never blocks.
+const HERE_DOCUMENT_PIPE_SIZE: usize = 4096;
+
 struct RedirectApplier {
     save: bool,
     savefd: [Option<RawFd>; 3], // fd <= 2
@@ -37,7 +43,7 @@ impl RedirectApplier {
         }
     }
 
-    fn exec(&mut self, ctx: &Context, list: Vec<Redirect>) -> Result<Vec<Redirect>> {
+    fn exec(&mut self, ctx: &mut Context, list: Vec<Redirect>) -> Result<Vec<Redirect>> {
         for redirect in list {
             let kind = redirect.value;
             let loc = redirect.location;
@@ -54,24 +60,34 @@ impl RedirectApplier {
                 }
                 RedirectKind::WriteBoth(wordlist) => self
                     .open(ctx, 1, wordlist, flag_write)
-                    .and_then(|_| self.copy(1, 2)),
-                RedirectKind::Copy(src, dest, close) => {
-                    self.copy(src, dest).and_then(|_| match close {
+                    .and_then(|_| Ok(self.copy(1, 2)?)),
+                RedirectKind::Copy(src, dest, close) => self
+                    .copy(src, dest)
+                    .and_then(|_| match close {
                         false => Ok(()),
                         true => self.close(src),
                     })
+                    .map_err(ShellErrorKind::from),
+                RedirectKind::Duplicate(fd, wordlist) => {
+                    self.duplicate(ctx, fd, wordlist, flag_write)
                 }
                 RedirectKind::Append(fd, wordlist) => self.open(ctx, fd, wordlist, flag_append),
                 RedirectKind::AppendBoth(wordlist) => self
                     .open(ctx, 1, wordlist, flag_append)
-                    .and_then(|_| self.copy(1, 2)),
-                RedirectKind::Close(fd) => self.close(fd),
+                    .and_then(|_| Ok(self.copy(1, 2)?)),
+                RedirectKind::Close(fd) => self.close(fd).map_err(ShellErrorKind::from),
                 RedirectKind::ReadWrite(fd, wordlist) => self.open(ctx, fd, wordlist, flag_rw),
+                RedirectKind::HereDocument(fd, wordlist) => {
+                    self.here_document(ctx, fd, wordlist, false)
+                }
+                RedirectKind::HereString(fd, wordlist) => {
+                    self.here_document(ctx, fd, wordlist, true)
+                }
             }
             .map_err(|e| {
                 self.save = false;
                 self.exec(ctx, self.restore_list()).ok();
-                ShellError::syscall_error(e, loc)
+                ShellError::new(e, loc)
             })?;
         }
 
@@ -80,14 +96,79 @@ impl RedirectApplier {
 
     fn open(
         &mut self,
-        ctx: &Context,
+        ctx: &mut Context,
         fd: RawFd,
         wordlist: Vec<Word>,
         flag: OFlag,
-    ) -> SysCallResult<()> {
-        let file = wordlist.to_string(ctx).unwrap();
+    ) -> RedirectResult {
+        let file = wordlist.to_string(ctx)?;
+        Ok(self.open_file(fd, &file, flag)?)
+    }
+
+    fn open_file(&mut self, fd: RawFd, file: &str, flag: OFlag) -> SysCallResult<()> {
         let mode = Mode::from_bits(0o666).unwrap();
-        let new_fd = syscall::open(&file, flag, mode)?;
+        let new_fd =
+            syscall::open(file, flag, mode).map_err(|e| SysCallError::new(file, e.errno()))?;
+
+        if fd == new_fd {
+            self.openfd.insert(fd);
+        } else {
+            self.copy(new_fd, fd)?;
+            self.close(new_fd)?;
+        }
+
+        Ok(())
+    }
+
+    // Duplicates the fd which the word expands to, e.g. >&$fd or <&$fd-. Like >&file, the
+    // output is redirected to a file if the word is not a number.
+    fn duplicate(
+        &mut self,
+        ctx: &mut Context,
+        dest: RawFd,
+        wordlist: Vec<Word>,
+        flag: OFlag,
+    ) -> RedirectResult {
+        let word = wordlist.to_string(ctx)?;
+        let (src, close) = match word.strip_suffix('-') {
+            Some(src) => (src, true),
+            None => (word.as_str(), false),
+        };
+
+        let result = match src.parse::<RawFd>() {
+            Ok(src) => {
+                self.copy(src, dest)?;
+                match close {
+                    true => self.close(src),
+                    false => Ok(()),
+                }
+            }
+            Err(_) if word == "-" => self.close(dest),
+            Err(_) if dest == 1 => self.open_file(1, &word, flag).and_then(|_| self.copy(1, 2)),
+            Err(_) => Err(SysCallError::new(word, Errno::EBADF)),
+        };
+        Ok(result?)
+    }
+
+    fn here_document(
+        &mut self,
+        ctx: &mut Context,
+        fd: RawFd,
+        wordlist: Vec<Word>,
+        newline: bool,
+    ) -> RedirectResult {
+        // Only a here string is subject to tilde expansion, not the body of a here document.
+        let mut body = match newline {
+            true => wordlist.to_string(ctx)?,
+            false => wordlist
+                .into_iter()
+                .map(|word| word.to_string(ctx))
+                .collect::<std::result::Result<_, _>>()?,
+        };
+        if newline {
+            body.push('\n');
+        }
+        let new_fd = open_here_document(body.as_bytes())?;
 
         if fd == new_fd {
             self.openfd.insert(fd);
@@ -101,7 +182,7 @@ impl RedirectApplier {
 
     fn copy(&mut self, src: RawFd, dest: RawFd) -> SysCallResult<()> {
         match dest {
-            fd if self.save && fd <= 2 && syscall::isatty(dest).unwrap_or(false) => {
+            fd if self.save && fd <= 2 => {
                 self.savefd[dest as usize] = match self.savefd[dest as usize] {
                     Some(f) => Some(f),
                     None => Some(syscall::dup_fd(dest, SHELL_FDBASE)?),
@@ -118,7 +199,7 @@ impl RedirectApplier {
     }
 
     fn close(&mut self, fd: RawFd) -> SysCallResult<()> {
-        if self.save && fd <= 2 && syscall::isatty(fd).unwrap_or(false) {
+        if self.save && fd <= 2 {
             self.savefd[fd as usize] = match self.savefd[fd as usize] {
                 Some(f) => Some(f),
                 None => Some(syscall::dup_fd(fd, SHELL_FDBASE)?),
@@ -147,4 +228,27 @@ impl RedirectApplier {
     }
 }
 
+fn open_here_document(body: &[u8]) -> SysCallResult<RawFd> {
+    if body.len() <= HERE_DOCUMENT_PIPE_SIZE {
+        let (read, write) = syscall::pipe()?;
+        let result = write_all(write, body);
+        syscall::close(write)?;
+        result.map(|_| read)
+    } else {
+        let (fd, path) = syscall::mkstemp(&temp_dir().join("rbsh-XXXXXX"))?;
+        syscall::unlink(&path)?;
+        write_all(fd, body)?;
+        syscall::lseek(fd, 0, Whence::SeekSet)?;
+        Ok(fd)
+    }
+}
+
+fn write_all(fd: RawFd, mut buf: &[u8]) -> SysCallResult<()> {
+    while !buf.is_empty() {
+        let size = syscall::write(fd, buf)?;
+        buf = &buf[size..];
+    }
+    Ok(())
+}
+
 include!("redirect_test.rs");
@@ -36,7 +36,7 @@ mod test {
             .return_const(Ok(3));
         assert!(r!(false)
             .exec(
-                &Context::new(),
+                &mut Context::new(),
                 vec![Redirect::read_from(
                     3,
                     wordlist![word!("foobar")],
@@ -72,7 +72,7 @@ mod test {
 
         assert!(r!(false)
             .exec(
-                &Context::new(),
+                &mut Context::new(),
                 vec![Redirect::read_from(
                     0,
                     wordlist![word!("foobar")],
@@ -96,7 +96,7 @@ mod test {
             .return_const(Ok(3));
         assert!(r!(false)
             .exec(
-                &Context::new(),
+                &mut Context::new(),
                 vec![Redirect::write_to(
                     3,
                     wordlist![word!("foobar")],
@@ -132,7 +132,7 @@ mod test {
             .return_const(Ok(()));
         assert!(r!(false)
             .exec(
-                &Context::new(),
+                &mut Context::new(),
                 vec![Redirect::write_to(
                     1,
                     wordlist![word!("foobar")],
@@ -177,7 +177,7 @@ mod test {
 
         assert!(r!(false)
             .exec(
-                &Context::new(),
+                &mut Context::new(),
                 vec![Redirect::write_both(
                     wordlist![word!("foobar")],
                     Location::new(1, 1),
@@ -196,7 +196,7 @@ mod test {
             .return_const(Ok(4));
         assert!(r!(false)
             .exec(
-                &Context::new(),
+                &mut Context::new(),
                 vec![Redirect::copy(3, 4, false, Location::new(1, 1))],
             )
             .is_ok());
@@ -216,7 +216,7 @@ mod test {
             .return_const(Ok(()));
         assert!(r!(false)
             .exec(
-                &Context::new(),
+                &mut Context::new(),
                 vec![Redirect::copy(3, 4, true, Location::new(1, 1))],
             )
             .is_ok());
@@ -236,7 +236,7 @@ mod test {
             .return_const(Ok(3));
         assert!(r!(false)
             .exec(
-                &Context::new(),
+                &mut Context::new(),
                 vec![Redirect::append(
                     3,
                     wordlist![word!("foobar")],
@@ -272,7 +272,7 @@ mod test {
 
         assert!(r!(false)
             .exec(
-                &Context::new(),
+                &mut Context::new(),
                 vec![Redirect::append(
                     1,
                     wordlist![word!("foobar")],
@@ -315,7 +315,7 @@ mod test {
             .return_const(Ok(()));
         assert!(r!(false)
             .exec(
-                &Context::new(),
+                &mut Context::new(),
                 vec![Redirect::append_both(
                     wordlist![word!("foobar")],
                     Location::new(1, 1),
@@ -334,12 +334,57 @@ mod test {
             .return_const(Ok(()));
         assert!(r!(false)
             .exec(
-                &Context::new(),
+                &mut Context::new(),
                 vec![Redirect::close(1, Location::new(1, 1))]
             )
             .is_ok());
     }
 
+    #[test]
+    fn test_save() {
+        // The fds 0-2 are restored after the command even when they are not terminals,
+        // e.g. in a script whose output is redirected to a file.
+        let isatty_context = syscall::isatty_context();
+        isatty_context.expect().returning(|_| Ok(false));
+
+        let dup_fd_context = syscall::dup_fd_context();
+        dup_fd_context
+            .expect()
+            .times(1)
+            .with(eq(1), eq(SHELL_FDBASE))
+            .return_const(Ok(10));
+        dup_fd_context
+            .expect()
+            .times(1)
+            .with(eq(2), eq(SHELL_FDBASE))
+            .return_const(Ok(11));
+        let dup2_context = syscall::dup2_context();
+        dup2_context
+            .expect()
+            .times(1)
+            .with(eq(3), eq(1))
+            .return_const(Ok(1));
+        let close_context = syscall::close_context();
+        close_context
+            .expect()
+            .times(1)
+            .with(eq(2))
+            .return_const(Ok(()));
+        assert_eq!(
+            r!(true).exec(
+                &mut Context::new(),
+                vec![
+                    Redirect::copy(3, 1, false, Location::new(1, 1)),
+                    Redirect::close(2, Location::new(1, 6)),
+                ],
+            ),
+            Ok(vec![
+                Redirect::copy(10, 1, true, Location::new(0, 0)),
+                Redirect::copy(11, 2, true, Location::new(0, 0)),
+            ])
+        );
+    }
+
     #[test]
     fn test_read_write() {
         let open_context = syscall::open_context();
@@ -354,7 +399,7 @@ mod test {
             .return_const(Ok(3));
         assert!(r!(false)
             .exec(
-                &Context::new(),
+                &mut Context::new(),
                 vec![Redirect::read_write(
                     3,
                     wordlist![word!("foobar")],
@@ -389,7 +434,7 @@ mod test {
             .return_const(Ok(()));
         assert!(r!(false)
             .exec(
-                &Context::new(),
+                &mut Context::new(),
                 vec![Redirect::read_write(
                     0,
                     wordlist![word!("foobar")],
@@ -398,4 +443,90 @@ mod test {
             )
             .is_ok());
     }
+
+    #[test]
+    fn test_here_document() {
+        let pipe_context = syscall::pipe_context();
+        pipe_context.expect().times(1).return_const(Ok((3, 4)));
+
+        let write_context = syscall::write_context();
+        write_context
+            .expect()
+            .times(1)
+            .withf(|fd, buf| *fd == 4 && buf == b"foobar\n")
+            .return_const(Ok(7));
+
+        let dup2_context = syscall::dup2_context();
+        dup2_context
+            .expect()
+            .times(1)
+            .with(eq(3), eq(0))
+            .return_const(Ok(0));
+
+        let close_context = syscall::close_context();
+        close_context
+            .expect()
+            .times(1)
+            .with(eq(4))
+            .return_const(Ok(()));
+        close_context
+            .expect()
+            .times(1)
+            .with(eq(3))
+            .return_const(Ok(()));
+
+        assert!(r!(false)
+            .exec(
+                &mut Context::new(),
+                vec![Redirect::here_document(
+                    0,
+                    wordlist![word!("foobar\n")],
+                    Location::new(1, 1),
+                )],
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn test_here_string() {
+        let pipe_context = syscall::pipe_context();
+        pipe_context.expect().times(1).return_const(Ok((3, 4)));
+
+        let write_context = syscall::write_context();
+        write_context
+            .expect()
+            .times(1)
+            .withf(|fd, buf| *fd == 4 && buf == b"foobar\n")
+            .return_const(Ok(7));
+
+        let dup2_context = syscall::dup2_context();
+        dup2_context
+            .expect()
+            .times(1)
+            .with(eq(3), eq(5))
+            .return_const(Ok(5));
+
+        let close_context = syscall::close_context();
+        close_context
+            .expect()
+            .times(1)
+            .with(eq(4))
+            .return_const(Ok(()));
+        close_context
+            .expect()
+            .times(1)
+            .with(eq(3))
+            .return_const(Ok(()));
+
+        assert!(r!(false)
+            .exec(
+                &mut Context::new(),
+                vec![Redirect::here_string(
+                    5,
+                    wordlist![word!("foobar")],
+                    Location::new(1, 1),
+                )],
+            )
+            .is_ok());
+    }
 }
@@ -0,0 +1,17 @@
+// Fixtures shared by the unit tests of the expansions.
+
+// Returns a new context with the variables set, e.g. ctx!("HOME" => "/home/rbsh"), and the
+// mock of env_get, which finds no environment variable. The mock must be kept alive while
+// the context is used.
+macro_rules! ctx {
+    ($($name: expr => $value: expr),* $(,)?) => {{
+        let env_get_context = crate::syscall::env_get_context();
+        env_get_context
+            .expect()
+            .returning(|_| Err(std::env::VarError::NotPresent));
+        #[allow(unused_mut)]
+        let mut ctx = crate::context::Context::new();
+        $(ctx.set_var($name, $value);)*
+        (ctx, env_get_context)
+    }};
+}
@@ -0,0 +1,96 @@
+use super::IsVarName;
+use crate::context::Context;
+use nix::unistd::{getuid, User};
+use rbsh_parser::{Word, WordKind};
+
+// Expands a tilde prefix at the beginning of the words. In an assignment, tilde prefixes
+// after '=' and the following ':' are expanded too, e.g. PATH=~/bin:~user/bin.
+// The expanded directory is a quoted word, so that it is not expanded any further.
+pub fn expand(ctx: &Context, words: Vec<Word>) -> Vec<Word> {
+    let assignment = words.is_var_name();
+    let len = words.len();
+
+    let mut result = vec![];
+    for (i, word) in words.into_iter().enumerate() {
+        if word.kind != WordKind::Normal {
+            result.push(word);
+            continue;
+        }
+
+        let s = &word.string;
+        let mut cursor = 0;
+        for position in tilde_positions(s, i == 0, assignment) {
+            // The tilde prefix is the characters up to the first slash (or colon in
+            // an assignment). It must not continue into the next word.
+            let prefix = &s[position + 1..];
+            let end = match prefix.find(|c: char| c == '/' || (assignment && c == ':')) {
+                Some(end) => end,
+                None if i == len - 1 => prefix.len(),
+                None => continue,
+            };
+            let dir = match home_dir(ctx, &prefix[..end]) {
+                Some(dir) => dir,
+                None => continue,
+            };
+
+            if cursor < position {
+                result.push(Word::new(
+                    s[cursor..position].to_string(),
+                    WordKind::Normal,
+                    word.location,
+                ));
+            }
+            result.push(Word::new(dir, WordKind::Quote, word.location));
+            cursor = position + 1 + end;
+        }
+        if cursor < s.len() {
+            result.push(Word::new(
+                s[cursor..].to_string(),
+                WordKind::Normal,
+                word.location,
+            ));
+        }
+    }
+    result
+}
+
+fn tilde_positions(s: &str, is_first_word: bool, assignment: bool) -> Vec<usize> {
+    if !assignment {
+        return match is_first_word && s.starts_with('~') {
+            true => vec![0],
+            false => vec![],
+        };
+    }
+
+    // In the first word, the value starts after '='.
+    let value = match is_first_word {
+        true => s.find('=').map(|i| i + 1),
+        false => None,
+    };
+    s.match_indices('~')
+        .map(|(i, _)| i)
+        .filter(|i| match value {
+            Some(value) => *i == value || (*i > value && s[..*i].ends_with(':')),
+            None => s[..*i].ends_with(':'),
+        })
+        .collect()
+}
+
+// ~ is the home directory, ~user is the home directory of the user,
+// and ~+ and ~- are the current and previous working directories.
+fn home_dir(ctx: &Context, name: &str) -> Option<String> {
+    let user = match name {
+        "" => match ctx.get_var("HOME") {
+            Some(home) => return Some(home),
+            None => User::from_uid(getuid()),
+        },
+        "+" => return ctx.get_var("PWD"),
+        "-" => return ctx.get_var("OLDPWD"),
+        _ => User::from_name(name),
+    };
+    user.ok()
+        .flatten()
+        .map(|user| user.dir.to_string_lossy().to_string())
+}
+
+include!("tilde_test.rs");
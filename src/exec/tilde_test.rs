@@ -0,0 +1,44 @@
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rbsh_parser::parse_word;
+
+    macro_rules! assert_expand {
+        ($ctx: expr, $s: expr, $expected: expr) => {
+            let result = expand(&$ctx, parse_word($s, 0).unwrap())
+                .into_iter()
+                .map(|w| w.string)
+                .collect::<String>();
+            assert_eq!(result, $expected)
+        };
+    }
+
+    #[test]
+    fn test_expand() {
+        let (ctx, _c) = ctx!("HOME" => "/home/rbsh", "PWD" => "/pwd", "OLDPWD" => "/oldpwd");
+        assert_expand!(ctx, "~", "/home/rbsh");
+        assert_expand!(ctx, "~/src", "/home/rbsh/src");
+        assert_expand!(ctx, "~+/a", "/pwd/a");
+        assert_expand!(ctx, "~-", "/oldpwd");
+        assert_expand!(ctx, "~no_such_user_rbsh/a", "~no_such_user_rbsh/a");
+        assert_expand!(ctx, "a~", "a~");
+        assert_expand!(ctx, "a:~", "a:~");
+        assert_expand!(ctx, "'~'", "~");
+        assert_expand!(ctx, "\\~", "~");
+        assert_expand!(ctx, "~$HOME", "~HOME");
+
+        // the expanded directory is quoted
+        let words = expand(&ctx, parse_word("~/*", 0).unwrap());
+        assert_eq!(words[0].kind, WordKind::Quote);
+        assert_eq!(words[1].kind, WordKind::Normal);
+    }
+
+    #[test]
+    fn test_assignment() {
+        let (ctx, _c) = ctx!("HOME" => "/home/rbsh", "PWD" => "/pwd");
+        assert_expand!(ctx, "A=~", "A=/home/rbsh");
+        assert_expand!(ctx, "PATH=~/bin:~+:$PATH", "PATH=/home/rbsh/bin:/pwd:PATH");
+        assert_expand!(ctx, "A=x~:a~", "A=x~:a~");
+        assert_expand!(ctx, "A=$x:~/a", "A=x:/home/rbsh/a");
+    }
+}
@@ -0,0 +1,74 @@
+use std::{iter::Peekable, str::Chars, time::Duration};
+
+// The format when TIMEFORMAT is not set.
+pub const DEFAULT_FORMAT: &str = "\nreal\t%3lR\nuser\t%3lU\nsys\t%3lS";
+
+// The format of time -p, which ignores TIMEFORMAT.
+pub const POSIX_FORMAT: &str = "real %2R\nuser %2U\nsys %2S";
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Times {
+    pub real: Duration,
+    pub user: Duration,
+    pub sys: Duration,
+}
+
+// Formats the times as TIMEFORMAT. %[p][l]R, %[p][l]U and %[p][l]S are the real, user and
+// system time, where p is the number of fractional digits (0 to 3, 3 by default) and l is
+// the long format such as 1m2.345s. %P is the CPU percentage and %% is a literal '%'.
+pub fn format(format: &str, times: &Times) -> String {
+    let mut result = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => result.push_str(&specifier(&mut chars, times)),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+fn specifier(chars: &mut Peekable<Chars>, times: &Times) -> String {
+    let precision = chars.next_if(char::is_ascii_digit);
+    let long = chars.next_if_eq(&'l');
+    let digits = precision.map_or(3, |c| c.to_digit(10).unwrap().min(3) as usize);
+
+    match chars.next() {
+        Some('R') => seconds(times.real, digits, long.is_some()),
+        Some('U') => seconds(times.user, digits, long.is_some()),
+        Some('S') => seconds(times.sys, digits, long.is_some()),
+        Some('P') if precision.is_none() && long.is_none() => {
+            let cpu = (times.user + times.sys).as_secs_f64();
+            let real = times.real.as_secs_f64();
+            let percent = match real > 0.0 {
+                true => cpu / real * 100.0,
+                false => 0.0,
+            };
+            format!("{percent:.2}")
+        }
+        Some('%') if precision.is_none() && long.is_none() => "%".to_string(),
+
+        // An unknown specifier is printed as it is.
+        c => ['%']
+            .into_iter()
+            .chain(precision)
+            .chain(long)
+            .chain(c)
+            .collect(),
+    }
+}
+
+fn seconds(duration: Duration, digits: usize, long: bool) -> String {
+    let micros = duration.as_micros();
+    let (secs, fraction) = (micros / 1_000_000, micros % 1_000_000);
+    let fraction = match digits {
+        0 => String::new(),
+        _ => format!(".{:0digits$}", fraction / 10u128.pow(6 - digits as u32)),
+    };
+    match long {
+        true => format!("{}m{}{fraction}s", secs / 60, secs % 60),
+        false => format!("{secs}{fraction}"),
+    }
+}
+
+include!("time_test.rs");
@@ -0,0 +1,26 @@
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let times = Times {
+            real: Duration::from_millis(62_345),
+            user: Duration::from_micros(1_500_999),
+            sys: Duration::from_millis(5),
+        };
+
+        assert_eq!(
+            format(DEFAULT_FORMAT, &times),
+            "\nreal\t1m2.345s\nuser\t0m1.500s\nsys\t0m0.005s"
+        );
+        assert_eq!(
+            format(POSIX_FORMAT, &times),
+            "real 62.34\nuser 1.50\nsys 0.00"
+        );
+        assert_eq!(format("%0R %1lU %9S", &times), "62 0m1.5s 0.005");
+        assert_eq!(format("%P%%", &times), "2.42%");
+        assert_eq!(format("%x %2lP %", &times), "%x %2lP %");
+        assert_eq!(format("", &times), "");
+    }
+}
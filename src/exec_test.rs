@@ -110,14 +110,14 @@ mod test {
 
     #[test]
     fn test_expand_command_line() {
-        let ctx = Context::new();
+        let mut ctx = Context::new();
         assert_eq!(
             Some(SimpleCommandKind::External {
                 env: HashMap::new(),
                 command: "foo".to_string(),
                 args: vec![],
             }),
-            expand_command_line(&ctx, vec![wordlist![word!("foo")]]).ok()
+            expand_command_line(&mut ctx, vec![wordlist![word!("foo")]]).ok()
         );
 
         assert_eq!(
@@ -127,7 +127,7 @@ mod test {
                 args: Args::new()
             }),
             expand_command_line(
-                &ctx,
+                &mut ctx,
                 vec![
                     wordlist![word!("foo=bar")],
                     wordlist![word!("baz=foo")],
@@ -144,7 +144,7 @@ mod test {
                 args: vec!["hoge=fuga".to_string()],
             }),
             expand_command_line(
-                &ctx,
+                &mut ctx,
                 vec![
                     wordlist![word!("foo=bar")],
                     wordlist![word!("baz")],
@@ -154,4 +154,35 @@ mod test {
             .ok()
         );
     }
+
+    #[test]
+    fn test_expand_command_line_return() {
+        let mut ctx = Context::new();
+        assert_eq!(
+            Some(SimpleCommandKind::Return {
+                args: vec!["1".to_string()]
+            }),
+            expand_command_line(&mut ctx, vec![wordlist![word!("return")], wordlist![word!("1")]])
+                .ok()
+        );
+    }
+
+    #[test]
+    fn test_is_match() {
+        let mut ctx = Context::new();
+        assert!(is_match(&mut ctx, wordlist![word!("foo")], "foo"));
+        assert!(!is_match(&mut ctx, wordlist![word!("foo")], "bar"));
+        assert!(is_match(&mut ctx, wordlist![word!("*.rs")], "main.rs"));
+        assert!(is_match(&mut ctx, wordlist![word!("?a[rz]")], "bar"));
+        assert!(is_match(&mut ctx, wordlist![word!("[!a]*")], "bar"));
+        assert!(!is_match(&mut ctx, wordlist![word!("[!b]*")], "bar"));
+
+        // quoted pattern matches literally
+        let quote = Word::new("*".to_string(), WordKind::Quote, Location::new(1, 1));
+        assert!(is_match(&mut ctx, wordlist![quote.clone()], "*"));
+        assert!(!is_match(&mut ctx, wordlist![quote], "foo"));
+
+        // invalid pattern
+        assert!(is_match(&mut ctx, wordlist![word!("[")], "["));
+    }
 }
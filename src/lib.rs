@@ -2,6 +2,7 @@ mod app;
 mod builtin;
 mod config;
 mod context;
+mod diagnostic;
 mod error;
 mod exec;
 mod mockable_syscall;
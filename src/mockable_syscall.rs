@@ -59,11 +59,16 @@ pub mod inner {
             termios,
             wait::{self, WaitPidFlag, WaitStatus},
         },
-        unistd::{self, ForkResult, Pid},
+        unistd::{self, ForkResult, Pid, Whence},
     };
     use std::{
-        collections::HashMap, convert::Infallible, env, ffi::CString, os::unix::io::RawFd,
-        path::PathBuf, process,
+        collections::HashMap,
+        convert::Infallible,
+        env,
+        ffi::CString,
+        os::unix::io::RawFd,
+        path::{Path, PathBuf},
+        process,
     };
 
     pub fn close(fd: RawFd) -> SysCallResult<()> {
@@ -79,6 +84,12 @@ pub mod inner {
         syscall!(fcntl::fcntl(src, arg))
     }
 
+    // Unlike dup_fd, the new fd is inherited by the commands which the shell executes.
+    pub fn dup_fd_inheritable(src: RawFd, dest: RawFd) -> SysCallResult<RawFd> {
+        let arg = FcntlArg::F_DUPFD(dest);
+        syscall!(fcntl::fcntl(src, arg))
+    }
+
     pub fn env_get(key: &str) -> Result<String, env::VarError> {
         env::var(key)
     }
@@ -119,6 +130,14 @@ pub mod inner {
         syscall!(unistd::isatty(fd))
     }
 
+    pub fn lseek(fd: RawFd, offset: i64, whence: Whence) -> SysCallResult<i64> {
+        syscall!(unistd::lseek(fd, offset, whence))
+    }
+
+    pub fn mkstemp(template: &Path) -> SysCallResult<(RawFd, PathBuf)> {
+        syscall!(unistd::mkstemp(template))
+    }
+
     pub fn open(path: &str, oflag: OFlag, mode: Mode) -> SysCallResult<RawFd> {
         syscall!(fcntl::open(path, oflag, mode))
     }
@@ -166,7 +185,15 @@ pub mod inner {
         syscall!(unistd::tcsetpgrp(fd, pgrp))
     }
 
+    pub fn unlink(path: &Path) -> SysCallResult<()> {
+        syscall!(unistd::unlink(path))
+    }
+
     pub fn waitpid(pid: Pid, options: Option<WaitPidFlag>) -> SysCallResult<WaitStatus> {
         syscall!(wait::waitpid(pid, options))
     }
+
+    pub fn write(fd: RawFd, buf: &[u8]) -> SysCallResult<usize> {
+        syscall!(unistd::write(fd, buf))
+    }
 }
@@ -5,11 +5,12 @@ use super::{
 };
 
 use nix::{
-    errno::errno,
+    errno::{errno, Errno},
     libc,
     sys::{
         signal::{killpg, SaFlags, SigAction, SigHandler, SigSet, Signal},
-        wait::{waitpid, WaitStatus},
+        time::{TimeVal, TimeValLike},
+        wait::{WaitPidFlag, WaitStatus},
     },
     unistd::{close, Pid},
 };
@@ -32,9 +33,10 @@ use std::{
     ptr,
     sync::{
         atomic::{AtomicI32, Ordering},
-        Arc, Condvar, Mutex,
+        Arc, Barrier, Condvar, Mutex,
     },
     thread,
+    time::Duration,
 };
 
 const TTYSIGNALS: [i32; 5] = [SIGQUIT, SIGTERM, SIGTSTP, SIGTTIN, SIGTTOU];
@@ -129,15 +131,20 @@ impl<'a> Iterator for Forever<'a> {
 }
 
 pub fn close_signal_handler() {
-    if let Some((read, write)) = unsafe { SIGNAL_HANDLER_FD.take() } {
+    if let Some((read, write)) = take_signal_handler_fd() {
         close(read).unwrap();
         close(write).unwrap();
     }
 }
 
+fn take_signal_handler_fd() -> Option<(RawFd, RawFd)> {
+    unsafe { SIGNAL_HANDLER_FD.take() }
+}
+
 pub struct JobSignalHandlerInner {
     interrupt: bool,
     status: Vec<WaitStatus>,
+    children_times: (Duration, Duration), // the user and system time of the reaped children
 }
 
 impl JobSignalHandlerInner {
@@ -145,9 +152,16 @@ impl JobSignalHandlerInner {
         Self {
             interrupt: false,
             status: vec![],
+            children_times: (Duration::ZERO, Duration::ZERO),
         }
     }
 
+    pub fn add_usage(&mut self, usage: &libc::rusage) {
+        let duration = |t| Duration::from_micros(TimeVal::from(t).num_microseconds() as u64);
+        self.children_times.0 += duration(usage.ru_utime);
+        self.children_times.1 += duration(usage.ru_stime);
+    }
+
     pub fn set_interrupt_flag(&mut self) {
         self.interrupt = true
     }
@@ -207,8 +221,14 @@ impl JobSignalHandler {
         let pair = inner.clone();
         let fg = forground.clone();
 
+        // A process forked while the thread is starting can not start a thread, as the lock
+        // which the thread holds in the standard library is never released in the child.
+        let started = Arc::new(Barrier::new(2));
+        let thread_started = started.clone();
+
         let thread = thread::Builder::new()
         .spawn(move || {
+            thread_started.wait();
             for sig in &mut signals {
                 match sig {
                     SIGINT => {
@@ -226,14 +246,15 @@ impl JobSignalHandler {
                     SIGCHLD => {
                         let any_child = Pid::from_raw(-1);
                         loop {
-                            match waitpid(any_child, Some(nix::sys::wait::WaitPidFlag::WNOHANG)) {
-                                Ok(s) if s == WaitStatus::StillAlive => break,
+                            match wait4(any_child, WaitPidFlag::WNOHANG) {
+                                Ok((WaitStatus::StillAlive, _)) => break,
                                 Err(_) => break,
-                                Ok(s) => {
+                                Ok((s, usage)) => {
                                     let (inner, cvar) = &*pair;
 
                                     let mut lock = inner.lock().unwrap();
                                     lock.push_status(s);
+                                    lock.add_usage(&usage);
                                     if matches!(s, WaitStatus::Signaled(_, signal, _) if signal == Signal::SIGINT)
                                     {
                                         lock.set_interrupt_flag();
@@ -247,6 +268,7 @@ impl JobSignalHandler {
                 }
             }
         }).unwrap();
+        started.wait();
 
         Ok(Self {
             inner,
@@ -276,6 +298,12 @@ impl JobSignalHandler {
         }
     }
 
+    // Returns the total user and system time of the children reaped so far.
+    pub fn children_times(&self) -> (Duration, Duration) {
+        let (mutex, _) = &*self.inner;
+        mutex.lock().unwrap().children_times
+    }
+
     pub fn is_interrupt(&self) -> bool {
         let (mutex, _) = &*self.inner;
         mutex.lock().unwrap().get_interrupt_flag()
@@ -295,12 +323,24 @@ impl JobSignalHandler {
     }
 
     pub fn close(self) {
-        close_signal_handler();
+        // The fds are closed by the thread when it drops the signal handler.
+        take_signal_handler_fd();
         self.signal_handler.close();
         self.thread.join().unwrap();
     }
 }
 
+// waitpid(2) which also returns the resource usage of the child.
+fn wait4(pid: Pid, options: WaitPidFlag) -> nix::Result<(WaitStatus, libc::rusage)> {
+    let mut status = 0;
+    let mut usage: libc::rusage = unsafe { mem::zeroed() };
+    let res = unsafe { libc::wait4(pid.as_raw(), &mut status, options.bits(), &mut usage) };
+    match Errno::result(res)? {
+        0 => Ok((WaitStatus::StillAlive, usage)),
+        pid => WaitStatus::from_raw(Pid::from_raw(pid), status).map(|s| (s, usage)),
+    }
+}
+
 macro_rules! sigaction {
     ($sig: expr, $new: expr, $old: expr) => {
         match libc::sigaction($sig, $new, $old) {
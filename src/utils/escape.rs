@@ -2,95 +2,118 @@ pub trait Escape {
     fn escape(&self) -> String;
 }
 
-impl Escape for String {
+// Decodes the backslash escape sequences of echo -e and $'...'. An unknown or incomplete
+// sequence is left as it is.
+impl Escape for str {
     fn escape(&self) -> String {
+        let chars = self.chars().collect::<Vec<_>>();
         let mut result = String::new();
-        let mut iter = self.split('\\');
-
-        result.push_str(iter.next().unwrap());
-        for s in iter {
-            let mut c = s.chars().collect::<Vec<_>>();
-            match c[0] {
-                'a' => c[0] = '\x07',
-                'b' => c[0] = '\x08',
-                'c' => {
-                    if let Some(cc) = contorl_char(c[1]) {
-                        c.remove(0);
-                        c[0] = cc;
-                    }
-                }
-                'C' if c[1] == '-' => {
-                    if let Some(cc) = contorl_char(c[2]) {
-                        c.remove(0);
-                        c.remove(0);
-                        c[2] = cc;
-                    }
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            i += 1;
+            if c != '\\' {
+                result.push(c);
+                continue;
+            }
+
+            let start = i;
+            let escaped = match chars.get(i) {
+                Some('a') => Some('\x07'),
+                Some('b') => Some('\x08'),
+                Some('e' | 'E') => Some('\x1b'),
+                Some('f') => Some('\x0c'),
+                Some('n') => Some('\n'),
+                Some('r') => Some('\r'),
+                Some('t') => Some('\t'),
+                Some('v') => Some('\x0b'),
+                Some(&c @ ('\\' | '\'' | '"' | '?')) => Some(c),
+
+                // \cx, \C-x: control-x
+                Some('c') => chars.get(i + 1).and_then(control_char).map(|c| {
+                    i += 1;
+                    c
+                }),
+                Some('C') if chars.get(i + 1) == Some(&'-') => {
+                    chars.get(i + 2).and_then(control_char).map(|c| {
+                        i += 2;
+                        c
+                    })
                 }
-                'e' | 'E' => c[0] = '\x1b',
-                'f' => c[0] = '\x0c',
-                'n' => c[0] = '\n',
-                'r' => c[0] = '\r',
-                't' => c[0] = '\t',
-                'v' => c[0] = '\x0b',
 
                 // \nnn: octal
-                n if n.is_digit(8) => {
-                    let mut code = 0;
-                    for _ in 1..=3 {
-                        if c.is_empty() || !c[0].is_digit(8) {
-                            break;
-                        }
-                        code *= 8;
-                        code += c.remove(0) as u32;
-                    }
-                    c.insert(0, char::from_u32(code).unwrap());
+                Some(c) if c.is_digit(8) => {
+                    i -= 1;
+                    code(&chars, &mut i, 8, 3)
                 }
 
                 // \xHH: Hex
                 // \uHHHH: Unicode(16bit)
                 // \UHHHHHHHH: Unicode(32bit)
-                'x' | 'u' | 'U' if c[1].is_ascii_hexdigit() => {
-                    let max = match c.remove(0) {
+                Some(&c @ ('x' | 'u' | 'U')) => {
+                    let max = match c {
                         'x' => 2,
                         'u' => 4,
-                        'U' => 4,
-                        _ => unreachable![],
+                        _ => 8,
                     };
-                    let mut code = 0;
-                    for _ in 1..=max {
-                        if c.is_empty() || !c[0].is_ascii_hexdigit() {
-                            break;
-                        }
-                        code *= 16;
-                        code += c.remove(0).to_digit(16).unwrap();
-                    }
-                    c.insert(0, char::from_u32(code).unwrap());
+                    code(&chars, &mut i, 16, max)
                 }
 
                 // ruby compatible
-                's' => c[0] = ' ',
-                _ => (),
+                Some('s') => Some(' '),
+                _ => None,
+            };
+
+            match escaped {
+                Some(c) => {
+                    result.push(c);
+                    i += 1;
+                }
+                None => {
+                    i = start;
+                    result.push('\\');
+                }
             }
-            result.push_str(&c.into_iter().collect::<String>())
         }
 
         result
     }
 }
 
-fn contorl_char(c: char) -> Option<char> {
+// Reads up to max digits after chars[i] as a character code. The index is moved to the
+// last digit.
+fn code(chars: &[char], i: &mut usize, radix: u32, max: usize) -> Option<char> {
+    let digits = chars[*i + 1..]
+        .iter()
+        .take(max)
+        .map_while(|c| c.to_digit(radix))
+        .collect::<Vec<_>>();
+    if digits.is_empty() {
+        return None;
+    }
+
+    let code = digits.iter().fold(0, |code, d| code * radix + d);
+    char::from_u32(code).map(|c| {
+        *i += digits.len();
+        c
+    })
+}
+
+fn control_char(c: &char) -> Option<char> {
     match c {
         '?' => Some('\x7f'),
 
         // 0x20-0x3e
-        ' '..='>' => char::from_u32(c as u32 - 0x20),
+        ' '..='>' => char::from_u32(*c as u32 - 0x20),
 
         // 0x40-0x5f
-        '@'..='_' => char::from_u32(c as u32 - 0x40),
+        '@'..='_' => char::from_u32(*c as u32 - 0x40),
 
         // 0x60-0x7e
-        '`'..='~' => char::from_u32(c as u32 - 0x60),
+        '`'..='~' => char::from_u32(*c as u32 - 0x60),
 
         _ => None,
     }
 }
+
+include!("escape_test.rs");
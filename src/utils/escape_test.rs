@@ -0,0 +1,24 @@
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!("a\\tb\\nc".escape(), "a\tb\nc");
+        assert_eq!("\\a\\b\\e\\E\\f\\r\\v\\s".escape(), "\x07\x08\x1b\x1b\x0c\r\x0b ");
+        assert_eq!("\\\\n \\' \\\" \\?".escape(), "\\n ' \" ?");
+        assert_eq!("\\101\\0\\7777".escape(), "A\0\u{1ff}7");
+        assert_eq!("\\x41\\x4a4\\u3042\\U0001F600".escape(), "AJ4あ😀");
+        assert_eq!("\\ca\\C-[\\c?".escape(), "\x01\x1b\x7f");
+
+        // incomplete or unknown sequences
+        assert_eq!("\\".escape(), "\\");
+        assert_eq!("a\\".escape(), "a\\");
+        assert_eq!("\\x".escape(), "\\x");
+        assert_eq!("\\xg".escape(), "\\xg");
+        assert_eq!("\\c".escape(), "\\c");
+        assert_eq!("\\C-".escape(), "\\C-");
+        assert_eq!("\\C".escape(), "\\C");
+        assert_eq!("\\z\\U110000".escape(), "\\z\\U110000");
+    }
+}
@@ -0,0 +1,239 @@
+// Runs scripts with the rbsh binary and checks what they print.
+use nix::sys::signal::Signal::SIGPIPE;
+use std::{
+    env, fs,
+    io::{Read, Write},
+    os::unix::process::ExitStatusExt,
+    process::{Command, ExitStatus, Output, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// Runs the script with `rbsh -c` in an empty temporary directory, which is also HOME,
+// so that the files written by the script do not remain in the source tree.
+fn execute(script: &str) -> Output {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let dir = env::temp_dir().join(format!(
+        "rbsh-exec-{}-{}",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&dir).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rbsh"))
+        .args(["-c", script])
+        .current_dir(&dir)
+        .env("HOME", &dir)
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    output
+}
+
+// Returns the standard output of the script.
+fn run(script: &str) -> String {
+    String::from_utf8(execute(script).stdout).unwrap()
+}
+
+// Runs rbsh with the arguments and the input, and closes the standard output after
+// reading a byte from it as `rbsh ... | head -c1` does.
+fn run_closing_stdout(args: &[&str], input: &str) -> ExitStatus {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rbsh"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    // rbsh reads the whole input before it prints anything.
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+    stdout.read_exact(&mut [0]).unwrap();
+    drop(stdout);
+
+    child.wait().unwrap()
+}
+
+macro_rules! assert_stdout {
+    ($script: expr, $stdout: expr) => {
+        assert_eq!(run($script), $stdout)
+    };
+}
+
+#[test]
+fn local() {
+    // an exported variable is still exported after the function returns
+    assert_stdout!("f() { local HOME; }; f; env | grep -c ^HOME=", "1\n");
+    assert_stdout!(
+        "f() { local HOME=x; env | grep ^HOME=; }; f; env | grep -c ^HOME=x",
+        "HOME=x\n0\n"
+    );
+    assert_stdout!(
+        "a=outer; f() { local a=(1 '2 3'); echo ${#a[@]} ${a[1]}; }; f; echo $a",
+        "2 2 3\nouter\n"
+    );
+}
+
+#[test]
+fn command_substitution() {
+    assert_stdout!("echo $(echo a) `echo b`; echo c", "a b\nc\n");
+}
+
+#[test]
+fn arithmetic_expansion() {
+    assert_stdout!(
+        "a=(5 6); s=abc; echo $(( ${a[0]} + 1 )) $(( ${#s} + 1 )) $(( ${x:-0} + 1 ))",
+        "6 4 1\n"
+    );
+    assert_stdout!("echo $(( $(echo 4) + 1 )) $(( `echo 4` + 1 ))", "5 5\n");
+    assert_stdout!(
+        "n=2; for ((i = 0; i < $n; i++)); do echo $i; done",
+        "0\n1\n"
+    );
+}
+
+#[test]
+fn redirect_expansion_error() {
+    assert_stdout!("echo a > $((1/0)); echo $?", "1\n");
+    assert_stdout!("echo a >&$((1/0)); echo $?", "1\n");
+    assert_stdout!("cat <<< $((1/0)); echo $?", "1\n");
+}
+
+#[test]
+fn parameter_expansion() {
+    // a shell which is not interactive exits
+    assert_stdout!("echo ${u:?message}; echo after", "");
+    assert_stdout!("(echo ${u:?message}); echo $?", "1\n");
+
+    assert_stdout!(
+        "f() { echo $#; }; f ${u:-\"a b\"}; f ${u:-a b}; x=1; f ${x:+'a b'}",
+        "1\n2\n1\n"
+    );
+}
+
+#[test]
+fn process_substitution() {
+    // the shell must not be killed by SIGCHLD of a substitution after it closes the signal handler
+    for script in ["/bin/echo <(:)", "diff <(printf a) <(printf a)", "x=(<(:))"] {
+        for _ in 0..50 {
+            assert_eq!(execute(script).status.code(), Some(0), "{script}");
+        }
+    }
+}
+
+#[test]
+fn function() {
+    assert_stdout!("f() { echo f; }; f", "f\n");
+    assert_stdout!("function f { echo f; }; f", "f\n");
+    assert_stdout!("function f\n{\necho f\n}\nf", "f\n");
+    assert_stdout!("function f() { echo f $1; }; f a", "f a\n");
+}
+
+#[test]
+fn connecter() {
+    // the rest of an && or || list is not run after return, break or continue
+    assert_stdout!("f() { return 1 || echo leaked; }; f; echo $?", "1\n");
+    assert_stdout!(
+        "f() { true && return 2 && echo leaked; }; f; echo $?",
+        "2\n"
+    );
+    assert_stdout!(
+        "for i in 1 2; do echo $i; break || echo leaked; done",
+        "1\n"
+    );
+    assert_stdout!(
+        "for i in 1 2; do echo $i; continue && echo leaked; done",
+        "1\n2\n"
+    );
+}
+
+#[test]
+fn array() {
+    assert_stdout!(
+        "declare -a q=(1 '2 3'); declare -p q",
+        "declare -a q=([0]=\"1\" [1]=\"2 3\")\n"
+    );
+    assert_stdout!(
+        "declare -A m=([k]=v [j]='a b'); declare -p m",
+        "declare -A m=([j]=\"a b\" [k]=\"v\")\n"
+    );
+    assert_stdout!("a=(1 2 3); a+=(4); echo ${a[@]}", "1 2 3 4\n");
+    assert_stdout!("s=ab; s+=cd; echo $s", "abcd\n");
+    assert_stdout!(
+        "a=(1 2); echo $((a[1] + 1)); ((a[0] += 5)); echo ${a[0]}",
+        "3\n6\n"
+    );
+}
+
+#[test]
+fn coproc() {
+    // the job of the coprocess is printed before the output
+    let stdout = run(
+        "coproc cat; echo hello >&${COPROC[1]}; read line <&${COPROC[0]}; echo $line
+        coproc P { read x; echo got $x; }; echo a >&${P[1]}; read line <&${P[0]}; echo $line",
+    );
+    let lines = stdout.lines().filter(|line| !line.starts_with('['));
+    assert_eq!(lines.collect::<Vec<_>>(), vec!["hello", "got a"]);
+}
+
+#[test]
+fn select() {
+    assert_stdout!(
+        "select x in a b; do echo $x; done < /dev/null; echo $?",
+        "1\n"
+    );
+    assert_stdout!(
+        "echo 2 | { select x in a b; do echo $x $REPLY; break; done; echo $?; }",
+        "b 2\n0\n"
+    );
+}
+
+#[test]
+fn dump_ast_to_closed_pipe() {
+    // the output is longer than the pipe buffer, so rbsh writes to the closed pipe
+    let input = format!("echo{}\n", " a".repeat(40000));
+    let status = run_closing_stdout(&["--dump-ast"], &input);
+    assert_eq!(status.signal(), Some(SIGPIPE as i32));
+    let status = run_closing_stdout(&["--dump-ast=tree"], &input);
+    assert_eq!(status.signal(), Some(SIGPIPE as i32));
+}
+
+#[test]
+fn format_to_closed_pipe() {
+    let input = format!("echo{}\n", " a".repeat(40000));
+    let status = run_closing_stdout(&["fmt"], &input);
+    assert_eq!(status.signal(), Some(SIGPIPE as i32));
+}
+
+#[test]
+fn check_to_closed_pipe() {
+    // every line is a syntax error, which is printed as JSON
+    let input = ")\n".repeat(1000);
+    let status = run_closing_stdout(&["-n", "--error-format=json"], &input);
+    assert_eq!(status.signal(), Some(SIGPIPE as i32));
+}
+
+#[test]
+fn stdin_source_name() {
+    // Returns what rbsh prints for a syntax error in the standard input.
+    let errors = |args: &[&str]| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_rbsh"))
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(b"echo (\n").unwrap();
+        let output = child.wait_with_output().unwrap();
+        String::from_utf8([output.stdout, output.stderr].concat()).unwrap()
+    };
+    assert!(errors(&["-n"]).starts_with("<stdin>:1:7: syntax error"));
+    assert!(errors(&["-n", "--error-format=json"]).contains(r#""file":"<stdin>""#));
+    assert!(errors(&[]).starts_with("<stdin>:1:7: syntax error"));
+}